@@ -1,36 +1,1178 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tauri::Emitter;
 use tauri_plugin_cli::CliExt;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_opener::OpenerExt;
+use tokio::sync::Notify;
 
-use crate::diff_parser::{self, DiffFile};
-use crate::repo_info::{self, RepoInfo};
+use crate::comments::{self, Comment};
+use crate::config::{self, RepoConfig};
+use crate::conflicts::{self, ConflictFile};
+use crate::coverage;
+use crate::diff_parser::{
+    self, ChangedFile, DiffFile, DiffOptions, DiffTarget, FileStatus, SshOptions, Vcs,
+    WhitespaceMode,
+};
+use crate::editor;
+use crate::export;
+use crate::file_tree::{self, TreeNode};
+use crate::forge;
+use crate::outline::{self, FileOutline};
+use crate::repo_info::{
+    self, CommitMessage, Identity, NormalizedRange, Positional, Refs, RemoteEntry, RepoInfo,
+    StashEntry,
+};
+use crate::review_ignore;
+use crate::search::{self, Match, SearchFilter, WsIssue};
+use crate::side_by_side::{self, SideBySideRow};
+use crate::staging;
+use crate::stats::{self, ChangeSummary, ExtStat};
 
 #[derive(Serialize)]
 pub struct StartupArgs {
     pub range: Option<String>,
     pub remote: Option<String>,
+    pub git_dir: Option<String>,
+    /// From `.great-review.toml`; the CLI has no flags for these.
+    pub context_lines: Option<u32>,
+    pub whitespace: WhitespaceMode,
+    /// `--diff-file`: a patch to review in place of the repository's changes.
+    pub diff_file: Option<String>,
 }
 
-#[tauri::command]
-pub fn get_diff(range: Option<String>, remote: Option<String>) -> Result<Vec<DiffFile>, String> {
-    let diff_text = if let Some(ref r) = remote {
-        diff_parser::run_remote_git_diff(r, range.as_deref())?
+#[derive(Serialize)]
+pub struct RemoteBundle {
+    pub repo_info: RepoInfo,
+    pub files: Vec<DiffFile>,
+}
+
+/// What `get_diff` returns. `raw` is the unified diff exactly as git produced it,
+/// before any `status_filter`, and only present when asked for with
+/// `DiffOptions::include_raw`.
+#[derive(Serialize)]
+pub struct DiffResult {
+    pub files: Vec<DiffFile>,
+    pub raw: Option<String>,
+    /// Files left out of `files` by the repository's `.great-review-ignore`.
+    pub ignored_count: usize,
+}
+
+/// What `get_diff_cancellable` returns: the `DiffResult` `get_diff` would, with the
+/// request id beside it. `get_diff` itself has no id to return, since the caller picks
+/// it up front so that `cancel_diff` can be called while the diff runs.
+#[derive(Serialize)]
+pub struct CancellableDiff {
+    pub request_id: u64,
+    #[serde(flatten)]
+    pub result: DiffResult,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DiffStreamComplete {
+    pub total_files: usize,
+    /// Files dropped by `.great-review-ignore`, as in `DiffResult::ignored_count`.
+    pub ignored_count: usize,
+}
+
+/// Emitted as `parse-progress` after each `diff --git` block of a streamed diff.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ParseProgress {
+    pub files_done: usize,
+    pub files_total: usize,
+}
+
+/// Upper bound on cached diffs; the least recently used entry is evicted first.
+const DIFF_CACHE_CAPACITY: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+struct DiffCacheKey {
+    repo_path: String,
+    options: DiffOptions,
+    remote: Option<String>,
+    head_sha: String,
+}
+
+impl DiffCacheKey {
+    fn same_repo(&self, other: &DiffCacheKey) -> bool {
+        self.repo_path == other.repo_path && self.remote == other.remote
+    }
+}
+
+/// In-memory LRU cache of parsed diffs, registered as Tauri managed state.
+///
+/// Only range diffs are cached: working-tree diffs depend on uncommitted changes
+/// that the HEAD sha does not capture.
+#[derive(Default)]
+pub struct DiffCache {
+    entries: Mutex<VecDeque<(DiffCacheKey, Vec<DiffFile>)>>,
+}
+
+impl DiffCache {
+    fn get(&self, key: &DiffCacheKey) -> Option<Vec<DiffFile>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        // HEAD moved since these entries were stored, so they may be stale
+        entries.retain(|(k, _)| !k.same_repo(key) || k.head_sha == key.head_sha);
+
+        let pos = entries.iter().position(|(k, _)| k == key)?;
+        let entry = entries.remove(pos)?;
+        let files = entry.1.clone();
+        entries.push_front(entry);
+        Some(files)
+    }
+
+    fn insert(&self, key: DiffCacheKey, files: Vec<DiffFile>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(k, _)| k != &key);
+        entries.push_front((key, files));
+        entries.truncate(DIFF_CACHE_CAPACITY);
+    }
+
+    fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+/// In-flight cancellable diffs keyed by the frontend's request id, registered as Tauri
+/// managed state.
+#[derive(Default)]
+pub struct DiffJobs {
+    jobs: Mutex<HashMap<u64, Arc<Notify>>>,
+}
+
+impl DiffJobs {
+    fn register(&self, request_id: u64) -> Arc<Notify> {
+        let cancel = Arc::new(Notify::new());
+        self.jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(request_id, cancel.clone());
+        cancel
+    }
+
+    fn finish(&self, request_id: u64) {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&request_id);
+    }
+
+    /// Returns false when no diff with that id is running (it may have just finished).
+    fn cancel(&self, request_id: u64) -> bool {
+        let job = self
+            .jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&request_id);
+        match job {
+            Some(cancel) => {
+                // A stored permit covers the race where the process has not spawned yet
+                cancel.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Runs blocking git, ssh or curl work on the blocking thread pool, so an async
+/// command never stalls the runtime while the process runs.
+async fn run_blocking<T, F>(f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| Err(format!("Background task failed: {}", e)))
+}
+
+/// The repository to diff, checked against `range` (a bare one needs a commit range).
+async fn locate_diff_repo(
+    git_dir: Option<&str>,
+    range: Option<&str>,
+) -> Result<repo_info::RepoRoot, String> {
+    let git_dir = git_dir.map(str::to_string);
+    let range = range.map(str::to_string);
+    run_blocking(move || {
+        let repo = repo_info::locate_repo(git_dir.as_deref())?;
+        repo.check_diff_range(range.as_deref())?;
+        Ok(repo)
+    })
+    .await
+}
+
+async fn load_diff_text(
+    options: &DiffOptions,
+    remote: Option<&str>,
+    git_dir: Option<&str>,
+    ssh: &SshOptions,
+) -> Result<String, String> {
+    if let Some(r) = remote {
+        diff_parser::run_remote_git_diff_async(r, options, ssh).await
+    } else {
+        let repo = locate_diff_repo(git_dir, options.range.as_deref()).await?;
+        if options.fetch_before_diff {
+            repo_info::fetch_range_refs(
+                options.range.as_deref(),
+                &repo.path,
+                repo_info::FETCH_TIMEOUT,
+            )
+            .await?;
+        }
+        diff_parser::run_git_diff_async(options, &repo.path).await
+    }
+}
+
+async fn load_diff_text_cancellable(
+    options: &DiffOptions,
+    remote: Option<&str>,
+    git_dir: Option<&str>,
+    ssh: &SshOptions,
+    cancel: &Notify,
+) -> Result<String, String> {
+    if let Some(r) = remote {
+        diff_parser::run_remote_git_diff_cancellable(r, options, ssh, cancel).await
+    } else {
+        let repo = locate_diff_repo(git_dir, options.range.as_deref()).await?;
+        diff_parser::run_git_diff_cancellable(options, &repo.path, cancel).await
+    }
+}
+
+/// What `get_diff` does to parsed files before returning them: for a local work tree
+/// `abs_path` is set and `.great-review-ignore` applied, then `status_filter` (when not
+/// empty). Returns the files and how many the ignore rules dropped.
+fn finish_files(
+    mut files: Vec<DiffFile>,
+    work_tree: Option<&str>,
+    status_filter: &[FileStatus],
+) -> Result<(Vec<DiffFile>, usize), String> {
+    let mut ignored_count = 0;
+    if let Some(work_tree) = work_tree {
+        diff_parser::set_abs_paths(&mut files, work_tree);
+        let rules = review_ignore::load_ignore_rules(work_tree)?;
+        (files, ignored_count) = rules.filter(files);
+    }
+    Ok((
+        diff_parser::filter_by_status(files, status_filter),
+        ignored_count,
+    ))
+}
+
+/// The `DiffResult` for parsed files, through `finish_files`; `raw` is dropped unless
+/// `include_raw` is set.
+fn diff_result(
+    files: Vec<DiffFile>,
+    raw: Option<String>,
+    work_tree: Option<&str>,
+    options: &DiffOptions,
+    status_filter: &[FileStatus],
+) -> Result<DiffResult, String> {
+    let (files, ignored_count) = finish_files(files, work_tree, status_filter)?;
+    Ok(DiffResult {
+        files,
+        raw: raw.filter(|_| options.include_raw),
+        ignored_count,
+    })
+}
+
+/// The work tree of the local repository a diff is taken in; `None` for remotes and
+/// bare repositories.
+async fn local_work_tree(
+    remote: Option<&str>,
+    git_dir: Option<&str>,
+) -> Result<Option<String>, String> {
+    if remote.is_some() {
+        return Ok(None);
+    }
+    let git_dir = git_dir.map(str::to_string);
+    let repo = run_blocking(move || repo_info::locate_repo(git_dir.as_deref())).await?;
+    Ok((!repo.bare).then_some(repo.path))
+}
+
+fn parse_diff_text(diff_text: &str, options: &DiffOptions) -> Vec<DiffFile> {
+    let parse = if options.word_diff {
+        diff_parser::parse_word_diff
+    } else {
+        diff_parser::parse_unified_diff
+    };
+    let files = if options.strip_ansi {
+        parse(&diff_parser::strip_ansi(diff_text))
     } else {
-        let repo_root = repo_info::find_repo_root()?;
-        diff_parser::run_git_diff(range.as_deref(), &repo_root)?
+        parse(diff_text)
+    };
+    let files = if cfg!(windows) || options.normalize_backslashes {
+        diff_parser::normalize_path_separators(files)
+    } else {
+        files
+    };
+    let files = diff_parser::apply_binary_overrides(files, &options.binary_overrides);
+    if options.merge_duplicate_files {
+        diff_parser::merge_duplicate_files(files)
+    } else {
+        files
+    }
+}
+
+/// Reads a patch given with `--diff-file`. Invalid UTF-8 is replaced rather than
+/// rejected, as it is for git's own output.
+fn read_diff_file(path: &str) -> Result<String, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read diff file '{}': {}", path, e))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn diff_cache_key(
+    options: &DiffOptions,
+    remote: Option<&str>,
+    git_dir: Option<&str>,
+    ssh: &SshOptions,
+) -> Result<DiffCacheKey, String> {
+    let (repo_path, head_sha) = match remote {
+        Some(r) => (String::new(), repo_info::get_remote_head_sha(r, ssh)?),
+        None => {
+            let repo = repo_info::locate_repo(git_dir)?;
+            let head_sha = repo_info::get_head_sha(&repo.path)?;
+            (repo.path, head_sha)
+        }
+    };
+    Ok(DiffCacheKey {
+        repo_path,
+        options: options.clone(),
+        remote: remote.map(str::to_string),
+        head_sha,
+    })
+}
+
+#[tauri::command]
+pub async fn get_diff(
+    cache: tauri::State<'_, DiffCache>,
+    ssh: tauri::State<'_, SshOptions>,
+    options: DiffOptions,
+    remote: Option<String>,
+    git_dir: Option<String>,
+    vcs: Option<Vcs>,
+    status_filter: Option<Vec<FileStatus>>,
+) -> Result<DiffResult, String> {
+    let status_filter = status_filter.unwrap_or_default();
+    let result = |files: Vec<DiffFile>, raw: Option<String>, work_tree: Option<&str>| {
+        diff_result(files, raw, work_tree, &options, &status_filter)
+    };
+
+    // Only git itself can produce the lines of a file it found binary
+    if options.forces_text() && (options.diff_file.is_some() || vcs == Some(Vcs::Hg)) {
+        return Err(diff_parser::FORCED_TEXT_LOCAL_ONLY.to_string());
+    }
+
+    if let Some(ref path) = options.diff_file {
+        let diff_text = read_diff_file(path)?;
+        let files = parse_diff_text(&diff_text, &options);
+        return result(files, Some(diff_text), None);
+    }
+
+    if vcs == Some(Vcs::Hg) {
+        // Only the range applies to Mercurial; the other options are git flags
+        if remote.is_some() {
+            return Err("Mercurial diffs are only supported for local repositories".to_string());
+        }
+        if options.target != DiffTarget::Range {
+            return Err("Mercurial diffs only support commit ranges".to_string());
+        }
+        let range = options.range.clone();
+        let (root, diff_text) = run_blocking(move || {
+            let root = repo_info::find_hg_root()?;
+            let diff_text = diff_parser::run_hg_diff(range.as_deref(), &root)?;
+            Ok((root, diff_text))
+        })
+        .await?;
+        let files = parse_diff_text(&diff_text, &options);
+        return result(files, Some(diff_text), Some(&root));
+    }
+
+    let work_tree = local_work_tree(remote.as_deref(), git_dir.as_deref()).await?;
+
+    // Working-tree and index diffs are never cached; see `DiffCache`. That includes a
+    // single revision, which git compares with the working tree. Neither are diffs
+    // that fetch first, since the fetched refs are not part of the key, nor requests
+    // for the raw text, which the cache does not keep.
+    let key = match options.range {
+        Some(ref range)
+            if repo_info::is_commit_range(range)
+                && !options.fetch_before_diff
+                && !options.include_raw
+                && options.target == DiffTarget::Range =>
+        {
+            let (options, remote, git_dir) = (options.clone(), remote.clone(), git_dir.clone());
+            let ssh = ssh.inner().clone();
+            run_blocking(move || {
+                diff_cache_key(&options, remote.as_deref(), git_dir.as_deref(), &ssh)
+            })
+            .await
+            .ok()
+        }
+        _ => None,
     };
+
+    // The cache holds unfiltered files, so one entry serves every filter
+    if let Some(files) = key.as_ref().and_then(|k| cache.get(k)) {
+        return result(files, None, work_tree.as_deref());
+    }
+
+    let diff_text = load_diff_text(&options, remote.as_deref(), git_dir.as_deref(), &ssh).await?;
+    let files = parse_diff_text(&diff_text, &options);
+
+    if let Some(key) = key {
+        cache.insert(key, files.clone());
+    }
+    result(files, Some(diff_text), work_tree.as_deref())
+}
+
+/// Like `get_diff`, but the git (or ssh) process can be killed with `cancel_diff`
+/// using the same `request_id`. Results are not cached.
+#[tauri::command]
+pub async fn get_diff_cancellable(
+    jobs: tauri::State<'_, DiffJobs>,
+    ssh: tauri::State<'_, SshOptions>,
+    request_id: u64,
+    options: DiffOptions,
+    remote: Option<String>,
+    git_dir: Option<String>,
+    status_filter: Option<Vec<FileStatus>>,
+) -> Result<CancellableDiff, String> {
+    let work_tree = local_work_tree(remote.as_deref(), git_dir.as_deref()).await?;
+    let cancel = jobs.register(request_id);
+    let result = load_diff_text_cancellable(
+        &options,
+        remote.as_deref(),
+        git_dir.as_deref(),
+        &ssh,
+        &cancel,
+    )
+    .await;
+    jobs.finish(request_id);
+
+    let diff_text = result?;
+    let files = parse_diff_text(&diff_text, &options);
+    let status_filter = status_filter.unwrap_or_default();
+    Ok(CancellableDiff {
+        request_id,
+        result: diff_result(
+            files,
+            Some(diff_text),
+            work_tree.as_deref(),
+            &options,
+            &status_filter,
+        )?,
+    })
+}
+
+#[tauri::command]
+pub fn cancel_diff(jobs: tauri::State<'_, DiffJobs>, request_id: u64) -> bool {
+    jobs.cancel(request_id)
+}
+
+/// Files changed by `range` without their hunks, for a fast first render of the tree.
+#[tauri::command]
+pub async fn get_changed_files(
+    ssh: tauri::State<'_, SshOptions>,
+    range: Option<String>,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<Vec<ChangedFile>, String> {
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            diff_parser::run_remote_git_name_status(r, range.as_deref(), &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo.check_diff_range(range.as_deref())?;
+            diff_parser::run_git_name_status(range.as_deref(), &repo.path)
+        }
+    })
+    .await
+}
+
+/// Each file's hunk headers without line content, for a collapsed outline view.
+#[tauri::command]
+pub async fn get_diff_outline(
+    ssh: tauri::State<'_, SshOptions>,
+    range: Option<String>,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<Vec<FileOutline>, String> {
+    let mut options = DiffOptions::new();
+    options.range = range;
+    let diff_text = load_diff_text(&options, remote.as_deref(), git_dir.as_deref(), &ssh).await?;
+    Ok(outline::outline(&parse_diff_text(&diff_text, &options)))
+}
+
+/// Unmerged paths of an in-progress merge and where their conflict markers are.
+#[tauri::command]
+pub fn get_conflicts(git_dir: Option<String>) -> Result<Vec<ConflictFile>, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    conflicts::find_conflicts(&repo.path)
+}
+
+#[tauri::command]
+pub fn list_stashes(git_dir: Option<String>) -> Result<Vec<StashEntry>, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    repo_info::list_stashes(&repo.path)
+}
+
+/// The changes saved in a stash entry, `stash@{0}` unless `stash_ref` names another.
+#[tauri::command]
+pub fn get_stash_diff(
+    stash_ref: Option<String>,
+    git_dir: Option<String>,
+) -> Result<Vec<DiffFile>, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    let stash_ref = stash_ref.as_deref().unwrap_or(diff_parser::DEFAULT_STASH);
+    let diff_text = diff_parser::run_stash_diff(stash_ref, &repo.path)?;
     Ok(diff_parser::parse_unified_diff(&diff_text))
 }
 
+/// What `head` adds on top of `base`; see `diff_parser::diff_branches`.
+#[tauri::command]
+pub async fn get_branch_diff(
+    ssh: tauri::State<'_, SshOptions>,
+    base: String,
+    head: String,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<Vec<DiffFile>, String> {
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            diff_parser::diff_remote_branches(r, &base, &head, &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            diff_parser::diff_branches(&base, &head, &repo.path)
+        }
+    })
+    .await
+}
+
+/// Compares two files on disk, e.g. a downloaded artifact against a checked-in one.
 #[tauri::command]
-pub fn get_repo_info_cmd(remote: Option<String>) -> Result<RepoInfo, String> {
-    if let Some(ref r) = remote {
-        repo_info::get_remote_repo_info(r)
+pub fn diff_files(left: String, right: String) -> Result<Vec<DiffFile>, String> {
+    let diff_text = diff_parser::diff_no_index(&left, &right)?;
+    Ok(diff_parser::parse_unified_diff(&diff_text))
+}
+
+/// Narrows `options` to one file. Both sides of a rename go in the pathspec, since
+/// git only pairs a rename when it sees the old and the new path.
+fn file_diff_options(options: &DiffOptions, path: &str, old_path: Option<&str>) -> DiffOptions {
+    let mut options = options.clone();
+    options.pathspecs = match old_path {
+        Some(old) if old != path => vec![old.to_string(), path.to_string()],
+        _ => vec![path.to_string()],
+    };
+    options
+}
+
+/// The hunks of a single file, for loading files lazily as they are expanded. When
+/// `old_path` is not known it is looked up with `--name-status` first so renames are
+/// still detected.
+#[tauri::command]
+pub async fn get_file_diff(
+    ssh: tauri::State<'_, SshOptions>,
+    path: String,
+    old_path: Option<String>,
+    options: DiffOptions,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<DiffFile, String> {
+    let old_path = match old_path {
+        Some(old) => Some(old),
+        None => {
+            let range = options.range.clone();
+            let (remote, git_dir) = (remote.clone(), git_dir.clone());
+            let ssh = ssh.inner().clone();
+            let changed = run_blocking(move || match remote {
+                Some(ref r) => diff_parser::run_remote_git_name_status(r, range.as_deref(), &ssh),
+                None => {
+                    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+                    diff_parser::run_git_name_status(range.as_deref(), &repo.path)
+                }
+            })
+            .await?;
+            changed
+                .into_iter()
+                .find(|f| f.path == path)
+                .and_then(|f| f.old_path)
+        }
+    };
+
+    let options = file_diff_options(&options, &path, old_path.as_deref());
+    let diff_text = load_diff_text(&options, remote.as_deref(), git_dir.as_deref(), &ssh).await?;
+    parse_diff_text(&diff_text, &options)
+        .into_iter()
+        .find(|f| f.path == path)
+        .ok_or_else(|| format!("No changes to '{}' in this diff", path))
+}
+
+#[tauri::command]
+pub fn clear_diff_cache(cache: tauri::State<'_, DiffCache>) {
+    cache.clear();
+}
+
+/// Streaming variant of `get_diff`: emits a `diff-file` event per parsed file and a
+/// final `diff-complete` event, so the UI can render early files while the rest parse.
+/// The files are the ones `get_diff` returns for the same arguments (Mercurial aside).
+#[tauri::command]
+pub async fn get_diff_stream(
+    app: tauri::AppHandle,
+    ssh: tauri::State<'_, SshOptions>,
+    options: DiffOptions,
+    remote: Option<String>,
+    git_dir: Option<String>,
+    status_filter: Option<Vec<FileStatus>>,
+) -> Result<(), String> {
+    let status_filter = status_filter.unwrap_or_default();
+    let (diff_text, work_tree) = match options.diff_file {
+        Some(_) if options.forces_text() => {
+            return Err(diff_parser::FORCED_TEXT_LOCAL_ONLY.to_string());
+        }
+        Some(ref path) => (read_diff_file(path)?, None),
+        None => {
+            let work_tree = local_work_tree(remote.as_deref(), git_dir.as_deref()).await?;
+            let diff_text =
+                load_diff_text(&options, remote.as_deref(), git_dir.as_deref(), &ssh).await?;
+            (diff_text, work_tree)
+        }
+    };
+
+    // Splitting first is the cheap pre-scan that gives the progress total. Duplicate
+    // blocks for one path can be far apart, so merging them needs the whole diff.
+    let chunks = if options.merge_duplicate_files {
+        vec![diff_text.as_str()]
+    } else {
+        diff_parser::split_file_chunks(&diff_text)
+    };
+    let files_total = chunks.len();
+    let mut total_files = 0;
+    let mut ignored_count = 0;
+    for (done, chunk) in chunks.into_iter().enumerate() {
+        let files = parse_diff_text(chunk, &options);
+        let (files, ignored) = finish_files(files, work_tree.as_deref(), &status_filter)?;
+        ignored_count += ignored;
+        for file in files {
+            app.emit("diff-file", &file)
+                .map_err(|e| format!("Failed to emit diff-file event: {}", e))?;
+            total_files += 1;
+        }
+        let progress = ParseProgress {
+            files_done: done + 1,
+            files_total,
+        };
+        app.emit("parse-progress", &progress)
+            .map_err(|e| format!("Failed to emit parse-progress event: {}", e))?;
+    }
+
+    let complete = DiffStreamComplete {
+        total_files,
+        ignored_count,
+    };
+    app.emit("diff-complete", complete)
+        .map_err(|e| format!("Failed to emit diff-complete event: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_repo_info_cmd(
+    ssh: tauri::State<'_, SshOptions>,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<RepoInfo, String> {
+    if let Some(r) = remote {
+        let ssh = ssh.inner().clone();
+        run_blocking(move || repo_info::get_remote_repo_info(&r, &ssh)).await
     } else {
-        let repo_root = repo_info::find_repo_root()?;
-        repo_info::get_repo_info(&repo_root)
+        run_blocking(move || {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo_info::get_repo_info(&repo.path)
+        })
+        .await
     }
 }
 
+#[tauri::command]
+pub fn get_merge_base(a: String, b: String, git_dir: Option<String>) -> Result<String, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    repo_info::get_merge_base(&a, &b, &repo.path)
+}
+
+/// Content of a blob such as `DiffFile::old_blob`; see `repo_info::get_blob`.
+#[tauri::command]
+pub fn get_blob_content(sha: String, git_dir: Option<String>) -> Result<String, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    repo_info::get_blob(&sha, &repo.path)
+}
+
+#[tauri::command]
+pub async fn get_refs(
+    ssh: tauri::State<'_, SshOptions>,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<Refs, String> {
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            repo_info::list_remote_refs(r, &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo_info::list_refs(&repo.path)
+        }
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn resolve_ref(
+    ssh: tauri::State<'_, SshOptions>,
+    ref_str: String,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<String, String> {
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            repo_info::resolve_remote_ref(r, &ref_str, &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo_info::resolve_ref(&ref_str, &repo.path)
+        }
+    })
+    .await
+}
+
+/// The branch to compare against by default; see `repo_info::get_default_branch`.
+#[tauri::command]
+pub async fn get_default_branch(
+    ssh: tauri::State<'_, SshOptions>,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<String, String> {
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            repo_info::get_remote_default_branch(r, &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo_info::get_default_branch(&repo.path)
+        }
+    })
+    .await
+}
+
+/// Commit messages for the range under review: only the tip's, or with `whole_range`
+/// every commit in the range.
+#[tauri::command]
+pub async fn get_commit_message(
+    ssh: tauri::State<'_, SshOptions>,
+    commit: String,
+    whole_range: Option<bool>,
+    first_parent: Option<bool>,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<Vec<CommitMessage>, String> {
+    let whole_range = whole_range.unwrap_or(false);
+    let first_parent = first_parent.unwrap_or(false);
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            repo_info::get_remote_commit_messages(r, &commit, whole_range, first_parent, &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo_info::get_commit_messages(&commit, whole_range, first_parent, &repo.path)
+        }
+    })
+    .await
+}
+
+/// Number of commits in `range`; see `repo_info::count_commits`.
+#[tauri::command]
+pub async fn count_commits(
+    ssh: tauri::State<'_, SshOptions>,
+    range: String,
+    first_parent: Option<bool>,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<u32, String> {
+    let first_parent = first_parent.unwrap_or(false);
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            repo_info::count_remote_commits(r, &range, first_parent, &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo_info::count_commits(&range, first_parent, &repo.path)
+        }
+    })
+    .await
+}
+
+/// Author of the last commit in `range`; see `repo_info::get_tip_author`.
+#[tauri::command]
+pub async fn get_tip_author(
+    ssh: tauri::State<'_, SshOptions>,
+    range: String,
+    remote: Option<String>,
+    git_dir: Option<String>,
+) -> Result<Identity, String> {
+    let ssh = ssh.inner().clone();
+    run_blocking(move || {
+        if let Some(ref r) = remote {
+            repo_info::get_remote_tip_author(r, &range, &ssh)
+        } else {
+            let repo = repo_info::locate_repo(git_dir.as_deref())?;
+            repo_info::get_tip_author(&range, &repo.path)
+        }
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn normalize_range(range: String, git_dir: Option<String>) -> Result<NormalizedRange, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    repo_info::normalize_range(&range, &repo.path)
+}
+
+#[tauri::command]
+pub fn get_remotes(git_dir: Option<String>) -> Result<Vec<RemoteEntry>, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    repo_info::list_remotes(&repo.path)
+}
+
+#[tauri::command]
+pub async fn apply_patch(
+    diff_text: String,
+    check_only: bool,
+    git_dir: Option<String>,
+) -> Result<(), String> {
+    run_blocking(move || {
+        let repo = repo_info::locate_repo(git_dir.as_deref())?;
+        diff_parser::apply_patch(&diff_text, &repo.path, check_only)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn search_in_diff(
+    files: Vec<DiffFile>,
+    query: String,
+    case_sensitive: bool,
+    filter: Option<SearchFilter>,
+) -> Vec<Match> {
+    search::search_diff(&files, &query, case_sensitive, filter.unwrap_or_default())
+}
+
+/// Added lines ending in whitespace; see `search::find_trailing_whitespace`.
+#[tauri::command]
+pub fn find_trailing_whitespace(files: Vec<DiffFile>) -> Vec<WsIssue> {
+    search::find_trailing_whitespace(&files)
+}
+
+/// Copies one hunk, as unified-diff text, to the clipboard and returns the text.
+#[tauri::command]
+pub fn copy_hunk(
+    app: tauri::AppHandle,
+    file_index: usize,
+    hunk_index: usize,
+    files: Vec<DiffFile>,
+) -> Result<String, String> {
+    let hunk = files
+        .get(file_index)
+        .ok_or_else(|| format!("No file at index {}", file_index))?
+        .hunks
+        .get(hunk_index)
+        .ok_or_else(|| format!("No hunk at index {} in file {}", hunk_index, file_index))?;
+
+    let text = diff_parser::format_hunk(hunk);
+    app.clipboard()
+        .write_text(text.clone())
+        .map_err(|e| format!("Failed to write to clipboard: {}", e))?;
+    Ok(text)
+}
+
+/// `base_content` with only the chosen hunks of `file` applied, for hunk-level staging.
+#[tauri::command]
+pub fn apply_selected_hunks(
+    file: DiffFile,
+    hunk_indices: Vec<usize>,
+    base_content: String,
+) -> Result<String, String> {
+    staging::apply_selected_hunks(&file, &hunk_indices, &base_content)
+}
+
+/// Opens a reviewed file in `$VISUAL`/`$EDITOR` at `line_no`, or with the system's
+/// default application when neither is set.
+#[tauri::command]
+pub fn open_in_editor(
+    app: tauri::AppHandle,
+    path: String,
+    line_no: Option<u32>,
+    git_dir: Option<String>,
+) -> Result<(), String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    let file = editor::resolve_in_repo(&repo.path, &path)?;
+
+    if editor::open_with_editor(&file, line_no)? {
+        return Ok(());
+    }
+    app.opener()
+        .open_path(file.display().to_string(), None::<&str>)
+        .map_err(|e| format!("Failed to open '{}': {}", path, e))
+}
+
+#[tauri::command]
+pub fn export_review_markdown(files: Vec<DiffFile>) -> String {
+    export::export_markdown(&files)
+}
+
+/// A self-contained HTML page of the review; see `export::export_html`.
+#[tauri::command]
+pub fn export_review_html(files: Vec<DiffFile>, repo_info: RepoInfo) -> String {
+    export::export_html(&files, &repo_info)
+}
+
+/// Nests `files` by directory for the sidebar; see `file_tree::build_file_tree`.
+#[tauri::command]
+pub fn get_file_tree(files: Vec<DiffFile>) -> TreeNode {
+    file_tree::build_file_tree(&files)
+}
+
+#[tauri::command]
+pub fn get_diff_summary(files: Vec<DiffFile>) -> ChangeSummary {
+    stats::summarize(&files)
+}
+
+/// Marks which added lines the LCOV report at `lcov_path` covers; see
+/// `coverage::annotate_coverage`.
+#[tauri::command]
+pub fn annotate_coverage(
+    mut files: Vec<DiffFile>,
+    lcov_path: String,
+) -> Result<Vec<DiffFile>, String> {
+    coverage::annotate_coverage(&mut files, &lcov_path)?;
+    Ok(files)
+}
+
+/// Per-bin change intensity of `file` for a minimap; see `stats::change_density`.
+#[tauri::command]
+pub fn get_change_density(file: DiffFile, buckets: usize) -> Vec<u8> {
+    stats::change_density(&file, buckets)
+}
+
+/// The changeset's main language, e.g. for a "mostly Rust" label; see
+/// `stats::dominant_language`.
+#[tauri::command]
+pub fn get_dominant_language(files: Vec<DiffFile>) -> Option<String> {
+    stats::dominant_language(&files)
+}
+
+#[tauri::command]
+pub fn get_stats_by_extension(files: Vec<DiffFile>) -> Vec<ExtStat> {
+    stats::stats_by_extension(&files)
+}
+
+#[tauri::command]
+pub fn get_side_by_side(file: DiffFile) -> Vec<SideBySideRow> {
+    side_by_side::to_side_by_side(&file)
+}
+
+#[tauri::command]
+pub fn save_review(comments: Vec<Comment>, git_dir: Option<String>) -> Result<(), String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    comments::save_comments(&repo.path, &comments)
+}
+
+#[tauri::command]
+pub fn load_review(git_dir: Option<String>) -> Result<Vec<Comment>, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    comments::load_comments(&repo.path)
+}
+
+#[tauri::command]
+pub fn mark_file_viewed(
+    path: String,
+    new_blob: Option<String>,
+    viewed: bool,
+    git_dir: Option<String>,
+) -> Result<(), String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    comments::mark_file_viewed(&repo.path, &path, new_blob.as_deref(), viewed)
+}
+
+/// Paths of `files` marked viewed at their current content.
+#[tauri::command]
+pub fn load_viewed_files(
+    files: Vec<DiffFile>,
+    git_dir: Option<String>,
+) -> Result<Vec<String>, String> {
+    let repo = repo_info::locate_repo(git_dir.as_deref())?;
+    comments::viewed_paths(&repo.path, &files)
+}
+
+/// Moves saved comments onto their lines in a re-fetched diff.
+#[tauri::command]
+pub fn reanchor_review(comments: Vec<Comment>, files: Vec<DiffFile>) -> Vec<Comment> {
+    comments::reanchor_comments(&comments, &files)
+}
+
+/// Diff of a GitHub pull request, reviewed without a local clone.
+#[tauri::command]
+pub async fn get_github_pr_diff(
+    url: String,
+    token: Option<String>,
+) -> Result<Vec<DiffFile>, String> {
+    let (owner, repo, number) = forge::parse_github_pr_url(&url)?;
+    let diff_text =
+        run_blocking(move || forge::fetch_github_pr(&owner, &repo, number, token.as_deref()))
+            .await?;
+    Ok(diff_parser::parse_unified_diff(&diff_text))
+}
+
+/// Diff of a GitLab merge request; the instance is taken from the URL.
+#[tauri::command]
+pub async fn get_gitlab_mr_diff(
+    url: String,
+    token: Option<String>,
+) -> Result<Vec<DiffFile>, String> {
+    let (base_url, project, iid) = forge::parse_gitlab_mr_url(&url)?;
+    let diff_text = run_blocking(move || {
+        forge::fetch_gitlab_mr(&project, iid, token.as_deref(), Some(&base_url))
+    })
+    .await?;
+    Ok(diff_parser::parse_unified_diff(&diff_text))
+}
+
+/// Repo info and diff for a remote in one ssh round-trip. `range`, when set, replaces
+/// the one in `options`.
+#[tauri::command]
+pub async fn get_remote_bundle(
+    ssh: tauri::State<'_, SshOptions>,
+    remote: String,
+    range: Option<String>,
+    options: Option<DiffOptions>,
+) -> Result<RemoteBundle, String> {
+    let mut options = options.unwrap_or_default();
+    if range.is_some() {
+        options.range = range;
+    }
+    let ssh = ssh.inner().clone();
+    let bundle_options = options.clone();
+    let (repo_info, diff_text) =
+        run_blocking(move || repo_info::get_remote_bundle(&remote, &bundle_options, &ssh)).await?;
+    Ok(RemoteBundle {
+        repo_info,
+        files: parse_diff_text(&diff_text, &options),
+    })
+}
+
+/// One repository of a multi-repo review: a local path or an ssh remote
+/// (`host:/path`), with its own range.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RepoSpec {
+    pub path_or_remote: String,
+    pub range: Option<String>,
+}
+
+/// The diff of one `RepoSpec`. A repository that failed has `error` set and no
+/// files, so the others are still shown.
+#[derive(Serialize, Clone, Debug)]
+pub struct RepoDiff {
+    pub path_or_remote: String,
+    pub repo_info: Option<RepoInfo>,
+    pub files: Vec<DiffFile>,
+    pub error: Option<String>,
+}
+
+/// Existing local directories win over the `host:/path` remote syntax.
+fn is_remote_spec(path_or_remote: &str) -> bool {
+    !std::path::Path::new(path_or_remote).exists() && path_or_remote.contains(':')
+}
+
+async fn load_local_repo_diff(
+    path: &str,
+    range: Option<&str>,
+) -> Result<(RepoInfo, Vec<DiffFile>), String> {
+    let path = path.to_string();
+    let info = run_blocking(move || repo_info::get_repo_info(&path)).await?;
+    let root = repo_info::RepoRoot {
+        path: info.path.clone(),
+        bare: info.bare,
+    };
+    root.check_diff_range(range)?;
+
+    let mut options = DiffOptions::new();
+    options.range = range.map(str::to_string);
+    let diff_text = diff_parser::run_git_diff_async(&options, &root.path).await?;
+    Ok((info, diff_parser::parse_unified_diff(&diff_text)))
+}
+
+/// Loads every repository concurrently; each result lands in its `RepoDiff`, in the
+/// order of `repos`.
+async fn load_multi_diff(repos: Vec<RepoSpec>, ssh: SshOptions) -> Vec<RepoDiff> {
+    let tasks: Vec<_> = repos
+        .into_iter()
+        .map(|spec| {
+            let ssh = ssh.clone();
+            let handle = if is_remote_spec(&spec.path_or_remote) {
+                let spec = spec.clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    let mut options = DiffOptions::new();
+                    options.range = spec.range.clone();
+                    let (info, diff_text) =
+                        repo_info::get_remote_bundle(&spec.path_or_remote, &options, &ssh)?;
+                    Ok((info, diff_parser::parse_unified_diff(&diff_text)))
+                })
+            } else {
+                let spec = spec.clone();
+                tauri::async_runtime::spawn(async move {
+                    load_local_repo_diff(&spec.path_or_remote, spec.range.as_deref()).await
+                })
+            };
+            (spec.path_or_remote, handle)
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (path_or_remote, handle) in tasks {
+        let result = handle
+            .await
+            .unwrap_or_else(|e| Err(format!("Diff task failed: {}", e)));
+        results.push(match result {
+            Ok((info, files)) => RepoDiff {
+                path_or_remote,
+                repo_info: Some(info),
+                files,
+                error: None,
+            },
+            Err(e) => RepoDiff {
+                path_or_remote,
+                repo_info: None,
+                files: Vec::new(),
+                error: Some(e),
+            },
+        });
+    }
+    results
+}
+
+/// Diffs several repositories for one workspace review. Per-repository failures are
+/// reported in their `RepoDiff` instead of failing the call.
+#[tauri::command]
+pub async fn get_multi_diff(
+    ssh: tauri::State<'_, SshOptions>,
+    repos: Vec<RepoSpec>,
+) -> Result<Vec<RepoDiff>, String> {
+    Ok(load_multi_diff(repos, ssh.inner().clone()).await)
+}
+
 fn extract_optional_arg(matches: &tauri_plugin_cli::Matches, name: &str) -> Option<String> {
     matches.args.get(name).and_then(|arg| {
         arg.value.as_str().and_then(|s| {
@@ -39,13 +1181,306 @@ fn extract_optional_arg(matches: &tauri_plugin_cli::Matches, name: &str) -> Opti
     })
 }
 
+fn extract_flag(matches: &tauri_plugin_cli::Matches, name: &str) -> bool {
+    matches
+        .args
+        .get(name)
+        .and_then(|arg| arg.value.as_bool())
+        .unwrap_or(false)
+}
+
+/// Reads the ssh-related CLI flags once at startup; stored as managed state.
+pub fn ssh_options_from_cli(app: &tauri::AppHandle) -> SshOptions {
+    match app.cli().matches() {
+        Ok(matches) => {
+            let defaults = SshOptions::default();
+            SshOptions {
+                batch_mode: !extract_flag(&matches, "no-batch-mode"),
+                retries: extract_optional_arg(&matches, "ssh-retries")
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(defaults.retries),
+                retry_backoff: extract_optional_arg(&matches, "ssh-retry-backoff-ms")
+                    .and_then(|ms| ms.parse().ok())
+                    .map(std::time::Duration::from_millis)
+                    .unwrap_or(defaults.retry_backoff),
+                command: extract_optional_arg(&matches, "ssh-command")
+                    .or_else(|| std::env::var("GIT_SSH_COMMAND").ok())
+                    .filter(|c| !c.trim().is_empty()),
+            }
+        }
+        Err(_) => SshOptions::default(),
+    }
+}
+
 #[tauri::command]
 pub fn get_startup_args(app: tauri::AppHandle) -> Result<StartupArgs, String> {
-    match app.cli().matches() {
-        Ok(matches) => Ok(StartupArgs {
-            range: extract_optional_arg(&matches, "range"),
-            remote: extract_optional_arg(&matches, "remote"),
-        }),
-        Err(e) => Err(format!("Failed to parse CLI args: {}", e)),
+    let matches = app
+        .cli()
+        .matches()
+        .map_err(|e| format!("Failed to parse CLI args: {}", e))?;
+    let mut args = StartupArgs {
+        range: extract_optional_arg(&matches, "range"),
+        remote: extract_optional_arg(&matches, "remote"),
+        git_dir: std::env::var("GIT_DIR").ok().filter(|d| !d.is_empty()),
+        context_lines: None,
+        whitespace: WhitespaceMode::Show,
+        diff_file: extract_optional_arg(&matches, "diff-file"),
+    };
+
+    // Remote repositories have no local config file to read, and a diff file needs no
+    // repository at all
+    if args.remote.is_some() || args.diff_file.is_some() {
+        return Ok(args);
+    }
+    let repo = repo_info::locate_repo(args.git_dir.as_deref());
+    let repo_path = repo.as_ref().ok().map(|r| r.path.as_str());
+    if let Some(range) = args.range.take() {
+        match repo_info::classify_positional(range, repo_path) {
+            Positional::Range(range) => args.range = Some(range),
+            Positional::DiffFile(path) => {
+                args.diff_file = Some(path);
+                return Ok(args);
+            }
+        }
+    }
+    let Ok(repo) = repo else {
+        return Ok(args);
+    };
+    match config::load_repo_config(&repo.path)? {
+        Some(repo_config) => Ok(apply_repo_config(args, repo_config)),
+        None => Ok(args),
+    }
+}
+
+/// Fills in what the CLI left unset from the repository's config file.
+fn apply_repo_config(args: StartupArgs, repo_config: RepoConfig) -> StartupArgs {
+    StartupArgs {
+        range: args.range.or(repo_config.range),
+        context_lines: args.context_lines.or(repo_config.context_lines),
+        whitespace: if repo_config.ignore_whitespace {
+            WhitespaceMode::IgnoreAll
+        } else {
+            args.whitespace
+        },
+        ..args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_multi_diff_reports_failures_per_repo() {
+        let first = crate::test_support::TempRepo::new("multi-first");
+        first.write("a.txt", "one\n");
+        first.commit_all("initial");
+        first.write("a.txt", "two\n");
+
+        let second = crate::test_support::TempRepo::new("multi-second");
+        second.write("b.txt", "one\n");
+        second.commit_all("first");
+        second.write("b.txt", "two\n");
+        second.commit_all("second");
+
+        let spec = |path: &str, range: Option<&str>| RepoSpec {
+            path_or_remote: path.to_string(),
+            range: range.map(str::to_string),
+        };
+        let repos = vec![
+            spec(first.path_str(), None),
+            spec("/nonexistent/great-review/repo", None),
+            spec(second.path_str(), Some("HEAD~1..HEAD")),
+        ];
+        let results = tauri::async_runtime::block_on(load_multi_diff(repos, SshOptions::default()));
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].files[0].path, "a.txt");
+        assert!(results[0].error.is_none());
+        assert!(results[1].repo_info.is_none());
+        assert!(results[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("does not exist"));
+        assert_eq!(results[2].files[0].path, "b.txt");
+    }
+
+    #[test]
+    fn test_finish_files() {
+        let repo = crate::test_support::TempRepo::new("finish-files");
+        repo.write(review_ignore::IGNORE_FILE, "*.lock\n");
+        let files = diff_parser::parse_unified_diff(
+            "\
+diff --git a/Cargo.lock b/Cargo.lock
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -1 +1 @@
+-a
++b
+diff --git a/new.rs b/new.rs
+new file mode 100644
+--- /dev/null
++++ b/new.rs
+@@ -0,0 +1 @@
++fn main() {}
+diff --git a/lib.rs b/lib.rs
+--- a/lib.rs
++++ b/lib.rs
+@@ -1 +1 @@
+-a
++b
+",
+        );
+
+        let (kept, ignored) =
+            finish_files(files.clone(), Some(repo.path_str()), &[FileStatus::Added]).unwrap();
+        assert_eq!(ignored, 1);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].path, "new.rs");
+        assert!(kept[0].abs_path.is_some());
+
+        // Without a work tree (remote, patch file) nothing is ignored
+        let (kept, ignored) = finish_files(files, None, &[]).unwrap();
+        assert_eq!((kept.len(), ignored), (3, 0));
+    }
+
+    #[test]
+    fn test_apply_repo_config_cli_wins() {
+        let args = StartupArgs {
+            range: Some("HEAD~1".to_string()),
+            remote: None,
+            git_dir: None,
+            context_lines: None,
+            whitespace: WhitespaceMode::Show,
+            diff_file: None,
+        };
+        let repo_config = RepoConfig {
+            range: Some("main...HEAD".to_string()),
+            context_lines: Some(8),
+            ignore_whitespace: true,
+        };
+        let merged = apply_repo_config(args, repo_config.clone());
+        assert_eq!(merged.range.as_deref(), Some("HEAD~1"));
+        assert_eq!(merged.context_lines, Some(8));
+        assert_eq!(merged.whitespace, WhitespaceMode::IgnoreAll);
+
+        let no_cli = StartupArgs {
+            range: None,
+            remote: None,
+            git_dir: None,
+            context_lines: None,
+            whitespace: WhitespaceMode::Show,
+            diff_file: None,
+        };
+        let merged = apply_repo_config(no_cli, repo_config);
+        assert_eq!(merged.range.as_deref(), Some("main...HEAD"));
+    }
+
+    #[test]
+    fn test_read_diff_file() {
+        let dir = crate::test_support::TempRepo::new("diff-file");
+        dir.write(
+            "review.patch",
+            "--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-a\n+e\n",
+        );
+        let path = dir.join("review.patch");
+        let text = read_diff_file(path.to_str().unwrap()).unwrap();
+        let files = parse_diff_text(&text, &DiffOptions::new());
+        assert_eq!(files[0].path, "a.txt");
+        assert_eq!(files[0].additions(), 1);
+
+        let err = read_diff_file("/nonexistent/review.patch").unwrap_err();
+        assert!(err.starts_with("Failed to read diff file"), "got: {err}");
+    }
+
+    fn key(range: &str, head_sha: &str) -> DiffCacheKey {
+        DiffCacheKey {
+            repo_path: "/repo".to_string(),
+            options: DiffOptions::new().range(range),
+            remote: None,
+            head_sha: head_sha.to_string(),
+        }
+    }
+
+    fn file(path: &str) -> DiffFile {
+        DiffFile {
+            path: path.to_string(),
+            old_path: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_cache_hit_and_miss() {
+        let cache = DiffCache::default();
+        assert!(cache.get(&key("main..HEAD", "aaa")).is_none());
+
+        cache.insert(key("main..HEAD", "aaa"), vec![file("a.txt")]);
+        let hit = cache.get(&key("main..HEAD", "aaa")).unwrap();
+        assert_eq!(hit[0].path, "a.txt");
+        assert!(cache.get(&key("v1..v2", "aaa")).is_none());
+    }
+
+    #[test]
+    fn test_diff_cache_invalidates_on_head_change() {
+        let cache = DiffCache::default();
+        cache.insert(key("main..HEAD", "aaa"), vec![file("a.txt")]);
+        cache.insert(key("v1..v2", "aaa"), vec![file("b.txt")]);
+
+        assert!(cache.get(&key("main..HEAD", "bbb")).is_none());
+        // The old-HEAD entry for the other range was dropped as well
+        assert!(cache.get(&key("v1..v2", "aaa")).is_none());
+    }
+
+    #[test]
+    fn test_diff_cache_is_bounded() {
+        let cache = DiffCache::default();
+        for i in 0..DIFF_CACHE_CAPACITY + 4 {
+            cache.insert(key(&format!("HEAD~{}", i), "aaa"), Vec::new());
+        }
+        assert_eq!(cache.entries.lock().unwrap().len(), DIFF_CACHE_CAPACITY);
+        // Oldest entries are evicted first
+        assert!(cache.get(&key("HEAD~0", "aaa")).is_none());
+        let newest = key(&format!("HEAD~{}", DIFF_CACHE_CAPACITY + 3), "aaa");
+        assert!(cache.get(&newest).is_some());
+    }
+
+    #[test]
+    fn test_file_diff_options_keeps_rename() {
+        let repo = crate::test_support::TempRepo::new("file-diff-rename");
+        repo.write("before.txt", "one\ntwo\nthree\nfour\n");
+        repo.write("other.txt", "other\n");
+        repo.commit_all("initial");
+        repo.git(&["mv", "before.txt", "after.txt"]);
+        repo.write("other.txt", "changed\n");
+
+        let options = file_diff_options(&DiffOptions::default(), "after.txt", Some("before.txt"));
+        assert_eq!(options.pathspecs, vec!["before.txt", "after.txt"]);
+
+        let diff_text = diff_parser::run_git_diff(&options, repo.path_str()).unwrap();
+        let files = diff_parser::parse_unified_diff(&diff_text);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "after.txt");
+        assert_eq!(files[0].status, diff_parser::FileStatus::Renamed);
+    }
+
+    #[test]
+    fn test_diff_jobs_cancel() {
+        let jobs = DiffJobs::default();
+        let cancel = jobs.register(7);
+        assert!(!jobs.cancel(8));
+        assert!(jobs.cancel(7));
+        // The permit is stored, so a waiter that starts late still sees it
+        tauri::async_runtime::block_on(cancel.notified());
+        assert!(!jobs.cancel(7));
+    }
+
+    #[test]
+    fn test_diff_cache_clear() {
+        let cache = DiffCache::default();
+        cache.insert(key("main..HEAD", "aaa"), Vec::new());
+        cache.clear();
+        assert!(cache.get(&key("main..HEAD", "aaa")).is_none());
     }
 }
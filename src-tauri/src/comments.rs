@@ -0,0 +1,360 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::diff_parser::{DiffFile, LineType};
+
+/// Directory under the repo root holding persisted review state.
+pub const REVIEW_DIR: &str = ".great-review";
+
+const COMMENTS_FILE: &str = "comments.json";
+const VIEWED_FILE: &str = "viewed.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Comment {
+    pub file_path: String,
+    /// New-side line number; `None` for comments on the hunk as a whole.
+    pub line_no: Option<u32>,
+    pub hunk_index: usize,
+    pub body: String,
+    /// ISO-8601 timestamp set by the frontend.
+    pub created_at: String,
+    pub resolved: bool,
+    /// Text of the commented line when the comment was made, used to re-anchor it.
+    #[serde(default)]
+    pub anchor_content: Option<String>,
+    /// Set when re-anchoring could not find the line in the current diff.
+    #[serde(default)]
+    pub orphaned: bool,
+}
+
+pub(crate) fn review_path(repo_path: &str, file: &str) -> PathBuf {
+    Path::new(repo_path).join(REVIEW_DIR).join(file)
+}
+
+/// Writes `contents` next to `path` and renames it into place, so a crash mid-write
+/// never leaves a truncated file behind.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, contents)
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Reads a JSON list from the review directory; a missing file is an empty list.
+fn read_review_list<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, String> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&json).map_err(|e| format!("Corrupt {}: {}", path.display(), e))
+}
+
+/// Saves `comments` to `<repo_root>/.great-review/comments.json`, ordered by file path
+/// and line so the file diffs cleanly between saves.
+pub fn save_comments(repo_path: &str, comments: &[Comment]) -> Result<(), String> {
+    let mut sorted = comments.to_vec();
+    sorted.sort_by(|a, b| {
+        (&a.file_path, a.line_no, a.hunk_index).cmp(&(&b.file_path, b.line_no, b.hunk_index))
+    });
+
+    let json = serde_json::to_string_pretty(&sorted)
+        .map_err(|e| format!("Failed to serialize comments: {e}"))?;
+    write_atomically(&review_path(repo_path, COMMENTS_FILE), &json)
+}
+
+/// Loads saved comments; a repo that was never reviewed has none.
+pub fn load_comments(repo_path: &str) -> Result<Vec<Comment>, String> {
+    read_review_list(&review_path(repo_path, COMMENTS_FILE))
+}
+
+/// A file the reviewer marked as viewed, at the content it had then.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ViewedFile {
+    pub path: String,
+    pub new_blob: Option<String>,
+    pub viewed: bool,
+}
+
+fn load_viewed_entries(repo_path: &str) -> Result<Vec<ViewedFile>, String> {
+    read_review_list(&review_path(repo_path, VIEWED_FILE))
+}
+
+/// Records `path` as viewed (or not) at blob `new_blob`. Marking a file again replaces
+/// the entry recorded for an older blob.
+pub fn mark_file_viewed(
+    repo_path: &str,
+    path: &str,
+    new_blob: Option<&str>,
+    viewed: bool,
+) -> Result<(), String> {
+    let mut entries = load_viewed_entries(repo_path)?;
+    entries.retain(|e| e.path != path);
+    if viewed {
+        entries.push(ViewedFile {
+            path: path.to_string(),
+            new_blob: new_blob.map(str::to_string),
+            viewed,
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let json = serde_json::to_string_pretty(&entries)
+        .map_err(|e| format!("Failed to serialize viewed files: {e}"))?;
+    write_atomically(&review_path(repo_path, VIEWED_FILE), &json)
+}
+
+/// Paths among `files` that are still marked viewed: the entry must have been stored
+/// for the file's current `new_blob`, so any content change un-marks it.
+pub fn viewed_paths(repo_path: &str, files: &[DiffFile]) -> Result<Vec<String>, String> {
+    let entries = load_viewed_entries(repo_path)?;
+    Ok(files
+        .iter()
+        .filter(|f| {
+            entries
+                .iter()
+                .any(|e| e.viewed && e.path == f.path && e.new_blob == f.new_blob)
+        })
+        .map(|f| f.path.clone())
+        .collect())
+}
+
+/// Moves `comment` to the line in `file` whose content matches its anchor, preferring
+/// the candidate closest to the old line number. Hunk-level comments are returned as
+/// they are; comments whose line no longer exists are marked `orphaned`.
+pub fn reanchor_comment(comment: &Comment, file: &DiffFile) -> Comment {
+    let mut updated = comment.clone();
+    let Some(old_line) = comment.line_no else {
+        return updated;
+    };
+
+    // Lines present on the new side, with the hunk they belong to
+    let new_side = file.hunks.iter().enumerate().flat_map(|(i, hunk)| {
+        hunk.lines
+            .iter()
+            .filter(|l| l.line_type != LineType::Deletion)
+            .filter_map(move |l| l.new_line_no.map(|n| (i, n, l.content.as_str())))
+    });
+
+    let matches: Vec<(usize, u32)> = match &comment.anchor_content {
+        Some(anchor) => {
+            let exact: Vec<_> = new_side
+                .clone()
+                .filter(|(_, _, content)| content == anchor)
+                .map(|(i, n, _)| (i, n))
+                .collect();
+            if exact.is_empty() {
+                // Tolerate re-indentation
+                new_side
+                    .filter(|(_, _, content)| content.trim() == anchor.trim())
+                    .map(|(i, n, _)| (i, n))
+                    .collect()
+            } else {
+                exact
+            }
+        }
+        None => new_side
+            .filter(|(_, n, _)| *n == old_line)
+            .map(|(i, n, _)| (i, n))
+            .collect(),
+    };
+
+    match matches
+        .into_iter()
+        .min_by_key(|(_, n)| n.abs_diff(old_line))
+    {
+        Some((hunk_index, line_no)) => {
+            updated.line_no = Some(line_no);
+            updated.hunk_index = hunk_index;
+            updated.orphaned = false;
+        }
+        None => updated.orphaned = true,
+    }
+    updated
+}
+
+/// Re-anchors every comment against a freshly parsed diff. Comments on files that are
+/// no longer in the diff are orphaned.
+pub fn reanchor_comments(comments: &[Comment], files: &[DiffFile]) -> Vec<Comment> {
+    comments
+        .iter()
+        .map(|c| match files.iter().find(|f| f.path == c.file_path) {
+            Some(file) => reanchor_comment(c, file),
+            None => Comment {
+                orphaned: true,
+                ..c.clone()
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    fn comment(file_path: &str, line_no: Option<u32>, body: &str) -> Comment {
+        Comment {
+            file_path: file_path.to_string(),
+            line_no,
+            hunk_index: 0,
+            body: body.to_string(),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            resolved: false,
+            anchor_content: None,
+            orphaned: false,
+        }
+    }
+
+    fn anchored(line_no: u32, anchor: &str) -> Comment {
+        Comment {
+            anchor_content: Some(anchor.to_string()),
+            ..comment("file.rs", Some(line_no), "note")
+        }
+    }
+
+    fn parse_file(diff: &str) -> DiffFile {
+        crate::diff_parser::parse_unified_diff(diff).remove(0)
+    }
+
+    fn blob_file(path: &str, new_blob: &str) -> DiffFile {
+        DiffFile {
+            path: path.to_string(),
+            old_blob: Some("1111111".to_string()),
+            new_blob: Some(new_blob.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_viewed_state_follows_blob() {
+        let repo = TempRepo::new("viewed-blob");
+        mark_file_viewed(repo.path_str(), "a.rs", Some("aaaaaaa"), true).unwrap();
+        mark_file_viewed(repo.path_str(), "b.rs", Some("bbbbbbb"), true).unwrap();
+
+        let files = vec![blob_file("a.rs", "aaaaaaa"), blob_file("b.rs", "ccccccc")];
+        // b.rs changed since it was viewed
+        assert_eq!(viewed_paths(repo.path_str(), &files).unwrap(), vec!["a.rs"]);
+
+        mark_file_viewed(repo.path_str(), "a.rs", Some("aaaaaaa"), false).unwrap();
+        assert!(viewed_paths(repo.path_str(), &files).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reanchor_unchanged_line() {
+        let file = parse_file(
+            "\
+diff --git a/file.rs b/file.rs
+--- a/file.rs
++++ b/file.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    old();
++    new();
+ }
+",
+        );
+        let reanchored = reanchor_comment(&anchored(2, "    new();"), &file);
+        assert_eq!(reanchored.line_no, Some(2));
+        assert!(!reanchored.orphaned);
+    }
+
+    #[test]
+    fn test_reanchor_shifted_line() {
+        let file = parse_file(
+            "\
+diff --git a/file.rs b/file.rs
+--- a/file.rs
++++ b/file.rs
+@@ -1,3 +1,5 @@
++use std::io;
++
+ fn main() {
+     new();
+ }
+",
+        );
+        let reanchored = reanchor_comment(&anchored(2, "    new();"), &file);
+        assert_eq!(reanchored.line_no, Some(4));
+        assert!(!reanchored.orphaned);
+    }
+
+    #[test]
+    fn test_reanchor_deleted_line() {
+        let file = parse_file(
+            "\
+diff --git a/file.rs b/file.rs
+--- a/file.rs
++++ b/file.rs
+@@ -1,3 +1,2 @@
+ fn main() {
+-    new();
+ }
+",
+        );
+        let reanchored = reanchor_comment(&anchored(2, "    new();"), &file);
+        assert_eq!(reanchored.line_no, Some(2));
+        assert!(reanchored.orphaned);
+    }
+
+    #[test]
+    fn test_reanchor_prefers_closest_match() {
+        let file = parse_file(
+            "\
+diff --git a/file.rs b/file.rs
+--- a/file.rs
++++ b/file.rs
+@@ -1,2 +1,6 @@
+ }
++x
++x
++x
++}
+ tail
+",
+        );
+        let reanchored = reanchor_comment(&anchored(4, "}"), &file);
+        assert_eq!(reanchored.line_no, Some(5));
+    }
+
+    #[test]
+    fn test_reanchor_comments_file_gone() {
+        let reanchored = reanchor_comments(&[anchored(1, "x")], &[]);
+        assert!(reanchored[0].orphaned);
+    }
+
+    #[test]
+    fn test_save_and_load_comments() {
+        let repo = TempRepo::new("comments-roundtrip");
+        let comments = vec![
+            comment("src/b.rs", Some(3), "second"),
+            comment("src/a.rs", Some(10), "first"),
+            comment("src/a.rs", None, "hunk-level"),
+        ];
+        save_comments(repo.path_str(), &comments).unwrap();
+
+        let loaded = load_comments(repo.path_str()).unwrap();
+        let bodies: Vec<&str> = loaded.iter().map(|c| c.body.as_str()).collect();
+        assert_eq!(bodies, vec!["hunk-level", "first", "second"]);
+        assert!(repo.join(".great-review/comments.json").exists());
+    }
+
+    #[test]
+    fn test_load_comments_without_file() {
+        let repo = TempRepo::new("comments-missing");
+        assert!(load_comments(repo.path_str()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_comments_corrupt_file() {
+        let repo = TempRepo::new("comments-corrupt");
+        repo.write(".great-review/comments.json", "{not json");
+        let err = load_comments(repo.path_str()).unwrap_err();
+        assert!(err.contains("Corrupt"), "got: {err}");
+    }
+}
@@ -0,0 +1,90 @@
+use serde::Deserialize;
+use std::path::Path;
+
+/// Per-repository review defaults, committed as `.great-review.toml` at the repo root.
+pub const CONFIG_FILE: &str = ".great-review.toml";
+
+/// Defaults from the config file. CLI arguments and values chosen in the GUI take
+/// precedence over these.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoConfig {
+    pub range: Option<String>,
+    pub context_lines: Option<u32>,
+    pub ignore_whitespace: bool,
+}
+
+/// Reads `<repo_root>/.great-review.toml`. A missing file is `Ok(None)`; a file that
+/// does not parse is an error, so a typo is not silently ignored.
+///
+/// The file comes from the repository under review, so it is not trusted: a `range`
+/// starting with `-` would reach git as an option and is rejected.
+pub fn load_repo_config(repo_root: &str) -> Result<Option<RepoConfig>, String> {
+    let path = Path::new(repo_root).join(CONFIG_FILE);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let config: RepoConfig =
+        toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    if let Some(range) = config.range.as_deref().filter(|r| r.starts_with('-')) {
+        return Err(format!(
+            "Invalid {}: range '{}' must not start with '-'",
+            path.display(),
+            range
+        ));
+    }
+    Ok(Some(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_load_repo_config() {
+        let repo = TempRepo::new("config-load");
+        assert_eq!(load_repo_config(repo.path_str()).unwrap(), None);
+
+        repo.write(
+            CONFIG_FILE,
+            "range = \"main...HEAD\"\ncontext_lines = 10\nignore_whitespace = true\n",
+        );
+        assert_eq!(
+            load_repo_config(repo.path_str()).unwrap(),
+            Some(RepoConfig {
+                range: Some("main...HEAD".to_string()),
+                context_lines: Some(10),
+                ignore_whitespace: true,
+            })
+        );
+
+        repo.write(CONFIG_FILE, "context_lines = 3\n");
+        let config = load_repo_config(repo.path_str()).unwrap().unwrap();
+        assert_eq!(config.range, None);
+        assert!(!config.ignore_whitespace);
+    }
+
+    #[test]
+    fn test_load_repo_config_invalid() {
+        let repo = TempRepo::new("config-invalid");
+        repo.write(CONFIG_FILE, "contxt_lines = 3\n");
+        let err = load_repo_config(repo.path_str()).unwrap_err();
+        assert!(err.starts_with("Invalid "), "got: {err}");
+    }
+
+    #[test]
+    fn test_load_repo_config_rejects_option_range() {
+        let repo = TempRepo::new("config-hostile");
+        let target = repo.join("written.patch");
+        repo.write(
+            CONFIG_FILE,
+            &format!("range = \"--output={}\"\n", target.display()),
+        );
+        let err = load_repo_config(repo.path_str()).unwrap_err();
+        assert!(err.contains("must not start with '-'"), "got: {err}");
+        assert!(!target.exists());
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::diff_parser::{check_repo_path, git_command};
+
+/// One `<<<<<<<` ... `>>>>>>>` block, as 1-based line numbers of its markers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConflictRegion {
+    pub start: u32,
+    /// The `|||||||` line of a diff3-style conflict, which shows the merge base.
+    pub base: Option<u32>,
+    pub separator: u32,
+    pub end: u32,
+}
+
+/// An unmerged path and the conflict blocks still in its working-tree copy. `regions`
+/// is empty when the markers were already removed but the path is not yet staged.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConflictFile {
+    pub path: String,
+    pub regions: Vec<ConflictRegion>,
+}
+
+fn is_marker(line: &str, marker: &str) -> bool {
+    line.strip_prefix(marker)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\r']))
+}
+
+/// Finds complete conflict blocks in `content`; a block missing its closing marker is
+/// not reported.
+fn conflict_regions(content: &str) -> Vec<ConflictRegion> {
+    let mut regions = Vec::new();
+    let mut start: Option<u32> = None;
+    let mut base: Option<u32> = None;
+    let mut separator: Option<u32> = None;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line_no = idx as u32 + 1;
+        if is_marker(line, "<<<<<<<") {
+            start = Some(line_no);
+            base = None;
+            separator = None;
+        } else if start.is_some() && separator.is_none() && is_marker(line, "|||||||") {
+            base = Some(line_no);
+        } else if start.is_some() && separator.is_none() && line.trim_end() == "=======" {
+            separator = Some(line_no);
+        } else if is_marker(line, ">>>>>>>") {
+            if let (Some(start), Some(separator)) = (start, separator) {
+                regions.push(ConflictRegion {
+                    start,
+                    base,
+                    separator,
+                    end: line_no,
+                });
+            }
+            start = None;
+            separator = None;
+        }
+    }
+    regions
+}
+
+/// Lists the unmerged paths of an in-progress merge, rebase or cherry-pick with the
+/// conflict markers left in each. `repo_path` may be any directory of the work tree;
+/// paths are relative to its root, as git prints them.
+pub fn find_conflicts(repo_path: &str) -> Result<Vec<ConflictFile>, String> {
+    check_repo_path(repo_path)?;
+
+    let root_output = git_command()
+        .args(["-C", repo_path, "rev-parse", "--show-toplevel"])
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !root_output.status.success() {
+        return Err(format!(
+            "Not inside a work tree: {}",
+            String::from_utf8_lossy(&root_output.stderr).trim()
+        ));
+    }
+    let root = String::from_utf8_lossy(&root_output.stdout)
+        .trim()
+        .to_string();
+
+    let output = git_command()
+        .args(["diff", "--name-only", "--diff-filter=U", "-z"])
+        .current_dir(&root)
+        .output()
+        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to list unmerged files: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut paths: Vec<&str> = stdout.split('\0').filter(|p| !p.is_empty()).collect();
+    // Each unmerged path appears once per conflicting stage
+    paths.dedup();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let regions = match std::fs::read(Path::new(&root).join(path)) {
+                Ok(bytes) => conflict_regions(&String::from_utf8_lossy(&bytes)),
+                // Deleted on one side: nothing in the working tree to mark up
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(format!("Failed to read '{}': {}", path, e)),
+            };
+            Ok(ConflictFile {
+                path: path.to_string(),
+                regions,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_conflict_regions() {
+        let content = "\
+a
+<<<<<<< HEAD
+ours
+||||||| base
+original
+=======
+theirs
+>>>>>>> topic
+b
+<<<<<<< HEAD
+unterminated
+";
+        assert_eq!(
+            conflict_regions(content),
+            vec![ConflictRegion {
+                start: 2,
+                base: Some(4),
+                separator: 6,
+                end: 8,
+            }]
+        );
+        assert!(conflict_regions("<<<<<<<< not a marker\n=======\n>>>>>>>\n").is_empty());
+
+        // A setext heading underline on their side is content, not a second separator
+        assert_eq!(
+            conflict_regions("<<<<<<< HEAD\nours\n=======\nTitle\n=======\n>>>>>>> topic\n"),
+            vec![ConflictRegion {
+                start: 1,
+                base: None,
+                separator: 3,
+                end: 6,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let repo = TempRepo::new("conflicts");
+        repo.write("a.txt", "one\n");
+        repo.write("clean.txt", "same\n");
+        repo.commit_all("base");
+        repo.git(&["checkout", "-q", "-b", "topic"]);
+        repo.write("a.txt", "topic\n");
+        repo.commit_all("topic");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("a.txt", "main\n");
+        repo.commit_all("main");
+        assert!(find_conflicts(repo.path_str()).unwrap().is_empty());
+
+        // The merge fails with a conflict, which is the point
+        let _ = crate::diff_parser::git_command()
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(["-c", "merge.conflictStyle=merge", "merge", "topic"])
+            .current_dir(repo.path_str())
+            .output();
+
+        let conflicts = find_conflicts(repo.path_str()).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "a.txt");
+        assert_eq!(
+            conflicts[0].regions,
+            vec![ConflictRegion {
+                start: 1,
+                base: None,
+                separator: 3,
+                end: 5,
+            }]
+        );
+
+        // From a subdirectory the paths are still read relative to the root
+        repo.write("sub/other.txt", "x\n");
+        let sub = repo.join("sub");
+        assert_eq!(find_conflicts(sub.to_str().unwrap()).unwrap(), conflicts);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use crate::diff_parser::{DiffFile, LineType};
+
+/// Hit counts per line number for each source file (`SF:`) of an LCOV report.
+type LineHits = HashMap<u32, u64>;
+
+/// Reads the `SF:`/`DA:` records of an LCOV tracefile. A file listed in several
+/// records (one per test run, say) has its hit counts added up.
+fn parse_lcov(text: &str) -> HashMap<String, LineHits> {
+    let mut report: HashMap<String, LineHits> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(path) = line.strip_prefix("SF:") {
+            current = Some(path.to_string());
+            report.entry(path.to_string()).or_default();
+        } else if line == "end_of_record" {
+            current = None;
+        } else if let (Some(path), Some(data)) = (&current, line.strip_prefix("DA:")) {
+            // DA:<line>,<hits>[,<checksum>]
+            let mut fields = data.split(',');
+            let line_no: Option<u32> = fields.next().and_then(|n| n.trim().parse().ok());
+            let hits: Option<u64> = fields.next().and_then(|n| n.trim().parse().ok());
+            if let (Some(line_no), Some(hits)) = (line_no, hits) {
+                let hits_so_far = report.entry(path.clone()).or_default();
+                *hits_so_far.entry(line_no).or_insert(0) += hits;
+            }
+        }
+    }
+    report
+}
+
+/// The report entry for `file`. LCOV paths are usually absolute or relative to where
+/// the tests ran, so besides an exact match (against `abs_path` too) an entry whose
+/// path ends in `/<path>` is taken; the shortest such one, when several do.
+fn hits_for<'a>(report: &'a HashMap<String, LineHits>, file: &DiffFile) -> Option<&'a LineHits> {
+    let exact = report
+        .get(&file.path)
+        .or_else(|| file.abs_path.as_ref().and_then(|p| report.get(p)));
+    if exact.is_some() {
+        return exact;
+    }
+    let suffix = format!("/{}", file.path);
+    report
+        .iter()
+        .filter(|(path, _)| path.ends_with(&suffix))
+        .min_by_key(|(path, _)| path.len())
+        .map(|(_, hits)| hits)
+}
+
+/// Sets `DiffLine::covered` on each addition from the LCOV tracefile at `lcov_path`:
+/// whether the tests executed it. Lines the report has no data for (comments, blank
+/// lines, files it does not list) keep `None`.
+pub fn annotate_coverage(files: &mut [DiffFile], lcov_path: &str) -> Result<(), String> {
+    let text = std::fs::read_to_string(lcov_path)
+        .map_err(|e| format!("Failed to read coverage report '{}': {}", lcov_path, e))?;
+    let report = parse_lcov(&text);
+
+    for file in files.iter_mut() {
+        let Some(hits) = hits_for(&report, file) else {
+            continue;
+        };
+        for hunk in &mut file.hunks {
+            for line in &mut hunk.lines {
+                if line.line_type != LineType::Addition {
+                    continue;
+                }
+                line.covered = line
+                    .new_line_no
+                    .and_then(|n| hits.get(&n))
+                    .map(|&count| count > 0);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::parse_unified_diff;
+    use crate::test_support::TempRepo;
+
+    const LCOV: &str = "\
+TN:
+SF:/home/ci/project/src/lib.rs
+DA:1,4
+DA:2,0
+DA:4,1
+end_of_record
+SF:/home/ci/project/src/lib.rs
+DA:2,3
+end_of_record
+SF:src/util.rs
+DA:1,0,abcdef
+end_of_record
+";
+
+    #[test]
+    fn test_parse_lcov() {
+        let report = parse_lcov(LCOV);
+        let lib = &report["/home/ci/project/src/lib.rs"];
+        assert_eq!(lib[&1], 4);
+        assert_eq!(lib[&2], 3);
+        assert_eq!(report["src/util.rs"][&1], 0);
+    }
+
+    #[test]
+    fn test_annotate_coverage() {
+        let repo = TempRepo::new("coverage");
+        repo.write("coverage.lcov", LCOV);
+        let mut files = parse_unified_diff(
+            "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,4 @@
+ fn a() {}
++fn b() {}
++// comment
++fn c() {}
+ fn d() {}
+diff --git a/src/util.rs b/src/util.rs
+--- a/src/util.rs
++++ b/src/util.rs
+@@ -0,0 +1 @@
++fn never_run() {}
+diff --git a/README.md b/README.md
+--- a/README.md
++++ b/README.md
+@@ -0,0 +1 @@
++docs
+",
+        );
+        let lcov_path = repo.join("coverage.lcov");
+        annotate_coverage(&mut files, &lcov_path.to_string_lossy()).unwrap();
+
+        let covered: Vec<Option<bool>> =
+            files[0].hunks[0].lines.iter().map(|l| l.covered).collect();
+        // Context lines are left alone even when the report has them
+        assert_eq!(covered, vec![None, Some(true), None, Some(true), None]);
+        assert_eq!(files[1].hunks[0].lines[0].covered, Some(false));
+        assert_eq!(files[2].hunks[0].lines[0].covered, None);
+
+        let err = annotate_coverage(&mut files, "/nonexistent/lcov.info").unwrap_err();
+        assert!(
+            err.starts_with("Failed to read coverage report"),
+            "got: {err}"
+        );
+    }
+}
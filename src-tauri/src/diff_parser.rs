@@ -1,12 +1,19 @@
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+use tokio::sync::Notify;
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub enum FileStatus {
     Added,
+    #[default]
     Modified,
     Deleted,
     Renamed,
+    Copied,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -16,15 +23,29 @@ pub enum LineType {
     Context,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct DiffLine {
     pub content: String,
     pub line_type: LineType,
     pub old_line_no: Option<u32>,
     pub new_line_no: Option<u32>,
+    /// Set when git reported `\ No newline at end of file` for this line.
+    pub no_newline: bool,
+    /// The spaces and tabs `content` starts with, for rendering indentation.
+    pub leading_whitespace: String,
+    /// First line of a run of consecutive lines with the same `line_type`.
+    pub run_start: bool,
+    /// Last line of such a run.
+    pub run_end: bool,
+    /// `[start, end)` byte offsets within `content` of the words git's word diff marked
+    /// as changed; only set by `parse_word_diff`.
+    pub word_ranges: Vec<(usize, usize)>,
+    /// Whether tests ran this added line, from a coverage report (see
+    /// `coverage::annotate_coverage`); `None` when not annotated or not instrumented.
+    pub covered: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct DiffHunk {
     pub header: String,
     pub old_start: u32,
@@ -32,487 +53,4741 @@ pub struct DiffHunk {
     pub new_start: u32,
     pub new_count: u32,
     pub lines: Vec<DiffLine>,
+    /// Every change only adds, removes or moves whitespace (e.g. a reindent).
+    pub is_whitespace_only: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct DiffFile {
     pub path: String,
     pub old_path: Option<String>,
     pub hunks: Vec<DiffHunk>,
     pub status: FileStatus,
+    /// Abbreviated blob hashes from the `index` line; `None` for the missing side of
+    /// an added or deleted file, or when the diff has no `index` line.
+    pub old_blob: Option<String>,
+    pub new_blob: Option<String>,
+    /// Octal file modes (`100644`, `100755`, `120000`, ...) from the metadata lines.
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    /// Either side is a symlink (mode `120000`), including regular file ↔ symlink
+    /// type changes.
+    pub is_symlink: bool,
+    /// Link targets for the sides that are symlinks; git diffs them as one-line content.
+    pub old_link_target: Option<String>,
+    pub new_link_target: Option<String>,
+    /// `similarity index NN%` of a rename or copy; 100 means the content is unchanged.
+    pub similarity: Option<u8>,
+    /// `dissimilarity index NN%` git adds when `-B` breaks a heavily rewritten file
+    /// into a complete rewrite.
+    pub dissimilarity: Option<u8>,
+    /// Commits a submodule (mode `160000`) pointed at on each side, taken from the
+    /// `Subproject commit <sha>` lines git diffs it as.
+    pub old_submodule_sha: Option<String>,
+    pub new_submodule_sha: Option<String>,
+    /// git reported the content as binary (`Binary files ... differ` or a binary
+    /// patch), so there are no hunks.
+    pub is_binary: bool,
+    /// `path` joined onto the working tree root, for opening the file from the UI. Only
+    /// set by `get_diff` for local repositories with a working tree.
+    pub abs_path: Option<String>,
 }
 
-fn parse_hunk_header(line: &str) -> Option<(u32, u32, u32, u32)> {
-    let line = line.strip_prefix("@@ ")?;
-    let end = line.find(" @@")?;
-    let range_part = &line[..end];
+/// Git's file mode for symbolic links.
+pub const SYMLINK_MODE: &str = "120000";
 
-    let mut parts = range_part.split_whitespace();
-    let old_range = parts.next()?.strip_prefix('-')?;
-    let new_range = parts.next()?.strip_prefix('+')?;
+/// Git's file mode for submodules (gitlinks).
+pub const SUBMODULE_MODE: &str = "160000";
 
-    let (old_start, old_count) = parse_range(old_range);
-    let (new_start, new_count) = parse_range(new_range);
+impl DiffFile {
+    pub fn additions(&self) -> usize {
+        self.count_lines(LineType::Addition)
+    }
 
-    Some((old_start, old_count, new_start, new_count))
-}
+    pub fn deletions(&self) -> usize {
+        self.count_lines(LineType::Deletion)
+    }
 
-fn parse_range(range: &str) -> (u32, u32) {
-    if let Some((start, count)) = range.split_once(',') {
-        (start.parse().unwrap_or(0), count.parse().unwrap_or(0))
-    } else {
-        (range.parse().unwrap_or(0), 1)
+    fn count_lines(&self, line_type: LineType) -> usize {
+        self.hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .filter(|l| l.line_type == line_type)
+            .count()
     }
 }
 
-pub fn parse_unified_diff(diff_text: &str) -> Vec<DiffFile> {
-    let mut files: Vec<DiffFile> = Vec::new();
-    let lines: Vec<&str> = diff_text.lines().collect();
-    let mut i = 0;
+/// Finds the `(hunk_index, line_index)` of the line shown at `new_line_no` on the new
+/// side. Deleted lines have no new-side number, so they are never returned.
+pub fn locate_line(file: &DiffFile, new_line_no: u32) -> Option<(usize, usize)> {
+    file.hunks
+        .iter()
+        .enumerate()
+        .find_map(|(hunk_index, hunk)| {
+            hunk.lines
+                .iter()
+                .position(|l| l.new_line_no == Some(new_line_no))
+                .map(|line_index| (hunk_index, line_index))
+        })
+}
 
-    while i < lines.len() {
-        let line = lines[i];
+/// The hunk as it reads after the change: context and additions, in order.
+pub fn hunk_new_lines(hunk: &DiffHunk) -> Vec<&DiffLine> {
+    hunk.lines
+        .iter()
+        .filter(|l| l.line_type != LineType::Deletion)
+        .collect()
+}
 
-        if line.starts_with("diff --git ") {
-            let mut path = String::new();
-            let mut old_path: Option<String> = None;
-            let mut status = FileStatus::Modified;
-            let mut hunks: Vec<DiffHunk> = Vec::new();
+/// The hunk as it read before the change: context and deletions, in order.
+pub fn hunk_old_lines(hunk: &DiffHunk) -> Vec<&DiffLine> {
+    hunk.lines
+        .iter()
+        .filter(|l| l.line_type != LineType::Addition)
+        .collect()
+}
 
-            // Extract path from "diff --git a/path b/path"
-            if let Some(b_pos) = line.rfind(" b/") {
-                path = line[b_pos + 3..].to_string();
-            }
+/// The status a single extended header line of a `diff --git` block implies, if any.
+fn metadata_status(line: &str) -> Option<FileStatus> {
+    if line.starts_with("new file mode ") {
+        Some(FileStatus::Added)
+    } else if line.starts_with("deleted file mode ") {
+        Some(FileStatus::Deleted)
+    } else if line.starts_with("rename from ") {
+        Some(FileStatus::Renamed)
+    } else if line.starts_with("copy from ") {
+        Some(FileStatus::Copied)
+    } else {
+        None
+    }
+}
 
-            i += 1;
+/// The status of a file from the extended header lines of its `diff --git` block (the
+/// lines between the header and the first hunk). Lines that say nothing about the
+/// status are ignored; with none left the file was modified in place.
+pub fn classify_status(metadata_lines: &[&str]) -> FileStatus {
+    metadata_lines
+        .iter()
+        .rev()
+        .find_map(|line| metadata_status(line))
+        .unwrap_or_default()
+}
 
-            // Parse file metadata lines
-            while i < lines.len() && !lines[i].starts_with("diff --git ") {
-                let line = lines[i];
+/// A changed file from `git diff --name-status`: enough for the file tree, without
+/// any line content.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChangedFile {
+    pub path: String,
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+}
 
-                if line.starts_with("new file mode") {
-                    status = FileStatus::Added;
-                } else if line.starts_with("deleted file mode") {
-                    status = FileStatus::Deleted;
-                } else if let Some(from) = line.strip_prefix("rename from ") {
-                    old_path = Some(from.to_string());
-                    status = FileStatus::Renamed;
-                } else if let Some(to) = line.strip_prefix("rename to ") {
-                    path = to.to_string();
-                } else if line.starts_with("Binary files") {
-                    // Skip binary files entirely
-                    break;
-                } else if line.starts_with("--- ") {
-                    // old file path; we already have it from the header
-                } else if line.starts_with("+++ ") {
-                    // new file path; we already have it from the header
-                } else if line.starts_with("@@ ") {
-                    if let Some((old_start, old_count, new_start, new_count)) =
-                        parse_hunk_header(line)
-                    {
-                        let header = line.to_string();
-                        let mut hunk_lines: Vec<DiffLine> = Vec::new();
-                        let mut old_line = old_start;
-                        let mut new_line = new_start;
-
-                        i += 1;
-
-                        while i < lines.len() {
-                            let hline = lines[i];
-
-                            if hline.starts_with("diff --git ") || hline.starts_with("@@ ") {
-                                break;
-                            }
-
-                            if hline == "\\ No newline at end of file" {
-                                i += 1;
-                                continue;
-                            }
-
-                            if let Some(content) = hline.strip_prefix('+') {
-                                hunk_lines.push(DiffLine {
-                                    content: content.to_string(),
-                                    line_type: LineType::Addition,
-                                    old_line_no: None,
-                                    new_line_no: Some(new_line),
-                                });
-                                new_line += 1;
-                            } else if let Some(content) = hline.strip_prefix('-') {
-                                hunk_lines.push(DiffLine {
-                                    content: content.to_string(),
-                                    line_type: LineType::Deletion,
-                                    old_line_no: Some(old_line),
-                                    new_line_no: None,
-                                });
-                                old_line += 1;
-                            } else if let Some(content) = hline.strip_prefix(' ') {
-                                hunk_lines.push(DiffLine {
-                                    content: content.to_string(),
-                                    line_type: LineType::Context,
-                                    old_line_no: Some(old_line),
-                                    new_line_no: Some(new_line),
-                                });
-                                old_line += 1;
-                                new_line += 1;
-                            } else {
-                                // Unknown line format, skip
-                                i += 1;
-                                continue;
-                            }
-
-                            i += 1;
-                        }
+/// Parses `git diff --name-status -z` output. Renames and copies (`R<score>`,
+/// `C<score>`) carry two paths, old then new; other codes carry one. Type changes and
+/// other rare codes are reported as modifications.
+pub fn parse_name_status(output: &str) -> Vec<ChangedFile> {
+    let mut fields = output.split('\0').filter(|f| !f.is_empty());
+    let mut files = Vec::new();
 
-                        hunks.push(DiffHunk {
-                            header,
-                            old_start,
-                            old_count,
-                            new_start,
-                            new_count,
-                            lines: hunk_lines,
-                        });
+    while let Some(code) = fields.next() {
+        let status = match code.chars().next() {
+            Some('A') => FileStatus::Added,
+            Some('D') => FileStatus::Deleted,
+            Some('R') => FileStatus::Renamed,
+            Some('C') => FileStatus::Copied,
+            _ => FileStatus::Modified,
+        };
+        let Some(first) = fields.next() else {
+            break;
+        };
+        let (path, old_path) = match status {
+            FileStatus::Renamed | FileStatus::Copied => match fields.next() {
+                Some(new) => (new, Some(first.to_string())),
+                None => break,
+            },
+            _ => (first, None),
+        };
+        files.push(ChangedFile {
+            path: path.to_string(),
+            old_path,
+            status,
+        });
+    }
 
-                        continue; // Don't increment i, already at next line
-                    }
-                } else {
-                    // Other metadata lines (index, similarity, etc.)
-                }
+    files
+}
 
-                i += 1;
-            }
+fn name_status_args(range: Option<&str>) -> Vec<&str> {
+    vec![
+        "diff",
+        "--name-status",
+        "-z",
+        "-M",
+        "--end-of-options",
+        range.unwrap_or("HEAD"),
+    ]
+}
 
-            files.push(DiffFile {
-                path,
-                old_path,
-                hunks,
-                status,
-            });
-        } else {
-            i += 1;
-        }
+/// Whether `HEAD` resolves to a commit; `false` in a freshly initialised repository.
+pub fn has_commits(repo_path: &str) -> Result<bool, String> {
+    let output = git_command()
+        .args(["rev-parse", "--verify", "--quiet", "HEAD^{commit}"])
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git rev-parse: {}", e))?;
+    Ok(output.status.success())
+}
+
+/// The current branch's upstream as a short ref name (e.g. `origin/main`).
+pub fn resolve_upstream(repo_path: &str) -> Result<String, String> {
+    let output = git_command()
+        .args([
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ])
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git rev-parse: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!(
+            "No upstream branch to compare against: {}",
+            stderr.trim().trim_start_matches("fatal: ")
+        ))
     }
+}
 
-    files
+/// Rejects ranges in a repository without commits, where git would only report an
+/// ambiguous argument. Returns whether `HEAD` exists so no-range diffs can pick their
+/// base.
+fn check_can_diff(range: Option<&str>, repo_path: &str) -> Result<bool, String> {
+    let has_head = has_commits(repo_path)?;
+    match range {
+        Some(range) if !has_head => Err(format!(
+            "Repository has no commits yet; cannot diff range '{}'",
+            range
+        )),
+        _ => Ok(has_head),
+    }
 }
 
-pub fn parse_remote_path(remote: &str) -> Result<(&str, &str), String> {
-    remote.split_once(':').ok_or_else(|| {
-        format!(
-            "Invalid remote path '{}'. Expected format: host:/path/to/repo or user@host:/path/to/repo",
-            remote
-        )
-    })
+/// Lists the files `run_git_diff` would show for `range`, without their hunks.
+pub fn run_git_name_status(
+    range: Option<&str>,
+    repo_path: &str,
+) -> Result<Vec<ChangedFile>, String> {
+    check_repo_path(repo_path)?;
+
+    let mut args = name_status_args(range);
+    if !check_can_diff(range, repo_path)? {
+        // No HEAD yet: compare the working tree to the index
+        args.truncate(args.len() - 2);
+    }
+    let output = git_command()
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+
+    if output.status.success() {
+        Ok(parse_name_status(&String::from_utf8_lossy(&output.stdout)))
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
 }
 
-pub fn run_remote_git_diff(remote: &str, range: Option<&str>) -> Result<String, String> {
+pub fn run_remote_git_name_status(
+    remote: &str,
+    range: Option<&str>,
+    ssh: &SshOptions,
+) -> Result<Vec<ChangedFile>, String> {
     let (host, path) = parse_remote_path(remote)?;
 
-    let git_cmd = match range {
-        Some(r) => format!("cd '{}' && git diff {}", path, r),
-        None => format!("cd '{}' && git diff HEAD", path),
-    };
+    let args: Vec<String> = name_status_args(range)
+        .iter()
+        .map(|a| shell_quote(a))
+        .collect();
+    let git_cmd = format!("cd {} && git {}", shell_quote(path), args.join(" "));
 
-    let output = Command::new("ssh")
-        .args(["-o", "ConnectTimeout=10", "-o", "BatchMode=yes", host, &git_cmd])
+    let output = ssh_command(host, &git_cmd, ssh)
         .output()
         .map_err(|e| format!("Failed to execute ssh: {}", e))?;
 
     if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        Ok(parse_name_status(&String::from_utf8_lossy(&output.stdout)))
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        if stderr.contains("Could not resolve hostname") {
-            Err(format!("Cannot resolve host '{}'. Check the hostname and your network connection.", host))
-        } else if stderr.contains("Connection refused") || stderr.contains("Connection timed out") {
-            Err(format!("Cannot connect to '{}'. Ensure the machine is reachable and SSH is running.", host))
-        } else if stderr.contains("Permission denied") {
-            Err(format!("SSH authentication failed for '{}'. Check your SSH keys or credentials.", host))
-        } else if stderr.contains("No such file or directory") || stderr.contains("not a directory") {
-            Err(format!("Remote path '{}' not found on '{}'.", path, host))
-        } else if stderr.contains("not a git repository") {
-            Err(format!("'{}' on '{}' is not a git repository.", path, host))
-        } else {
-            Err(format!("Remote command failed: {}", stderr.trim()))
+        Err(describe_remote_error(&stderr, host, path))
+    }
+}
+
+/// Renders a hunk back to unified-diff text: the header followed by `+`/`-`/` `
+/// prefixed lines, including any `\ No newline at end of file` markers.
+pub fn format_hunk(hunk: &DiffHunk) -> String {
+    let mut out = String::new();
+    out.push_str(&hunk.header);
+    out.push('\n');
+    for line in &hunk.lines {
+        let prefix = match line.line_type {
+            LineType::Addition => '+',
+            LineType::Deletion => '-',
+            LineType::Context => ' ',
+        };
+        out.push(prefix);
+        out.push_str(&line.content);
+        out.push('\n');
+        if line.no_newline {
+            out.push_str("\\ No newline at end of file\n");
+        }
+    }
+    out
+}
+
+/// Turns parsed files back into unified-diff text that `parse_unified_diff` reads to
+/// the same structure: `diff --git` header, status and mode metadata, `index` line
+/// when the blobs are known, file markers and hunks. New and deleted files without a
+/// recorded mode are written with mode 100644.
+pub fn reconstruct_unified_diff(files: &[DiffFile]) -> String {
+    let mut out = String::new();
+    for file in files {
+        let old_path = file.old_path.as_deref().unwrap_or(&file.path);
+        out.push_str(&format!("diff --git a/{} b/{}\n", old_path, file.path));
+
+        // Mode unchanged: git puts it on the index line instead
+        let mut index_mode = None;
+        match file.status {
+            FileStatus::Added => out.push_str(&format!(
+                "new file mode {}\n",
+                file.new_mode.as_deref().unwrap_or("100644")
+            )),
+            FileStatus::Deleted => out.push_str(&format!(
+                "deleted file mode {}\n",
+                file.old_mode.as_deref().unwrap_or("100644")
+            )),
+            FileStatus::Renamed | FileStatus::Copied | FileStatus::Modified => {
+                if file.old_mode == file.new_mode {
+                    index_mode = file.new_mode.as_deref();
+                } else {
+                    if let Some(mode) = &file.old_mode {
+                        out.push_str(&format!("old mode {}\n", mode));
+                    }
+                    if let Some(mode) = &file.new_mode {
+                        out.push_str(&format!("new mode {}\n", mode));
+                    }
+                }
+                if let Some(percent) = file.similarity {
+                    out.push_str(&format!("similarity index {}%\n", percent));
+                }
+                if let Some(percent) = file.dissimilarity {
+                    out.push_str(&format!("dissimilarity index {}%\n", percent));
+                }
+                match file.status {
+                    FileStatus::Renamed => out.push_str(&format!(
+                        "rename from {}\nrename to {}\n",
+                        old_path, file.path
+                    )),
+                    FileStatus::Copied => {
+                        out.push_str(&format!("copy from {}\ncopy to {}\n", old_path, file.path))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if file.old_blob.is_some() || file.new_blob.is_some() {
+            let missing = "0000000";
+            out.push_str(&format!(
+                "index {}..{}",
+                file.old_blob.as_deref().unwrap_or(missing),
+                file.new_blob.as_deref().unwrap_or(missing)
+            ));
+            if let Some(mode) = index_mode {
+                out.push_str(&format!(" {}", mode));
+            }
+            out.push('\n');
+        }
+
+        let old_marker = match file.status {
+            FileStatus::Added => "/dev/null".to_string(),
+            _ => format!("a/{}", old_path),
+        };
+        let new_marker = match file.status {
+            FileStatus::Deleted => "/dev/null".to_string(),
+            _ => format!("b/{}", file.path),
+        };
+        if file.is_binary {
+            out.push_str(&format!(
+                "Binary files {} and {} differ\n",
+                old_marker, new_marker
+            ));
+        } else if !file.hunks.is_empty() {
+            out.push_str(&format!("--- {}\n+++ {}\n", old_marker, new_marker));
+            for hunk in &file.hunks {
+                out.push_str(&format_hunk(hunk));
+            }
         }
     }
+    out
+}
+
+/// Something the parser could not make sense of and skipped.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ParseWarning {
+    /// 1-based line number in the diff text.
+    pub line_no: usize,
+    pub content: String,
+    pub message: String,
+}
+
+fn parse_hunk_header(line: &str) -> Option<(u32, u32, u32, u32)> {
+    let line = line.strip_prefix("@@ ")?;
+    let end = line.find(" @@")?;
+    let range_part = &line[..end];
+
+    let mut parts = range_part.split_whitespace();
+    let old_range = parts.next()?.strip_prefix('-')?;
+    let new_range = parts.next()?.strip_prefix('+')?;
+
+    let (old_start, old_count) = parse_range(old_range)?;
+    let (new_start, new_count) = parse_range(new_range)?;
+
+    Some((old_start, old_count, new_start, new_count))
 }
 
-pub fn run_git_diff(range: Option<&str>, repo_path: &str) -> Result<String, String> {
-    let args = match range {
-        Some(r) => vec!["diff", r],
-        None => vec!["diff", "HEAD"],
+/// Parses `start,count` (or `start`, meaning a count of 1). Ranges whose end does not
+/// fit in a `u32` are rejected, so line numbers computed from them cannot wrap.
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let (start, count): (u32, u32) = match range.split_once(',') {
+        Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+        None => (range.parse().ok()?, 1),
     };
+    start.checked_add(count)?;
+    Some((start, count))
+}
 
-    let output = Command::new("git")
-        .args(&args)
-        .current_dir(repo_path)
-        .output()
-        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+/// Parses `87%` from a similarity/dissimilarity metadata line.
+fn parse_percentage(value: &str) -> Option<u8> {
+    value.trim().strip_suffix('%')?.parse().ok()
+}
 
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    } else if range.is_none() {
-        // Fallback: git diff (no HEAD) for repos with no commits
-        let fallback = Command::new("git")
-            .args(["diff"])
-            .current_dir(repo_path)
-            .output()
-            .map_err(|e| format!("Failed to execute git diff fallback: {}", e))?;
+fn first_line_of_type(hunks: &[DiffHunk], line_type: LineType) -> Option<String> {
+    hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .find(|l| l.line_type == line_type)
+        .map(|l| l.content.clone())
+}
+
+/// The commit from a submodule's `Subproject commit <sha>[-dirty]` line of `line_type`.
+fn submodule_sha(mode: Option<&str>, hunks: &[DiffHunk], line_type: LineType) -> Option<String> {
+    if mode != Some(SUBMODULE_MODE) {
+        return None;
+    }
+    let line = first_line_of_type(hunks, line_type)?;
+    let sha = line.strip_prefix("Subproject commit ")?;
+    // A submodule with uncommitted changes is reported as `<sha>-dirty`
+    Some(sha.trim().trim_end_matches("-dirty").to_string())
+}
 
-        if fallback.status.success() {
-            Ok(String::from_utf8_lossy(&fallback.stdout).to_string())
+/// Parses the part after `index ` (`abc1234..def5678` with an optional mode) into the
+/// old and new blob hashes and the mode. All-zero hashes stand for a side that does
+/// not exist.
+fn parse_index_line(index: &str) -> Option<(Option<String>, Option<String>, Option<String>)> {
+    let mut parts = index.split_whitespace();
+    let hashes = parts.next()?;
+    let mode = parts.next().map(str::to_string);
+    let (old, new) = hashes.split_once("..")?;
+    let blob = |hash: &str| {
+        if hash.is_empty() || hash.chars().all(|c| c == '0') {
+            None
         } else {
-            Err(String::from_utf8_lossy(&fallback.stderr).to_string())
+            Some(hash.to_string())
         }
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    };
+    Some((blob(old), blob(new), mode))
+}
+
+/// The `-- ` line that starts a `git format-patch` signature (by default git's version),
+/// or the `From <sha> <date>` line that starts the next message of a mailbox.
+fn is_mail_boundary(line: &str) -> bool {
+    if line == "-- " {
+        return true;
     }
+    line.strip_prefix("From ")
+        .and_then(|rest| rest.split_once(' '))
+        .is_some_and(|(sha, _)| {
+            matches!(sha.len(), 40 | 64) && sha.chars().all(|c| c.is_ascii_hexdigit())
+        })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Diff lines read from an iterator with as much lookahead as the parser asks for,
+/// which is at most two lines, so the whole text never has to be in memory at once.
+struct LineCursor<'a, I: Iterator<Item = &'a str>> {
+    lines: I,
+    ahead: VecDeque<&'a str>,
+    /// 1-based number of the line `peek()` returns.
+    line_no: usize,
+}
 
-    #[test]
-    fn test_parse_additions_only() {
-        let diff = "\
-diff --git a/hello.txt b/hello.txt
-new file mode 100644
-index 0000000..e69de29
---- /dev/null
-+++ b/hello.txt
-@@ -0,0 +1,3 @@
-+line one
-+line two
-+line three
-";
-        let files = parse_unified_diff(diff);
-        assert_eq!(files.len(), 1);
-        assert_eq!(files[0].path, "hello.txt");
-        assert_eq!(files[0].status, FileStatus::Added);
-        assert_eq!(files[0].hunks.len(), 1);
-        assert_eq!(files[0].hunks[0].lines.len(), 3);
-        for line in &files[0].hunks[0].lines {
-            assert_eq!(line.line_type, LineType::Addition);
-            assert!(line.old_line_no.is_none());
-            assert!(line.new_line_no.is_some());
+impl<'a, I: Iterator<Item = &'a str>> LineCursor<'a, I> {
+    fn new(lines: I) -> Self {
+        LineCursor {
+            lines,
+            ahead: VecDeque::new(),
+            line_no: 1,
         }
     }
 
-    #[test]
-    fn test_parse_deletions_only() {
-        let diff = "\
-diff --git a/old.txt b/old.txt
-deleted file mode 100644
-index abc1234..0000000
---- a/old.txt
-+++ /dev/null
-@@ -1,3 +0,0 @@
--first line
--second line
--third line
-";
-        let files = parse_unified_diff(diff);
-        assert_eq!(files.len(), 1);
-        assert_eq!(files[0].status, FileStatus::Deleted);
-        assert_eq!(files[0].hunks[0].lines.len(), 3);
-        for line in &files[0].hunks[0].lines {
-            assert_eq!(line.line_type, LineType::Deletion);
-            assert!(line.old_line_no.is_some());
-            assert!(line.new_line_no.is_none());
+    fn peek_nth(&mut self, n: usize) -> Option<&'a str> {
+        while self.ahead.len() <= n {
+            self.ahead.push_back(self.lines.next()?);
         }
+        Some(self.ahead[n])
     }
 
-    #[test]
-    fn test_parse_mixed_changes() {
-        let diff = "\
-diff --git a/file.txt b/file.txt
-index abc1234..def5678 100644
---- a/file.txt
-+++ b/file.txt
-@@ -1,5 +1,5 @@
- line one
--line two old
-+line two new
- line three
--line four old
-+line four new
-";
-        let files = parse_unified_diff(diff);
-        assert_eq!(files.len(), 1);
-        assert_eq!(files[0].status, FileStatus::Modified);
-        let lines = &files[0].hunks[0].lines;
-        assert_eq!(lines.len(), 6);
-        assert_eq!(lines[0].line_type, LineType::Context);
-        assert_eq!(lines[1].line_type, LineType::Deletion);
-        assert_eq!(lines[2].line_type, LineType::Addition);
-        assert_eq!(lines[3].line_type, LineType::Context);
-        assert_eq!(lines[4].line_type, LineType::Deletion);
-        assert_eq!(lines[5].line_type, LineType::Addition);
+    fn peek(&mut self) -> Option<&'a str> {
+        self.peek_nth(0)
     }
 
-    #[test]
-    fn test_parse_multiple_files() {
-        let diff = "\
-diff --git a/a.txt b/a.txt
-index abc..def 100644
---- a/a.txt
-+++ b/a.txt
-@@ -1,2 +1,2 @@
- unchanged
--old a
-+new a
-diff --git a/b.txt b/b.txt
-index 111..222 100644
---- a/b.txt
-+++ b/b.txt
-@@ -1,2 +1,2 @@
- unchanged
--old b
-+new b
-";
-        let files = parse_unified_diff(diff);
-        assert_eq!(files.len(), 2);
-        assert_eq!(files[0].path, "a.txt");
-        assert_eq!(files[1].path, "b.txt");
+    fn advance(&mut self) {
+        if self
+            .ahead
+            .pop_front()
+            .or_else(|| self.lines.next())
+            .is_some()
+        {
+            self.line_no += 1;
+        }
     }
+}
 
-    #[test]
-    fn test_parse_renamed_file() {
-        let diff = "\
-diff --git a/old_name.txt b/new_name.txt
-similarity index 100%
-rename from old_name.txt
-rename to new_name.txt
-";
-        let files = parse_unified_diff(diff);
-        assert_eq!(files.len(), 1);
+/// Parses the hunk whose `@@` header is the cursor's next line, leaving the cursor on
+/// the first line after it. With `counted`, the hunk also ends once the header's line counts are
+/// used up, which plain `diff -u` output needs because nothing else marks where one
+/// file's hunks stop and the next file's `---` header starts. A header with malformed
+/// or out-of-range counts is reported in `warnings` and its hunk skipped, as are lines
+/// inside a hunk that have no known prefix.
+fn parse_hunk<'a>(
+    lines: &mut LineCursor<'a, impl Iterator<Item = &'a str>>,
+    counted: bool,
+    warnings: &mut Vec<ParseWarning>,
+) -> Option<DiffHunk> {
+    let header = lines.peek()?;
+    let Some((old_start, old_count, new_start, new_count)) = parse_hunk_header(header) else {
+        warnings.push(ParseWarning {
+            line_no: lines.line_no,
+            content: header.to_string(),
+            message: "Malformed or out-of-range hunk header".to_string(),
+        });
+        return None;
+    };
+    let mut hunk_lines: Vec<DiffLine> = Vec::new();
+    let mut old_line = old_start;
+    let mut new_line = new_start;
+
+    lines.advance();
+
+    while let Some(hline) = lines.peek() {
+        if hline.starts_with("diff --git ") || hline.starts_with("@@ ") {
+            break;
+        }
+
+        if hline == "\\ No newline at end of file" {
+            if let Some(last) = hunk_lines.last_mut() {
+                last.no_newline = true;
+            }
+            lines.advance();
+            continue;
+        }
+
+        let complete = old_line >= old_start + old_count && new_line >= new_start + new_count;
+        // Past the lines the header promised, `-- ` is a signature rather than a `- `
+        // deletion
+        if complete && (counted || is_mail_boundary(hline)) {
+            break;
+        }
+
+        if let Some(content) = hline.strip_prefix('+') {
+            hunk_lines.push(DiffLine {
+                content: content.to_string(),
+                line_type: LineType::Addition,
+                old_line_no: None,
+                new_line_no: Some(new_line),
+                no_newline: false,
+                leading_whitespace: leading_whitespace(content),
+                run_start: false,
+                run_end: false,
+                word_ranges: Vec::new(),
+                covered: None,
+            });
+            new_line = new_line.saturating_add(1);
+        } else if let Some(content) = hline.strip_prefix('-') {
+            hunk_lines.push(DiffLine {
+                content: content.to_string(),
+                line_type: LineType::Deletion,
+                old_line_no: Some(old_line),
+                new_line_no: None,
+                no_newline: false,
+                leading_whitespace: leading_whitespace(content),
+                run_start: false,
+                run_end: false,
+                word_ranges: Vec::new(),
+                covered: None,
+            });
+            old_line = old_line.saturating_add(1);
+        } else if let Some(content) = hline.strip_prefix(' ') {
+            hunk_lines.push(DiffLine {
+                content: content.to_string(),
+                line_type: LineType::Context,
+                old_line_no: Some(old_line),
+                new_line_no: Some(new_line),
+                no_newline: false,
+                leading_whitespace: leading_whitespace(content),
+                run_start: false,
+                run_end: false,
+                word_ranges: Vec::new(),
+                covered: None,
+            });
+            old_line = old_line.saturating_add(1);
+            new_line = new_line.saturating_add(1);
+        } else {
+            warnings.push(ParseWarning {
+                line_no: lines.line_no,
+                content: hline.to_string(),
+                message: "Unrecognized line in hunk".to_string(),
+            });
+            lines.advance();
+            continue;
+        }
+
+        lines.advance();
+    }
+    mark_runs(&mut hunk_lines);
+
+    Some(DiffHunk {
+        header: header.to_string(),
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+        is_whitespace_only: is_whitespace_only(&hunk_lines),
+        lines: hunk_lines,
+    })
+}
+
+/// Sets `run_start`/`run_end` on each line by comparing its type with its neighbours'.
+fn mark_runs(lines: &mut [DiffLine]) {
+    for idx in 0..lines.len() {
+        let line_type = &lines[idx].line_type;
+        let run_start = idx == 0 || lines[idx - 1].line_type != *line_type;
+        let run_end = lines
+            .get(idx + 1)
+            .is_none_or(|next| next.line_type != *line_type);
+        lines[idx].run_start = run_start;
+        lines[idx].run_end = run_end;
+    }
+}
+
+fn leading_whitespace(content: &str) -> String {
+    content
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// True when the hunk has changes and, with all whitespace removed, its deleted lines
+/// and added lines are the same multiset. Lines that are blank after stripping are
+/// ignored, so adding or dropping blank lines also counts as whitespace-only.
+fn is_whitespace_only(lines: &[DiffLine]) -> bool {
+    let stripped = |line_type: LineType| {
+        let mut contents: Vec<String> = lines
+            .iter()
+            .filter(|l| l.line_type == line_type)
+            .map(|l| {
+                l.content
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<String>()
+            })
+            .filter(|c| !c.is_empty())
+            .collect();
+        contents.sort();
+        contents
+    };
+
+    lines.iter().any(|l| l.line_type != LineType::Context)
+        && stripped(LineType::Deletion) == stripped(LineType::Addition)
+}
+
+/// Path from a `diff --git <src-prefix><path> <dst-prefix><path>` header. The prefixes
+/// are `a/` and `b/` by default, but `diff.srcPrefix`/`diff.dstPrefix` (or
+/// `--no-prefix`) change them, so the header is split where both sides end in the same
+/// path after a prefix of the same depth.
+fn git_header_path(header: &str) -> Option<&str> {
+    let rest = header.strip_prefix("diff --git ")?;
+    for (space, _) in rest.match_indices(' ') {
+        let (left, right) = (&rest[..space], &rest[space + 1..]);
+        // Candidate prefixes: none, or everything up to a '/' in the source side
+        let starts = std::iter::once(0).chain(left.match_indices('/').map(|(i, _)| i + 1));
+        for start in starts {
+            let (src_prefix, path) = left.split_at(start);
+            if path.is_empty() {
+                continue;
+            }
+            if let Some(dst_prefix) = right.strip_suffix(path) {
+                let depth = |prefix: &str| prefix.matches('/').count();
+                let is_prefix = dst_prefix.is_empty() || dst_prefix.ends_with('/');
+                if is_prefix && depth(src_prefix) == depth(dst_prefix) {
+                    return Some(path);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Path from a `---`/`+++` line of a git diff, dropping the (possibly custom) prefix:
+/// git always writes one, unless `--no-prefix` is in effect.
+fn git_marker_path(marker: &str) -> Option<&str> {
+    let marker = plain_header_path(marker);
+    if marker == "/dev/null" {
+        return None;
+    }
+    Some(marker.split_once('/').map_or(marker, |(_, path)| path))
+}
+
+/// Path from a plain `diff -u` file header, dropping the tab-separated timestamp.
+fn plain_header_path(header: &str) -> &str {
+    header.split('\t').next().unwrap_or(header).trim_end()
+}
+
+/// Path from a Mercurial `diff -r <rev> [-r <rev>] <path>` header.
+fn hg_header_path(line: &str) -> Option<&str> {
+    let mut rest = line.strip_prefix("diff ")?;
+    while let Some(after_flag) = rest.strip_prefix("-r ") {
+        // Skip the revision following each -r
+        rest = after_flag.split_once(' ').map(|(_, r)| r)?;
+    }
+    Some(rest).filter(|p| !p.is_empty() && !p.starts_with('-'))
+}
+
+/// Fallback for diffs without `diff --git` lines (e.g. `diff -u old new` or `hg diff`):
+/// each file starts at a `---`/`+++` pair, and a `/dev/null` side marks an added or
+/// deleted file. A preceding Mercurial `diff -r` header supplies the path, since its
+/// `---`/`+++` lines carry `a/`/`b/` prefixes.
+fn parse_plain_unified_diff<'a>(
+    lines: &mut LineCursor<'a, impl Iterator<Item = &'a str>>,
+    warnings: &mut Vec<ParseWarning>,
+) -> Vec<DiffFile> {
+    let mut files: Vec<DiffFile> = Vec::new();
+    let mut header_path: Option<&str> = None;
+
+    while let Some(line) = lines.peek() {
+        if line.starts_with("diff -r ") {
+            header_path = hg_header_path(line);
+            lines.advance();
+            continue;
+        }
+
+        let (Some(old), Some(new)) = (
+            line.strip_prefix("--- "),
+            lines.peek_nth(1).and_then(|l| l.strip_prefix("+++ ")),
+        ) else {
+            lines.advance();
+            continue;
+        };
+        let (old, new) = (plain_header_path(old), plain_header_path(new));
+        lines.advance();
+        lines.advance();
+
+        let mut hunks: Vec<DiffHunk> = Vec::new();
+        while lines.peek().is_some_and(|l| l.starts_with("@@ ")) {
+            match parse_hunk(lines, true, warnings) {
+                Some(hunk) => hunks.push(hunk),
+                None => lines.advance(),
+            }
+        }
+
+        let (path, status) = if old == "/dev/null" {
+            (new, FileStatus::Added)
+        } else if new == "/dev/null" {
+            (old, FileStatus::Deleted)
+        } else {
+            (new, FileStatus::Modified)
+        };
+        let path = header_path.take().unwrap_or(path);
+        files.push(DiffFile {
+            path: path.to_string(),
+            hunks,
+            status,
+            ..Default::default()
+        });
+    }
+
+    files
+}
+
+pub fn parse_unified_diff(diff_text: &str) -> Vec<DiffFile> {
+    parse_unified_diff_verbose(diff_text).0
+}
+
+/// Like `parse_unified_diff`, but also returns what was skipped while parsing.
+pub fn parse_unified_diff_verbose(diff_text: &str) -> (Vec<DiffFile>, Vec<ParseWarning>) {
+    parse_unified_diff_lines(diff_text.lines())
+}
+
+/// Parses a diff one line at a time, holding only the parsed files and a couple of lines
+/// of lookahead, so a large diff can be read straight from a pipe or file. The first file
+/// header decides the format: `diff --git` selects git's, while a `diff -r` header or a
+/// `---`/`+++` pair selects the plain `diff -u` fallback. Anything before it (commit
+/// messages, mail headers) is skipped.
+pub fn parse_unified_diff_lines<'a>(
+    lines: impl Iterator<Item = &'a str>,
+) -> (Vec<DiffFile>, Vec<ParseWarning>) {
+    let mut lines = LineCursor::new(lines);
+    let mut warnings: Vec<ParseWarning> = Vec::new();
+
+    while let Some(line) = lines.peek() {
+        if line.starts_with("diff --git ") {
+            let files = parse_git_diff(&mut lines, false, &mut warnings);
+            return (files, warnings);
+        }
+        if line.starts_with("diff -r ")
+            || (line.starts_with("--- ")
+                && lines.peek_nth(1).is_some_and(|l| l.starts_with("+++ ")))
+        {
+            let files = parse_plain_unified_diff(&mut lines, &mut warnings);
+            return (files, warnings);
+        }
+        lines.advance();
+    }
+
+    (Vec::new(), warnings)
+}
+
+/// Parses `git diff --word-diff=porcelain` output. Each line of a hunk is rebuilt from
+/// its words: an unchanged line becomes a context line, and a changed one becomes a
+/// deletion (unless it was added) and an addition (unless it was removed), with
+/// `word_ranges` marking the words that changed.
+pub fn parse_word_diff(diff_text: &str) -> Vec<DiffFile> {
+    let mut lines = LineCursor::new(diff_text.lines());
+    let mut warnings: Vec<ParseWarning> = Vec::new();
+    while let Some(line) = lines.peek() {
+        if line.starts_with("diff --git ") {
+            return parse_git_diff(&mut lines, true, &mut warnings);
+        }
+        lines.advance();
+    }
+    Vec::new()
+}
+
+/// One line of a word-diff hunk: the words up to a `~`, applied to each side.
+#[derive(Default)]
+struct WordDiffLine {
+    old: String,
+    new: String,
+    old_ranges: Vec<(usize, usize)>,
+    new_ranges: Vec<(usize, usize)>,
+    in_old: bool,
+    in_new: bool,
+    no_newline: bool,
+}
+
+impl WordDiffLine {
+    fn is_empty(&self) -> bool {
+        !self.in_old && !self.in_new
+    }
+}
+
+/// `parse_hunk` for word-diff output, where `~` ends a line and every other line is
+/// one word (or run of words) prefixed with ` `, `-` or `+`.
+fn parse_word_diff_hunk<'a>(
+    lines: &mut LineCursor<'a, impl Iterator<Item = &'a str>>,
+    warnings: &mut Vec<ParseWarning>,
+) -> Option<DiffHunk> {
+    let header = lines.peek()?;
+    let Some((old_start, old_count, new_start, new_count)) = parse_hunk_header(header) else {
+        warnings.push(ParseWarning {
+            line_no: lines.line_no,
+            content: header.to_string(),
+            message: "Malformed or out-of-range hunk header".to_string(),
+        });
+        return None;
+    };
+    lines.advance();
+
+    let mut word_lines: Vec<WordDiffLine> = Vec::new();
+    let mut current = WordDiffLine::default();
+    while let Some(wline) = lines.peek() {
+        if wline.starts_with("diff --git ") || wline.starts_with("@@ ") {
+            break;
+        }
+        if wline == "~" {
+            word_lines.push(std::mem::take(&mut current));
+        } else if wline == "\\ No newline at end of file" {
+            if let Some(last) = word_lines.last_mut() {
+                last.no_newline = true;
+            }
+        } else if let Some(word) = wline.strip_prefix(' ') {
+            current.old.push_str(word);
+            current.new.push_str(word);
+            current.in_old = true;
+            current.in_new = true;
+        } else if let Some(word) = wline.strip_prefix('-') {
+            let start = current.old.len();
+            current.old.push_str(word);
+            current.old_ranges.push((start, current.old.len()));
+            current.in_old = true;
+        } else if let Some(word) = wline.strip_prefix('+') {
+            let start = current.new.len();
+            current.new.push_str(word);
+            current.new_ranges.push((start, current.new.len()));
+            current.in_new = true;
+        } else {
+            warnings.push(ParseWarning {
+                line_no: lines.line_no,
+                content: wline.to_string(),
+                message: "Unrecognized line in hunk".to_string(),
+            });
+        }
+        lines.advance();
+    }
+    if !current.is_empty() {
+        word_lines.push(current);
+    }
+
+    // A bare `~` is an added or removed blank line, and nothing says which. Blank
+    // context lines are written as ` `, so the header's counts settle it: the old side
+    // takes what it is missing first, as deletions come before additions.
+    let mut missing_old =
+        (old_count as usize).saturating_sub(word_lines.iter().filter(|l| l.in_old).count());
+    let mut missing_new =
+        (new_count as usize).saturating_sub(word_lines.iter().filter(|l| l.in_new).count());
+    for line in word_lines.iter_mut().filter(|l| l.is_empty()) {
+        if missing_old > 0 {
+            line.in_old = true;
+            missing_old -= 1;
+        } else if missing_new > 0 {
+            line.in_new = true;
+            missing_new -= 1;
+        } else {
+            line.in_old = true;
+            line.in_new = true;
+        }
+    }
+
+    let mut hunk_lines: Vec<DiffLine> = Vec::new();
+    let mut old_line = old_start;
+    let mut new_line = new_start;
+    let diff_line = |content: String, line_type, old_line_no, new_line_no, word_ranges| DiffLine {
+        leading_whitespace: leading_whitespace(&content),
+        content,
+        line_type,
+        old_line_no,
+        new_line_no,
+        no_newline: false,
+        run_start: false,
+        run_end: false,
+        word_ranges,
+        covered: None,
+    };
+    for line in word_lines {
+        let unchanged =
+            line.in_old && line.in_new && line.old_ranges.is_empty() && line.new_ranges.is_empty();
+        if unchanged {
+            hunk_lines.push(diff_line(
+                line.new,
+                LineType::Context,
+                Some(old_line),
+                Some(new_line),
+                Vec::new(),
+            ));
+            old_line = old_line.saturating_add(1);
+            new_line = new_line.saturating_add(1);
+        } else {
+            if line.in_old {
+                hunk_lines.push(diff_line(
+                    line.old,
+                    LineType::Deletion,
+                    Some(old_line),
+                    None,
+                    line.old_ranges,
+                ));
+                old_line = old_line.saturating_add(1);
+            }
+            if line.in_new {
+                hunk_lines.push(diff_line(
+                    line.new,
+                    LineType::Addition,
+                    None,
+                    Some(new_line),
+                    line.new_ranges,
+                ));
+                new_line = new_line.saturating_add(1);
+            }
+        }
+        if line.no_newline {
+            if let Some(last) = hunk_lines.last_mut() {
+                last.no_newline = true;
+            }
+        }
+    }
+    mark_runs(&mut hunk_lines);
+
+    Some(DiffHunk {
+        header: header.to_string(),
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+        is_whitespace_only: is_whitespace_only(&hunk_lines),
+        lines: hunk_lines,
+    })
+}
+
+/// The `diff --git` parser; with `word_diff`, hunks are in `--word-diff=porcelain`
+/// form (see `parse_word_diff`).
+fn parse_git_diff<'a>(
+    lines: &mut LineCursor<'a, impl Iterator<Item = &'a str>>,
+    word_diff: bool,
+    warnings: &mut Vec<ParseWarning>,
+) -> Vec<DiffFile> {
+    let mut files: Vec<DiffFile> = Vec::new();
+
+    while let Some(line) = lines.peek() {
+        if line.starts_with("diff --git ") {
+            let mut path = String::new();
+            let mut old_path: Option<String> = None;
+            let mut status = FileStatus::Modified;
+            let mut hunks: Vec<DiffHunk> = Vec::new();
+            let mut old_blob: Option<String> = None;
+            let mut new_blob: Option<String> = None;
+            let mut old_mode: Option<String> = None;
+            let mut new_mode: Option<String> = None;
+            let mut similarity: Option<u8> = None;
+            let mut dissimilarity: Option<u8> = None;
+            let mut is_binary = false;
+
+            if let Some(header_path) = git_header_path(line) {
+                path = header_path.to_string();
+            }
+
+            lines.advance();
+
+            // Parse file metadata lines
+            while let Some(line) = lines.peek().filter(|l| !l.starts_with("diff --git ")) {
+                // The rest of a format-patch message, skipped up to the next `diff --git`
+                if is_mail_boundary(line) {
+                    break;
+                }
+                if let Some(line_status) = metadata_status(line) {
+                    status = line_status;
+                }
+                if let Some(mode) = line.strip_prefix("new file mode ") {
+                    new_mode = Some(mode.trim().to_string());
+                } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+                    old_mode = Some(mode.trim().to_string());
+                } else if let Some(mode) = line.strip_prefix("old mode ") {
+                    old_mode = Some(mode.trim().to_string());
+                } else if let Some(mode) = line.strip_prefix("new mode ") {
+                    new_mode = Some(mode.trim().to_string());
+                } else if let Some(from) = line.strip_prefix("rename from ") {
+                    old_path = Some(from.to_string());
+                } else if let Some(to) = line.strip_prefix("rename to ") {
+                    path = to.to_string();
+                } else if let Some(from) = line.strip_prefix("copy from ") {
+                    old_path = Some(from.to_string());
+                } else if let Some(to) = line.strip_prefix("copy to ") {
+                    path = to.to_string();
+                } else if let Some(percent) = line.strip_prefix("similarity index ") {
+                    similarity = parse_percentage(percent);
+                } else if let Some(percent) = line.strip_prefix("dissimilarity index ") {
+                    dissimilarity = parse_percentage(percent);
+                } else if let Some(index) = line.strip_prefix("index ") {
+                    if let Some((old, new, mode)) = parse_index_line(index) {
+                        old_blob = old;
+                        new_blob = new;
+                        // A mode on the index line means it is unchanged
+                        if let Some(mode) = mode {
+                            old_mode.get_or_insert_with(|| mode.clone());
+                            new_mode.get_or_insert(mode);
+                        }
+                    }
+                } else if line.starts_with("Binary files") || line == "GIT binary patch" {
+                    // Skip binary content entirely
+                    is_binary = true;
+                    break;
+                } else if let Some(marker) = line
+                    .strip_prefix("--- ")
+                    .or_else(|| line.strip_prefix("+++ "))
+                {
+                    // Only needed when the header could not be split
+                    if path.is_empty() {
+                        path = git_marker_path(marker).unwrap_or_default().to_string();
+                    }
+                } else if line.starts_with("@@ ") {
+                    let hunk = if word_diff {
+                        parse_word_diff_hunk(lines, warnings)
+                    } else {
+                        parse_hunk(lines, false, warnings)
+                    };
+                    if let Some(hunk) = hunk {
+                        hunks.push(hunk);
+                        continue; // Already at the next line
+                    }
+                } else {
+                    // Other metadata lines (similarity, mode changes, etc.)
+                }
+
+                lines.advance();
+            }
+
+            let old_link_target = if old_mode.as_deref() == Some(SYMLINK_MODE) {
+                first_line_of_type(&hunks, LineType::Deletion)
+            } else {
+                None
+            };
+            let new_link_target = if new_mode.as_deref() == Some(SYMLINK_MODE) {
+                first_line_of_type(&hunks, LineType::Addition)
+            } else {
+                None
+            };
+
+            let old_submodule_sha = submodule_sha(old_mode.as_deref(), &hunks, LineType::Deletion);
+            let new_submodule_sha = submodule_sha(new_mode.as_deref(), &hunks, LineType::Addition);
+
+            files.push(DiffFile {
+                is_symlink: old_mode.as_deref() == Some(SYMLINK_MODE)
+                    || new_mode.as_deref() == Some(SYMLINK_MODE),
+                path,
+                old_path,
+                hunks,
+                status,
+                old_blob,
+                new_blob,
+                old_mode,
+                new_mode,
+                old_link_target,
+                new_link_target,
+                similarity,
+                dissimilarity,
+                old_submodule_sha,
+                new_submodule_sha,
+                is_binary,
+                abs_path: None,
+            });
+        } else {
+            lines.advance();
+        }
+    }
+
+    files
+}
+
+/// Removes ANSI CSI escape sequences (`\x1b[32m`, `\x1b[m`, `\x1b[K`, ...) such as
+/// git's colored output, which would otherwise hide the `+`/`-` line prefixes.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            chars.next();
+            // Parameter and intermediate bytes, then one final byte
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Folds files that appear more than once (interrupted merges, hand-edited patches)
+/// into the first occurrence, appending the later hunks in order. The first block's
+/// metadata wins.
+pub fn merge_duplicate_files(files: Vec<DiffFile>) -> Vec<DiffFile> {
+    let mut merged: Vec<DiffFile> = Vec::with_capacity(files.len());
+    for file in files {
+        match merged.iter_mut().find(|f| f.path == file.path) {
+            Some(existing) => {
+                existing.hunks.extend(file.hunks);
+                existing.is_binary |= file.is_binary;
+            }
+            None => merged.push(file),
+        }
+    }
+    merged
+}
+
+/// The first entry of `overrides` for `path`'s extension, matched case-insensitively
+/// with or without the leading dot.
+fn binary_override_for(path: &str, overrides: &[(String, bool)]) -> Option<bool> {
+    let extension = std::path::Path::new(path).extension()?.to_str()?;
+    overrides
+        .iter()
+        .find(|(ext, _)| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+        .map(|(_, is_binary)| *is_binary)
+}
+
+/// Sets `is_binary` on files whose extension appears in `overrides`, in place of
+/// git's own guess. Forcing a file to binary drops its hunks. For a file git found
+/// binary to have hunks when forced to text, the diff must have been taken with them
+/// (see `with_forced_text`); this only clears the flag.
+pub fn apply_binary_overrides(files: Vec<DiffFile>, overrides: &[(String, bool)]) -> Vec<DiffFile> {
+    if overrides.is_empty() {
+        return files;
+    }
+    files
+        .into_iter()
+        .map(|mut file| {
+            if let Some(is_binary) = binary_override_for(&file.path, overrides) {
+                file.is_binary = is_binary;
+                if is_binary {
+                    file.hunks.clear();
+                }
+            }
+            file
+        })
+        .collect()
+}
+
+/// Replaces `\` with `/` in each file's `path` and `old_path`, so paths from Windows
+/// tools split into directories like git's own.
+pub fn normalize_path_separators(files: Vec<DiffFile>) -> Vec<DiffFile> {
+    files
+        .into_iter()
+        .map(|mut file| {
+            file.path = file.path.replace('\\', "/");
+            file.old_path = file.old_path.map(|p| p.replace('\\', "/"));
+            file
+        })
+        .collect()
+}
+
+/// Sets each file's `abs_path` from the working tree root `work_tree`. Deleted files
+/// get one too, though nothing exists there any more.
+pub fn set_abs_paths(files: &mut [DiffFile], work_tree: &str) {
+    for file in files {
+        let abs_path = std::path::Path::new(work_tree).join(&file.path);
+        file.abs_path = Some(abs_path.to_string_lossy().into_owned());
+    }
+}
+
+/// Keeps the files whose status is in `statuses`; an empty filter keeps everything.
+pub fn filter_by_status(files: Vec<DiffFile>, statuses: &[FileStatus]) -> Vec<DiffFile> {
+    if statuses.is_empty() {
+        return files;
+    }
+    files
+        .into_iter()
+        .filter(|f| statuses.contains(&f.status))
+        .collect()
+}
+
+/// Splits raw diff text into one chunk per `diff --git` block, so each file can be
+/// parsed (and sent to the frontend) independently of the rest of the diff. Plain
+/// `diff -u` output has no such header to split on reliably, so text without one is a
+/// single chunk (none when empty).
+pub fn split_file_chunks(diff_text: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = Vec::new();
+    let mut offset = 0;
+    for line in diff_text.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    if starts.is_empty() && !diff_text.is_empty() {
+        return vec![diff_text];
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let end = starts.get(idx + 1).copied().unwrap_or(diff_text.len());
+            &diff_text[start..end]
+        })
+        .collect()
+}
+
+/// How remote commands are run over ssh.
+///
+/// Host aliases from `~/.ssh/config` work as the `host` part of a remote, and only
+/// `ConnectTimeout` is forced on the command line. `BatchMode=yes` is on by default so a
+/// missing key fails fast instead of hanging on a prompt, but it can conflict with
+/// alias-based setups that rely on an agent or `ProxyCommand` needing interaction, so
+/// it can be turned off with `--no-batch-mode`.
+///
+/// Remote diffs and repo info retry connection-level failures `retries` times,
+/// doubling `retry_backoff` between attempts (`--ssh-retries`, `--ssh-retry-backoff-ms`).
+///
+/// `command` replaces `ssh` itself, e.g. a wrapper or a client with extra global
+/// options; it comes from `--ssh-command` or `GIT_SSH_COMMAND`.
+#[derive(Clone, Debug)]
+pub struct SshOptions {
+    pub batch_mode: bool,
+    pub retries: u32,
+    pub retry_backoff: Duration,
+    pub command: Option<String>,
+}
+
+impl Default for SshOptions {
+    fn default() -> Self {
+        SshOptions {
+            batch_mode: true,
+            retries: 2,
+            retry_backoff: Duration::from_millis(500),
+            command: None,
+        }
+    }
+}
+
+impl SshOptions {
+    fn args(&self) -> Vec<&'static str> {
+        let mut args = vec!["-o", "ConnectTimeout=10"];
+        if self.batch_mode {
+            args.extend(["-o", "BatchMode=yes"]);
+        }
+        args
+    }
+}
+
+/// How `git diff` treats whitespace differences.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum WhitespaceMode {
+    #[default]
+    Show,
+    /// `-w`
+    IgnoreAll,
+    /// `-b`
+    IgnoreChange,
+    /// `--ignore-space-at-eol`
+    IgnoreEol,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+/// What a diff compares, beyond what a plain `range` can express.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum DiffTarget {
+    /// `DiffOptions::range`, or the working tree against `HEAD` without one.
+    #[default]
+    Range,
+    /// The index (staged changes) against a commit: `git diff --cached <commit>`.
+    StagedAgainst(String),
+    /// Commits on the current branch that its upstream does not have:
+    /// `<upstream>...HEAD`.
+    AgainstUpstream,
+    /// Everything changed since the current branch forked from the named one, committed
+    /// or not, untracked files included: `git diff $(git merge-base HEAD <branch>)`.
+    /// Local repositories only.
+    MergeBaseWith(String),
+}
+
+/// `DiffTarget::AgainstUpstream` as git spells it; local diffs resolve the upstream
+/// first so a missing one gets a clear error.
+const UPSTREAM_RANGE: &str = "@{upstream}...HEAD";
+
+/// Context size for `DiffOptions::full_context`: the largest `-U` git accepts, so every
+/// line of a changed file lands in the one hunk.
+const FULL_CONTEXT_LINES: i32 = i32::MAX;
+
+/// Everything that shapes a `git diff` invocation. Missing fields deserialize to
+/// their defaults, so the frontend only sends what it changes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DiffOptions {
+    /// Defaults to the working tree against `HEAD`.
+    pub range: Option<String>,
+    pub context_lines: Option<u32>,
+    pub whitespace: WhitespaceMode,
+    pub algorithm: Option<DiffAlgorithm>,
+    /// Similarity percentage for rename detection (`-M<n>%`).
+    pub rename_threshold: Option<u8>,
+    /// Turn rename detection off (`--no-renames`, overriding `rename_threshold`): a
+    /// renamed file shows as a deletion plus an addition, so no file has
+    /// `FileStatus::Renamed` and `old_path` is always `None`.
+    pub no_renames: bool,
+    pub pathspecs: Vec<String>,
+    pub reverse: bool,
+    /// Fetch the remote-tracking refs named in `range` first (local repos only).
+    pub fetch_before_diff: bool,
+    /// Replaces `range` for targets other than `DiffTarget::Range`.
+    pub target: DiffTarget,
+    /// Show each changed file in full as a single hunk; overrides `context_lines`.
+    /// Output grows with file size rather than change size, so large files make for
+    /// large diffs.
+    pub full_context: bool,
+    /// Fold `diff --git` blocks for the same path into one file (see
+    /// `merge_duplicate_files`). A streamed diff is then parsed in one piece.
+    pub merge_duplicate_files: bool,
+    /// Remove ANSI escape sequences (see `strip_ansi`) before parsing, for output
+    /// colored by a `color.ui=always` config.
+    pub strip_ansi: bool,
+    /// `(extension, is_binary)` pairs that override git's binary detection (see
+    /// `apply_binary_overrides`). Forcing text reruns git with `--text` for those
+    /// files, so it is refused for remote, Mercurial and `diff_file` diffs.
+    pub binary_overrides: Vec<(String, bool)>,
+    /// Have `get_diff` return the unparsed diff text as well. Off by default since it
+    /// roughly doubles the payload.
+    pub include_raw: bool,
+    /// Parse this patch file instead of running git, so no repository is needed. The
+    /// other options do not apply to it, apart from the parsing ones.
+    pub diff_file: Option<String>,
+    /// Have git compute word-level changes (`--word-diff=porcelain`), parsed with
+    /// `parse_word_diff` so changed lines carry `DiffLine::word_ranges`. Git gives no
+    /// way to pair a multi-line edit's lines, so each changed line's deletion and
+    /// addition come out next to each other.
+    pub word_diff: bool,
+    /// Turn `\` path separators into `/` (see `normalize_path_separators`), for patches
+    /// made by Windows tools. Always done on Windows; elsewhere a `\` can be part of a
+    /// file name, so only when asked.
+    pub normalize_backslashes: bool,
+    /// The parent (1-based, first by default) a `<commit>^!` range compares the commit
+    /// to, passed to git as `<commit>^<n> <commit>`. Only matters for merges, where git
+    /// would otherwise show a combined diff against all parents, typically empty.
+    pub merge_parent: Option<u32>,
+    /// Run exactly the diff asked for: in a repository without commits git's own error
+    /// about `HEAD` comes back instead of a diff of the working tree against the index
+    /// (see `DiffOptions::local_args`). For scripts that want no guessing; the GUI
+    /// leaves it off.
+    pub strict: bool,
+    /// `(key, value)` pairs passed to git as `-c key=value` ahead of `diff`, for
+    /// settings without an option of their own (say `diff.indentHeuristic`). Keys must
+    /// look like `section.key` or `section.subsection.key`.
+    pub config_overrides: Vec<(String, String)>,
+    /// `diff.indentHeuristic`, which shifts ambiguous hunk boundaries to follow
+    /// indentation. On by default, as in git since 2.14, but always passed so a user's
+    /// config cannot change the hunks. An entry in `config_overrides` wins.
+    pub indent_heuristic: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            range: None,
+            context_lines: None,
+            whitespace: WhitespaceMode::default(),
+            algorithm: None,
+            rename_threshold: None,
+            no_renames: false,
+            pathspecs: Vec::new(),
+            reverse: false,
+            fetch_before_diff: false,
+            target: DiffTarget::default(),
+            full_context: false,
+            merge_duplicate_files: false,
+            strip_ansi: false,
+            binary_overrides: Vec::new(),
+            include_raw: false,
+            diff_file: None,
+            word_diff: false,
+            normalize_backslashes: false,
+            merge_parent: None,
+            strict: false,
+            config_overrides: Vec::new(),
+            indent_heuristic: true,
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn range(mut self, range: impl Into<String>) -> Self {
+        self.range = Some(range.into());
+        self
+    }
+
+    pub fn context_lines(mut self, lines: u32) -> Self {
+        self.context_lines = Some(lines);
+        self
+    }
+
+    pub fn whitespace(mut self, mode: WhitespaceMode) -> Self {
+        self.whitespace = mode;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: DiffAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    pub fn rename_threshold(mut self, percent: u8) -> Self {
+        self.rename_threshold = Some(percent);
+        self
+    }
+
+    pub fn no_renames(mut self, no_renames: bool) -> Self {
+        self.no_renames = no_renames;
+        self
+    }
+
+    pub fn pathspec(mut self, pathspec: impl Into<String>) -> Self {
+        self.pathspecs.push(pathspec.into());
+        self
+    }
+
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    pub fn fetch_before_diff(mut self, fetch: bool) -> Self {
+        self.fetch_before_diff = fetch;
+        self
+    }
+
+    pub fn target(mut self, target: DiffTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn full_context(mut self, full: bool) -> Self {
+        self.full_context = full;
+        self
+    }
+
+    pub fn merge_duplicate_files(mut self, merge: bool) -> Self {
+        self.merge_duplicate_files = merge;
+        self
+    }
+
+    pub fn strip_ansi(mut self, strip: bool) -> Self {
+        self.strip_ansi = strip;
+        self
+    }
+
+    pub fn binary_override(mut self, extension: impl Into<String>, is_binary: bool) -> Self {
+        self.binary_overrides.push((extension.into(), is_binary));
+        self
+    }
+
+    pub fn diff_file(mut self, path: impl Into<String>) -> Self {
+        self.diff_file = Some(path.into());
+        self
+    }
+
+    pub fn word_diff(mut self, word_diff: bool) -> Self {
+        self.word_diff = word_diff;
+        self
+    }
+
+    pub fn normalize_backslashes(mut self, normalize: bool) -> Self {
+        self.normalize_backslashes = normalize;
+        self
+    }
+
+    pub fn merge_parent(mut self, parent: u32) -> Self {
+        self.merge_parent = Some(parent);
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn indent_heuristic(mut self, enabled: bool) -> Self {
+        self.indent_heuristic = enabled;
+        self
+    }
+
+    pub fn config_override(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config_overrides.push((key.into(), value.into()));
+        self
+    }
+
+    /// Whether `binary_overrides` forces some extension to text, which only a local
+    /// git diff can honour (see `with_forced_text`).
+    pub fn forces_text(&self) -> bool {
+        self.binary_overrides
+            .iter()
+            .any(|(_, is_binary)| !is_binary)
+    }
+
+    /// Rejects `config_overrides` keys git would misread, such as one with no section
+    /// or one containing `=` that would split the pair elsewhere.
+    fn check_config_overrides(&self) -> Result<(), String> {
+        for (key, _) in &self.config_overrides {
+            if !is_valid_config_key(key) {
+                return Err(format!(
+                    "Invalid git config key '{}': expected section.key",
+                    key
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The revision argument the user asked for, if any.
+    fn revision(&self) -> Option<&str> {
+        match &self.target {
+            DiffTarget::Range => self.range.as_deref(),
+            DiffTarget::StagedAgainst(commit) => Some(commit),
+            DiffTarget::AgainstUpstream => Some(UPSTREAM_RANGE),
+            // Stands in for the merge base, which only `local_args` resolves
+            DiffTarget::MergeBaseWith(branch) => Some(branch),
+        }
+    }
+
+    /// The `git` argv (without the `git` itself) for these options.
+    pub fn to_args(&self) -> Vec<String> {
+        self.build_args(Some(self.revision().unwrap_or("HEAD")))
+    }
+
+    /// Same as `to_args` but comparing the working tree to the index, for repos
+    /// where `HEAD` does not exist yet.
+    fn args_without_head(&self) -> Vec<String> {
+        self.build_args(None)
+    }
+
+    /// `to_args` for a local repository, falling back to `args_without_head` when the
+    /// repository has no commits yet, unless `strict`.
+    fn local_args(&self, repo_path: &str) -> Result<Vec<String>, String> {
+        self.check_config_overrides()?;
+        if self.target == DiffTarget::AgainstUpstream {
+            let range = format!("{}...HEAD", resolve_upstream(repo_path)?);
+            return Ok(self.build_args(Some(&range)));
+        }
+        if let DiffTarget::MergeBaseWith(branch) = &self.target {
+            let base = crate::repo_info::get_merge_base("HEAD", branch, repo_path)?;
+            return Ok(self.build_args(Some(&base)));
+        }
+        if self.strict || check_can_diff(self.revision(), repo_path)? {
+            Ok(self.to_args())
+        } else {
+            Ok(self.args_without_head())
+        }
+    }
+
+    /// `-c <key>=<value>` pairs placed before the `diff` subcommand.
+    fn config_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-c".to_string(),
+            format!("diff.indentHeuristic={}", self.indent_heuristic),
+        ];
+        for (key, value) in &self.config_overrides {
+            args.push("-c".to_string());
+            args.push(format!("{key}={value}"));
+        }
+        args
+    }
+
+    /// Flags shaping the patch itself (context, whitespace, algorithm, renames,
+    /// direction and word diff), shared by every `git diff` the options produce.
+    fn push_format_args(&self, args: &mut Vec<String>) {
+        if self.full_context {
+            args.push(format!("-U{FULL_CONTEXT_LINES}"));
+        } else if let Some(n) = self.context_lines {
+            args.push(format!("-U{n}"));
+        }
+        match self.whitespace {
+            WhitespaceMode::Show => {}
+            WhitespaceMode::IgnoreAll => args.push("-w".to_string()),
+            WhitespaceMode::IgnoreChange => args.push("-b".to_string()),
+            WhitespaceMode::IgnoreEol => args.push("--ignore-space-at-eol".to_string()),
+        }
+        if let Some(algorithm) = self.algorithm {
+            let name = match algorithm {
+                DiffAlgorithm::Myers => "myers",
+                DiffAlgorithm::Minimal => "minimal",
+                DiffAlgorithm::Patience => "patience",
+                DiffAlgorithm::Histogram => "histogram",
+            };
+            args.push(format!("--diff-algorithm={name}"));
+        }
+        if self.no_renames {
+            args.push("--no-renames".to_string());
+        } else if let Some(percent) = self.rename_threshold {
+            args.push(format!("-M{percent}%"));
+        }
+        if self.reverse {
+            args.push("-R".to_string());
+        }
+        if self.word_diff {
+            args.push("--word-diff=porcelain".to_string());
+        }
+    }
+
+    /// Arguments for the new-file diff of an untracked `path`, formatted like the
+    /// main diff so the two can be parsed as one.
+    fn untracked_file_args(&self, path: &str) -> Vec<String> {
+        let mut args = self.config_args();
+        args.extend(["diff".to_string(), "--no-index".to_string()]);
+        self.push_format_args(&mut args);
+        args.extend(["--".to_string(), "/dev/null".to_string(), path.to_string()]);
+        args
+    }
+
+    fn build_args(&self, revision: Option<&str>) -> Vec<String> {
+        let mut args = self.config_args();
+        args.push("diff".to_string());
+        if let DiffTarget::StagedAgainst(_) = self.target {
+            args.push("--cached".to_string());
+        }
+        self.push_format_args(&mut args);
+        // A revision starting with `-` (from a config file, say) must not be read as an
+        // option such as `--output=<file>`
+        if revision.is_some() {
+            args.push("--end-of-options".to_string());
+        }
+        match revision.map(|r| (r, r.strip_suffix("^!"))) {
+            Some((_, Some(commit))) => {
+                let parent = self.merge_parent.unwrap_or(1).max(1);
+                args.push(format!("{commit}^{parent}"));
+                args.push(commit.to_string());
+            }
+            Some((revision, None)) => args.push(revision.to_string()),
+            None => {}
+        }
+        if !self.pathspecs.is_empty() {
+            args.push("--".to_string());
+            args.extend(self.pathspecs.iter().cloned());
+        }
+        args
+    }
+}
+
+/// Whether `key` has git's `section[.subsection].name` shape: a section of letters,
+/// digits and `-`, and a name that starts with a letter and continues with
+/// letters, digits and `-`. Subsections may hold anything but a newline, NUL or `=`
+/// (git would end the key at the first `=` of `-c <key>=<value>`).
+fn is_valid_config_key(key: &str) -> bool {
+    let (Some((section, _)), Some((middle, name))) = (key.split_once('.'), key.rsplit_once('.'))
+    else {
+        return false;
+    };
+    let section_ok = !section.is_empty()
+        && section
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let name_ok = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let subsection_ok = !middle[section.len()..].contains(['\n', '\0', '=']);
+    section_ok && name_ok && subsection_ok
+}
+
+/// Version control system a local diff is taken from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Vcs {
+    #[default]
+    Git,
+    Hg,
+}
+
+/// Builds an `hg` command with a stable environment; `HGPLAIN` disables user
+/// configuration that changes output (color, pager, aliases, localization).
+pub fn hg_command() -> Command {
+    let mut cmd = Command::new("hg");
+    cmd.env("HGPLAIN", "1").env("LC_ALL", "C");
+    cmd
+}
+
+/// Maps a git-style range to `hg diff` arguments: `a..b` compares two revisions,
+/// `a...b` compares `b` to the common ancestor, and a single revision compares the
+/// working directory to it.
+fn hg_diff_args(range: Option<&str>) -> Vec<String> {
+    let mut args = vec!["diff".to_string()];
+    let Some(r) = range else {
+        return args;
+    };
+    if let Some((a, b)) = r.split_once("...") {
+        args.extend([
+            "-r".to_string(),
+            format!("ancestor({}, {})", a, b),
+            "-r".to_string(),
+            b.to_string(),
+        ]);
+    } else if let Some((a, b)) = r.split_once("..") {
+        args.extend([
+            "-r".to_string(),
+            a.to_string(),
+            "-r".to_string(),
+            b.to_string(),
+        ]);
+    } else {
+        args.extend(["-r".to_string(), r.to_string()]);
+    }
+    args
+}
+
+/// Mercurial counterpart of `run_git_diff`; without a range it shows uncommitted changes.
+pub fn run_hg_diff(range: Option<&str>, repo_path: &str) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+
+    let output = hg_command()
+        .args(hg_diff_args(range))
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute hg diff: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+/// Stash shown by `run_stash_diff` when none is named.
+pub const DEFAULT_STASH: &str = "stash@{0}";
+
+/// The patch of a stash entry (`git stash show -p`), relative to the commit it was
+/// made on.
+pub fn run_stash_diff(stash_ref: &str, repo_path: &str) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+    if stash_ref.starts_with('-') {
+        return Err(format!("Invalid stash reference '{}'", stash_ref));
+    }
+
+    let output = git_command()
+        .args(["stash", "show", "-p", stash_ref])
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git stash show: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "Failed to show stash '{}': {}",
+            stash_ref,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Names the branch `resolve_ref` could not find, as base or head of a comparison.
+fn check_branch(role: &str, name: &str, resolved: Result<String, String>) -> Result<(), String> {
+    match resolved {
+        Ok(_) => Ok(()),
+        Err(e) if e.starts_with("Unknown revision") => {
+            Err(format!("Unknown {} branch '{}'", role, name))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn branch_diff_options(base: &str, head: &str) -> DiffOptions {
+    DiffOptions::new().range(format!("{}...{}", base, head))
+}
+
+/// The changes on `head` since it forked from `base` (`base...head`), parsed. Both are
+/// checked first, so a mistyped name gets an error saying which one is unknown.
+pub fn diff_branches(base: &str, head: &str, repo_path: &str) -> Result<Vec<DiffFile>, String> {
+    check_branch("base", base, crate::repo_info::resolve_ref(base, repo_path))?;
+    check_branch("head", head, crate::repo_info::resolve_ref(head, repo_path))?;
+    let diff_text = run_git_diff(&branch_diff_options(base, head), repo_path)?;
+    Ok(parse_unified_diff(&diff_text))
+}
+
+/// `diff_branches` over ssh.
+pub fn diff_remote_branches(
+    remote: &str,
+    base: &str,
+    head: &str,
+    ssh: &SshOptions,
+) -> Result<Vec<DiffFile>, String> {
+    let resolve = |name| crate::repo_info::resolve_remote_ref(remote, name, ssh);
+    check_branch("base", base, resolve(base))?;
+    check_branch("head", head, resolve(head))?;
+    let diff_text = run_remote_git_diff(remote, &branch_diff_options(base, head), ssh)?;
+    Ok(parse_unified_diff(&diff_text))
+}
+
+/// The patch between two files on disk that need not be in any repository
+/// (`git diff --no-index`). Identical files give an empty patch.
+pub fn diff_no_index(left: &str, right: &str) -> Result<String, String> {
+    let output = git_command()
+        .args(["diff", "--no-index", "--", left, right])
+        .output()
+        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+
+    // Exit code 1 means the files differ, but git also uses it for unreadable paths;
+    // only the former prints a patch
+    match output.status.code() {
+        Some(0) => Ok(String::new()),
+        Some(1) if !output.stdout.is_empty() => {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+        _ => Err(format!(
+            "Failed to diff '{}' and '{}': {}",
+            left,
+            right,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Builds a `git` command with a stable environment: a C locale so messages such as
+/// `Binary files ... differ` are never translated, and no pager or color regardless
+/// of the user's git config.
+pub fn git_command() -> Command {
+    let mut cmd = Command::new("git");
+    cmd.env("LC_ALL", "C").env("GIT_PAGER", "cat").args([
+        "-c",
+        "color.ui=false",
+        "-c",
+        "core.pager=cat",
+    ]);
+    cmd
+}
+
+/// Fails with a clear message when `repo_path` is missing or not a directory, instead of
+/// the opaque OS error git's spawn would report.
+pub fn check_repo_path(repo_path: &str) -> Result<(), String> {
+    let path = std::path::Path::new(repo_path);
+    if !path.exists() {
+        Err(format!("Repository path '{}' does not exist", repo_path))
+    } else if !path.is_dir() {
+        Err(format!(
+            "Repository path '{}' is not a directory",
+            repo_path
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Environment prefix for remote commands, mirroring what `git_command` sets locally:
+/// the `GIT_CONFIG_*` variables (git 2.31+) stand in for its `-c` options, so a remote
+/// `color.ui=always` cannot color the output.
+const REMOTE_GIT_ENV: &str = "export LC_ALL=C GIT_PAGER=cat GIT_CONFIG_COUNT=2 \
+    GIT_CONFIG_KEY_0=color.ui GIT_CONFIG_VALUE_0=false \
+    GIT_CONFIG_KEY_1=core.pager GIT_CONFIG_VALUE_1=cat; ";
+
+/// Splits a command line such as `GIT_SSH_COMMAND` into words the way a POSIX shell
+/// would for plain words and quoting: single quotes are literal, double quotes and
+/// backslashes escape. Variables and other expansions are not supported.
+fn split_command_line(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Builds an ssh `Command` that runs `remote_cmd` on `host`, with `options.command`
+/// (program and its own arguments) in place of `ssh` when set.
+pub fn ssh_command(host: &str, remote_cmd: &str, options: &SshOptions) -> Command {
+    let argv = options
+        .command
+        .as_deref()
+        .map(split_command_line)
+        .unwrap_or_default();
+    let mut cmd = match argv.split_first() {
+        Some((program, args)) => {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        }
+        None => Command::new("ssh"),
+    };
+    cmd.args(options.args())
+        .arg(host)
+        .arg(format!("{}{}", REMOTE_GIT_ENV, remote_cmd));
+    cmd
+}
+
+/// Stderr of ssh itself (exit code 255) when the connection dropped rather than being
+/// refused outright; worth another attempt.
+const TRANSIENT_SSH_ERRORS: [&str; 5] = [
+    "Connection reset",
+    "Connection closed",
+    "Broken pipe",
+    "kex_exchange_identification",
+    "Connection timed out",
+];
+
+/// Whether a failed ssh invocation may succeed if repeated. Errors from the remote
+/// shell or git never exit with 255, so those are not retried.
+fn is_transient_ssh_failure(code: Option<i32>, stderr: &str) -> bool {
+    code == Some(255) && TRANSIENT_SSH_ERRORS.iter().any(|e| stderr.contains(e))
+}
+
+/// Runs `remote_cmd` on `host`, retrying transient connection failures as configured
+/// in `options`.
+pub fn ssh_output_with_retry(
+    host: &str,
+    remote_cmd: &str,
+    options: &SshOptions,
+) -> Result<Output, String> {
+    let mut delay = options.retry_backoff;
+    let mut attempt = 0;
+    loop {
+        let output = ssh_command(host, remote_cmd, options)
+            .output()
+            .map_err(|e| format!("Failed to execute ssh: {}", e))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if attempt >= options.retries || !is_transient_ssh_failure(output.status.code(), &stderr) {
+            return Ok(output);
+        }
+        std::thread::sleep(delay);
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+    }
+}
+
+/// Like `ssh_output_with_retry`, but waits between attempts without blocking the
+/// async runtime, and stops (killing ssh) as soon as `cancel` is notified.
+async fn ssh_output_with_retry_cancellable(
+    host: &str,
+    remote_cmd: &str,
+    options: &SshOptions,
+    cancel: &Notify,
+) -> Result<Output, String> {
+    let mut delay = options.retry_backoff;
+    let mut attempt = 0;
+    loop {
+        let cmd = ssh_command(host, remote_cmd, options);
+        let output = output_or_cancel(cmd, cancel, "ssh").await?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if attempt >= options.retries || !is_transient_ssh_failure(output.status.code(), &stderr) {
+            return Ok(output);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = cancel.notified() => return Err(DIFF_CANCELLED.to_string()),
+        }
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+    }
+}
+
+const REMOTE_FORMAT_HINT: &str = "Expected format: host:/path/to/repo or user@host:/path/to/repo";
+
+pub fn parse_remote_path(remote: &str) -> Result<(&str, &str), String> {
+    let invalid = |reason: &str| {
+        format!(
+            "Invalid remote path '{}': {}. {}",
+            remote, reason, REMOTE_FORMAT_HINT
+        )
+    };
+
+    let (host, path) = remote
+        .split_once(':')
+        .ok_or_else(|| invalid("missing ':' between host and path"))?;
+
+    let hostname = match host.split_once('@') {
+        Some((user, hostname)) => {
+            if user.is_empty() {
+                return Err(invalid("user before '@' is empty"));
+            }
+            hostname
+        }
+        None => host,
+    };
+    if hostname.is_empty() {
+        return Err(invalid("host is empty"));
+    }
+    // ssh would take it as an option such as `-oProxyCommand=<cmd>`
+    if host.starts_with('-') {
+        return Err(invalid("host must not start with '-'"));
+    }
+    if path.is_empty() {
+        return Err(invalid("repository path is empty"));
+    }
+    if !path.starts_with('/') {
+        return Err(invalid(&format!(
+            "repository path '{}' must be absolute",
+            path
+        )));
+    }
+
+    Ok((host, path))
+}
+
+pub fn run_remote_git_diff(
+    remote: &str,
+    options: &DiffOptions,
+    ssh: &SshOptions,
+) -> Result<String, String> {
+    let (host, path) = parse_remote_path(remote)?;
+    check_remote_target(options)?;
+
+    let output = ssh_output_with_retry(host, &remote_diff_command(path, options), ssh)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(describe_remote_error(&stderr, host, path))
+    }
+}
+
+pub(crate) fn check_remote_target(options: &DiffOptions) -> Result<(), String> {
+    options.check_config_overrides()?;
+    if options.forces_text() {
+        return Err(FORCED_TEXT_LOCAL_ONLY.to_string());
+    }
+    match options.target {
+        DiffTarget::MergeBaseWith(_) => {
+            Err("Merge-base diffs are only supported for local repositories".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn remote_diff_command(path: &str, options: &DiffOptions) -> String {
+    let args: Vec<String> = options.to_args().iter().map(|a| shell_quote(a)).collect();
+    format!("cd {} && git {}", shell_quote(path), args.join(" "))
+}
+
+/// Single-quotes `arg` for a POSIX shell so pathspecs with spaces or metacharacters
+/// survive the trip through ssh.
+pub(crate) fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Turns the stderr of a failed remote command into an actionable message.
+pub(crate) fn describe_remote_error(stderr: &str, host: &str, path: &str) -> String {
+    if stderr.contains("Could not resolve hostname") {
+        format!(
+            "Cannot resolve host '{}'. Check the hostname and your network connection.",
+            host
+        )
+    } else if stderr.contains("Connection refused") || stderr.contains("Connection timed out") {
+        format!(
+            "Cannot connect to '{}'. Ensure the machine is reachable and SSH is running.",
+            host
+        )
+    } else if stderr.contains("Permission denied") {
+        format!(
+            "SSH authentication failed for '{}'. Check your SSH keys or credentials.",
+            host
+        )
+    } else if stderr.contains("No such file or directory") || stderr.contains("not a directory") {
+        format!("Remote path '{}' not found on '{}'.", path, host)
+    } else if stderr.contains("not a git repository") {
+        format!("'{}' on '{}' is not a git repository.", path, host)
+    } else {
+        format!("Remote command failed: {}", stderr.trim())
+    }
+}
+
+pub fn run_git_diff(options: &DiffOptions, repo_path: &str) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(options.local_args(repo_path)?)
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+
+    if output.status.success() {
+        let diff_text = String::from_utf8_lossy(&output.stdout).to_string();
+        let diff_text = with_forced_text(diff_text, options, repo_path)?;
+        with_untracked_files(diff_text, options, repo_path)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+/// Error for binary overrides forcing text on a diff not taken by local git.
+pub const FORCED_TEXT_LOCAL_ONLY: &str =
+    "Forcing files to text is only supported for local git repositories";
+
+/// Swaps the `Binary files ... differ` block of each file `binary_overrides` forces to
+/// text for its hunks, taken by running the same diff again with `--text` for just
+/// those paths.
+fn with_forced_text(
+    diff_text: String,
+    options: &DiffOptions,
+    repo_path: &str,
+) -> Result<String, String> {
+    if !options.forces_text() {
+        return Ok(diff_text);
+    }
+    let is_forced = |f: &DiffFile| {
+        f.is_binary && binary_override_for(&f.path, &options.binary_overrides) == Some(false)
+    };
+    let forced: Vec<DiffFile> = parse_unified_diff(&diff_text)
+        .into_iter()
+        .filter(is_forced)
+        .collect();
+    if forced.is_empty() {
+        return Ok(diff_text);
+    }
+
+    let mut text_options = options.clone();
+    text_options.pathspecs = forced
+        .iter()
+        .flat_map(|f| std::iter::once(&f.path).chain(f.old_path.as_ref()))
+        .map(|p| format!(":(literal){}", p))
+        .collect();
+    let mut args = text_options.local_args(repo_path)?;
+    let diff_at = args.iter().position(|a| a == "diff").unwrap_or(0);
+    args.insert(diff_at + 1, "--text".to_string());
+    let output = git_command()
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+
+    let text_diff = String::from_utf8_lossy(&output.stdout);
+    let chunk_path = |chunk: &str| parse_unified_diff(chunk).into_iter().next().map(|f| f.path);
+    let text_chunks: Vec<(String, &str)> = split_file_chunks(&text_diff)
+        .into_iter()
+        .filter_map(|chunk| Some((chunk_path(chunk)?, chunk)))
+        .collect();
+    let mut merged = String::with_capacity(diff_text.len());
+    for chunk in split_file_chunks(&diff_text) {
+        let path = chunk_path(chunk);
+        let text = text_chunks
+            .iter()
+            .find(|(p, _)| Some(p) == path.as_ref())
+            .map(|(_, text)| *text);
+        merged.push_str(text.unwrap_or(chunk));
+    }
+    Ok(merged)
+}
+
+/// Appends a new-file diff for each untracked (and not ignored) file under the
+/// pathspecs when the target covers them, as `git diff` alone never shows those.
+fn with_untracked_files(
+    mut diff_text: String,
+    options: &DiffOptions,
+    repo_path: &str,
+) -> Result<String, String> {
+    if !matches!(options.target, DiffTarget::MergeBaseWith(_)) {
+        return Ok(diff_text);
+    }
+
+    let output = git_command()
+        .args(["ls-files", "--others", "--exclude-standard", "-z", "--"])
+        .args(&options.pathspecs)
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git ls-files: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to list untracked files: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for path in stdout.split('\0').filter(|p| !p.is_empty()) {
+        let output = git_command()
+            .args(options.untracked_file_args(path))
+            .current_dir(repo_path)
+            .output()
+            .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+        // Exit code 1 means the files differ, as they always do against /dev/null
+        match output.status.code() {
+            Some(0) | Some(1) => diff_text.push_str(&String::from_utf8_lossy(&output.stdout)),
+            _ => {
+                return Err(format!(
+                    "Failed to diff untracked file '{}': {}",
+                    path,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+        }
+    }
+    Ok(diff_text)
+}
+
+/// Non-blocking variant of `run_git_diff` for use from async Tauri commands.
+pub async fn run_git_diff_async(options: &DiffOptions, repo_path: &str) -> Result<String, String> {
+    run_git_diff_cancellable(options, repo_path, &Notify::new()).await
+}
+
+/// Error returned by the cancellable runners when their `Notify` fires first.
+pub const DIFF_CANCELLED: &str = "Diff cancelled";
+
+/// Runs `cmd` to completion unless `cancel` is notified first, in which case the child
+/// is killed and `DIFF_CANCELLED` is returned.
+async fn output_or_cancel(cmd: Command, cancel: &Notify, what: &str) -> Result<Output, String> {
+    let child = tokio::process::Command::from(cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Dropping the wait future when cancelled drops the child, which kills it
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to execute {}: {}", what, e))?;
+
+    tokio::select! {
+        output = child.wait_with_output() => {
+            output.map_err(|e| format!("Failed to execute {}: {}", what, e))
+        }
+        _ = cancel.notified() => Err(DIFF_CANCELLED.to_string()),
+    }
+}
+
+/// Like `run_git_diff_async`, but the git process is killed as soon as `cancel` is
+/// notified.
+pub async fn run_git_diff_cancellable(
+    options: &DiffOptions,
+    repo_path: &str,
+    cancel: &Notify,
+) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+
+    // Resolving the target may run git (merge base, upstream), so not on the runtime
+    let (opts, path) = (options.clone(), repo_path.to_string());
+    let args = tauri::async_runtime::spawn_blocking(move || opts.local_args(&path))
+        .await
+        .unwrap_or_else(|e| Err(format!("Diff task failed: {}", e)))?;
+    let mut cmd = git_command();
+    cmd.args(args).current_dir(repo_path);
+    let output = output_or_cancel(cmd, cancel, "git diff").await?;
+
+    if output.status.success() {
+        let diff_text = String::from_utf8_lossy(&output.stdout).to_string();
+        if !options.forces_text() && !matches!(options.target, DiffTarget::MergeBaseWith(_)) {
+            return Ok(diff_text);
+        }
+        // More git processes (one per untracked file): keep them off the async runtime
+        let (options, repo_path) = (options.clone(), repo_path.to_string());
+        let follow_up = tauri::async_runtime::spawn_blocking(move || {
+            let diff_text = with_forced_text(diff_text, &options, &repo_path)?;
+            with_untracked_files(diff_text, &options, &repo_path)
+        });
+        tokio::select! {
+            result = follow_up => {
+                result.unwrap_or_else(|e| Err(format!("Diff task failed: {}", e)))
+            }
+            _ = cancel.notified() => Err(DIFF_CANCELLED.to_string()),
+        }
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+/// Non-blocking variant of `run_remote_git_diff` for use from async Tauri commands.
+pub async fn run_remote_git_diff_async(
+    remote: &str,
+    options: &DiffOptions,
+    ssh: &SshOptions,
+) -> Result<String, String> {
+    run_remote_git_diff_cancellable(remote, options, ssh, &Notify::new()).await
+}
+
+/// Like `run_remote_git_diff_async`, but the ssh process is killed as soon as `cancel`
+/// is notified.
+pub async fn run_remote_git_diff_cancellable(
+    remote: &str,
+    options: &DiffOptions,
+    ssh: &SshOptions,
+    cancel: &Notify,
+) -> Result<String, String> {
+    let (host, path) = parse_remote_path(remote)?;
+    check_remote_target(options)?;
+
+    let remote_cmd = remote_diff_command(path, options);
+    let output = ssh_output_with_retry_cancellable(host, &remote_cmd, ssh, cancel).await?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(describe_remote_error(&stderr, host, path))
+    }
+}
+
+/// Pipes `diff_text` to `git apply` (with `--check` when `check_only`, which leaves the
+/// working tree untouched). On failure the error lists each hunk git could not apply
+/// followed by git's full output.
+pub fn apply_patch(diff_text: &str, repo_path: &str, check_only: bool) -> Result<(), String> {
+    check_repo_path(repo_path)?;
+
+    let mut cmd = git_command();
+    cmd.arg("apply");
+    if check_only {
+        cmd.arg("--check");
+    }
+    let mut child = cmd
+        .arg("-")
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute git apply: {}", e))?;
+
+    // git apply reads the whole patch before writing anything, so this cannot deadlock
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(diff_text.as_bytes())
+            .map_err(|e| format!("Failed to write patch to git apply: {}", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to execute git apply: {}", e))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let failed: Vec<&str> = stderr
+        .lines()
+        .filter_map(|l| l.strip_prefix("error: patch failed: "))
+        .collect();
+    if failed.is_empty() {
+        Err(format!("Patch does not apply: {}", stderr))
+    } else {
+        Err(format!(
+            "Patch does not apply. Failed hunks (file:line): {}\n\n{}",
+            failed.join(", "),
+            stderr
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_additions_only() {
+        let diff = "\
+diff --git a/hello.txt b/hello.txt
+new file mode 100644
+index 0000000..e69de29
+--- /dev/null
++++ b/hello.txt
+@@ -0,0 +1,3 @@
++line one
++line two
++line three
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "hello.txt");
+        assert_eq!(files[0].status, FileStatus::Added);
+        assert_eq!(files[0].hunks.len(), 1);
+        assert_eq!(files[0].hunks[0].lines.len(), 3);
+        for line in &files[0].hunks[0].lines {
+            assert_eq!(line.line_type, LineType::Addition);
+            assert!(line.old_line_no.is_none());
+            assert!(line.new_line_no.is_some());
+        }
+    }
+
+    #[test]
+    fn test_parse_deletions_only() {
+        let diff = "\
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+index abc1234..0000000
+--- a/old.txt
++++ /dev/null
+@@ -1,3 +0,0 @@
+-first line
+-second line
+-third line
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].status, FileStatus::Deleted);
+        assert_eq!(files[0].hunks[0].lines.len(), 3);
+        for line in &files[0].hunks[0].lines {
+            assert_eq!(line.line_type, LineType::Deletion);
+            assert!(line.old_line_no.is_some());
+            assert!(line.new_line_no.is_none());
+        }
+    }
+
+    #[test]
+    fn test_parse_mixed_changes() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+index abc1234..def5678 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1,5 +1,5 @@
+ line one
+-line two old
++line two new
+ line three
+-line four old
++line four new
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].status, FileStatus::Modified);
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(lines.len(), 6);
+        assert!(lines.iter().all(|l| !l.no_newline));
+        assert_eq!(lines[0].line_type, LineType::Context);
+        assert_eq!(lines[1].line_type, LineType::Deletion);
+        assert_eq!(lines[2].line_type, LineType::Addition);
+        assert_eq!(lines[3].line_type, LineType::Context);
+        assert_eq!(lines[4].line_type, LineType::Deletion);
+        assert_eq!(lines[5].line_type, LineType::Addition);
+        // Every line is a run of its own
+        assert!(lines.iter().all(|l| l.run_start && l.run_end));
+    }
+
+    #[test]
+    fn test_mark_runs() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -1,3 +1,3 @@
+ ctx
+-a
+-b
++c
++d
+";
+        let lines = &parse_unified_diff(diff)[0].hunks[0].lines;
+        let runs: Vec<(bool, bool)> = lines.iter().map(|l| (l.run_start, l.run_end)).collect();
+        assert_eq!(
+            runs,
+            vec![
+                (true, true),
+                (true, false),
+                (false, true),
+                (true, false),
+                (false, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_files() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+index abc..def 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ unchanged
+-old a
++new a
+diff --git a/b.txt b/b.txt
+index 111..222 100644
+--- a/b.txt
++++ b/b.txt
+@@ -1,2 +1,2 @@
+ unchanged
+-old b
++new b
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "a.txt");
+        assert_eq!(files[1].path, "b.txt");
+    }
+
+    #[test]
+    fn test_merge_duplicate_files() {
+        let diff = "\
+diff --git a/dup.txt b/dup.txt
+index abc..def 100644
+--- a/dup.txt
++++ b/dup.txt
+@@ -1 +1 @@
+-one
++ONE
+diff --git a/other.txt b/other.txt
+--- a/other.txt
++++ b/other.txt
+@@ -1 +1 @@
+-x
++y
+diff --git a/dup.txt b/dup.txt
+--- a/dup.txt
++++ b/dup.txt
+@@ -10 +10 @@
+-ten
++TEN
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 3);
+
+        let merged = merge_duplicate_files(files);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].path, "dup.txt");
+        assert_eq!(merged[0].old_blob.as_deref(), Some("abc"));
+        let starts: Vec<u32> = merged[0].hunks.iter().map(|h| h.old_start).collect();
+        assert_eq!(starts, vec![1, 10]);
+        assert_eq!(merged[1].path, "other.txt");
+    }
+
+    #[test]
+    fn test_set_abs_paths() {
+        let mut files = vec![
+            DiffFile {
+                path: "src/main.rs".to_string(),
+                ..Default::default()
+            },
+            DiffFile {
+                path: "README.md".to_string(),
+                ..Default::default()
+            },
+        ];
+        set_abs_paths(&mut files, "/home/me/repo");
+        assert_eq!(
+            files[0].abs_path.as_deref(),
+            Some("/home/me/repo/src/main.rs")
+        );
+        assert_eq!(
+            files[1].abs_path.as_deref(),
+            Some("/home/me/repo/README.md")
+        );
+        let parsed = parse_unified_diff("diff --git a/a.txt b/a.txt\n");
+        assert_eq!(parsed[0].abs_path, None);
+    }
+
+    #[test]
+    fn test_filter_by_status() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+index abc..def 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ unchanged
+-old a
++new a
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index 111..000
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+diff --git a/old.txt b/new.txt
+similarity index 100%
+rename from old.txt
+rename to new.txt
+";
+        let files = parse_unified_diff(diff);
+
+        let deleted = filter_by_status(files.clone(), &[FileStatus::Deleted]);
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].path, "gone.txt");
+
+        let some = filter_by_status(files.clone(), &[FileStatus::Renamed, FileStatus::Modified]);
+        let paths: Vec<&str> = some.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "new.txt"]);
+
+        assert_eq!(filter_by_status(files.clone(), &[]), files);
+    }
+
+    #[test]
+    fn test_parse_renamed_file() {
+        let diff = "\
+diff --git a/old_name.txt b/new_name.txt
+similarity index 100%
+rename from old_name.txt
+rename to new_name.txt
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 1);
         assert_eq!(files[0].path, "new_name.txt");
         assert_eq!(files[0].old_path, Some("old_name.txt".to_string()));
         assert_eq!(files[0].status, FileStatus::Renamed);
+        assert_eq!(files[0].similarity, Some(100));
+    }
+
+    #[test]
+    fn test_parse_dissimilarity_index() {
+        let diff = "\
+diff --git a/rewritten.rs b/rewritten.rs
+dissimilarity index 92%
+index abc..def 100644
+--- a/rewritten.rs
++++ b/rewritten.rs
+@@ -1,2 +1,2 @@
+-fn old() {}
+-fn older() {}
++struct New;
++impl New {}
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert_eq!(files[0].dissimilarity, Some(92));
+        assert_eq!(files[0].similarity, None);
+        assert_eq!(parse_unified_diff(&reconstruct_unified_diff(&files)), files);
+    }
+
+    #[test]
+    fn test_parse_copied_file() {
+        let diff = "\
+diff --git a/base.rs b/copy.rs
+similarity index 87%
+copy from base.rs
+copy to copy.rs
+index abc..def 100644
+--- a/base.rs
++++ b/copy.rs
+@@ -1 +1 @@
+-fn base() {}
++fn copy() {}
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files[0].status, FileStatus::Copied);
+        assert_eq!(files[0].path, "copy.rs");
+        assert_eq!(files[0].old_path.as_deref(), Some("base.rs"));
+        assert_eq!(files[0].similarity, Some(87));
+    }
+
+    #[test]
+    fn test_parse_new_file() {
+        let diff = "\
+diff --git a/new.rs b/new.rs
+new file mode 100644
+index 0000000..abc1234
+--- /dev/null
++++ b/new.rs
+@@ -0,0 +1,2 @@
++fn main() {}
++// done
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].status, FileStatus::Added);
+        assert_eq!(files[0].hunks[0].lines.len(), 2);
+        assert_eq!(files[0].hunks[0].lines[0].content, "fn main() {}");
+    }
+
+    #[test]
+    fn test_parse_deleted_file() {
+        let diff = "\
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index abc1234..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1,1 +0,0 @@
+-goodbye
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].status, FileStatus::Deleted);
+        assert_eq!(files[0].hunks[0].lines.len(), 1);
+        assert_eq!(files[0].hunks[0].lines[0].content, "goodbye");
+        assert_eq!(files[0].hunks[0].lines[0].line_type, LineType::Deletion);
+    }
+
+    #[test]
+    fn test_parse_binary_files_skipped() {
+        let diff = "\
+diff --git a/image.png b/image.png
+new file mode 100644
+Binary files /dev/null and b/image.png differ
+diff --git a/text.txt b/text.txt
+index abc..def 100644
+--- a/text.txt
++++ b/text.txt
+@@ -1,1 +1,1 @@
+-old
++new
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 2);
+        // Binary file should have no hunks
+        assert_eq!(files[0].path, "image.png");
+        assert!(files[0].hunks.is_empty());
+        assert!(files[0].is_binary);
+        // Text file should parse normally
+        assert_eq!(files[1].path, "text.txt");
+        assert_eq!(files[1].hunks.len(), 1);
+        assert!(!files[1].is_binary);
+    }
+
+    #[test]
+    fn test_parse_warns_on_unrecognized_lines() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ kept
+*garbage
+-old
++new
+";
+        let (files, warnings) = parse_unified_diff_verbose(diff);
+        assert_eq!(
+            warnings,
+            vec![ParseWarning {
+                line_no: 6,
+                content: "*garbage".to_string(),
+                message: "Unrecognized line in hunk".to_string(),
+            }]
+        );
+        assert_eq!(files, parse_unified_diff(diff));
+        assert_eq!(files[0].hunks[0].lines.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_format_patch_mailbox() {
+        // `git format-patch --stdout HEAD~2`
+        let mbox = "\
+From f7578928043dbdcbeeb99594e71cc3180a4332bb Mon Sep 17 00:00:00 2001
+From: Ada <ada@example.com>
+Date: Mon, 1 Jan 2024 00:00:00 +0000
+Subject: [PATCH 1/2] Shorten notes
+
+Drops the list item.
+---
+ notes.md | 3 +--
+ 1 file changed, 1 insertion(+), 2 deletions(-)
+
+diff --git a/notes.md b/notes.md
+index 297f6fb..99b356d 100644
+--- a/notes.md
++++ b/notes.md
+@@ -1,3 +1,2 @@
+ one
+-two
+-- item
++2
+-- 
+2.39.5
+
+
+From d2f86a4fa43c1105c283fa717ec76f0e7e1438cb Mon Sep 17 00:00:00 2001
+From: Ada <ada@example.com>
+Date: Mon, 1 Jan 2024 00:00:00 +0000
+Subject: [PATCH 2/2] Add b
+
+---
+ b.txt | 1 +
+ 1 file changed, 1 insertion(+)
+ create mode 100644 b.txt
+
+diff --git a/b.txt b/b.txt
+new file mode 100644
+index 0000000..45b983b
+--- /dev/null
++++ b/b.txt
+@@ -0,0 +1 @@
++hi
+-- 
+2.39.5
+
+";
+        let (files, warnings) = parse_unified_diff_verbose(mbox);
+        assert_eq!(warnings, vec![]);
+        assert_eq!(files.len(), 2);
+
+        let lines: Vec<(&LineType, &str)> = files[0].hunks[0]
+            .lines
+            .iter()
+            .map(|l| (&l.line_type, l.content.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (&LineType::Context, "one"),
+                (&LineType::Deletion, "two"),
+                (&LineType::Deletion, "- item"),
+                (&LineType::Addition, "2"),
+            ]
+        );
+        assert_eq!(files[1].status, FileStatus::Added);
+        assert_eq!(files[1].hunks[0].lines.len(), 1);
+
+        // Without a signature the next message's `From` line ends the hunk
+        let unsigned = mbox.replacen("-- \n2.39.5\n", "", 1);
+        let files = parse_unified_diff(&unsigned);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].hunks[0].lines.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_colored_diff() {
+        let diff = "\
+\x1b[1mdiff --git a/a.txt b/a.txt\x1b[m
+\x1b[1mindex abc1234..def5678 100644\x1b[m
+\x1b[1m--- a/a.txt\x1b[m
+\x1b[1m+++ b/a.txt\x1b[m
+\x1b[36m@@ -1,2 +1,2 @@\x1b[m
+ kept
+\x1b[31m-old\x1b[m
+\x1b[32m+\x1b[m\x1b[32mnew\x1b[m\x1b[K
+";
+        assert!(parse_unified_diff(diff).is_empty());
+
+        let files = parse_unified_diff(&strip_ansi(diff));
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(files[0].path, "a.txt");
+        assert_eq!(files[0].old_blob.as_deref(), Some("abc1234"));
+        assert_eq!(lines[1].line_type, LineType::Deletion);
+        assert_eq!(lines[1].content, "old");
+        assert_eq!(lines[2].line_type, LineType::Addition);
+        assert_eq!(lines[2].content, "new");
+        assert!(matches!(strip_ansi("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn test_classify_status() {
+        assert_eq!(
+            classify_status(&["new file mode 100644", "index 0000000..abc1234"]),
+            FileStatus::Added
+        );
+        assert_eq!(
+            classify_status(&["deleted file mode 100755", "index abc1234..0000000"]),
+            FileStatus::Deleted
+        );
+        assert_eq!(
+            classify_status(&[
+                "similarity index 90%",
+                "rename from old.rs",
+                "rename to new.rs"
+            ]),
+            FileStatus::Renamed
+        );
+        assert_eq!(
+            classify_status(&["similarity index 100%", "copy from a.rs", "copy to b.rs"]),
+            FileStatus::Copied
+        );
+        assert_eq!(
+            classify_status(&["old mode 100644", "new mode 100755"]),
+            FileStatus::Modified
+        );
+        assert_eq!(
+            classify_status(&["index abc1234..def5678 100644"]),
+            FileStatus::Modified
+        );
+        assert_eq!(classify_status(&[]), FileStatus::Modified);
+    }
+
+    #[test]
+    fn test_normalize_path_separators() {
+        let plain = "\
+--- src\\old\\main.c
++++ src\\new\\main.c
+@@ -1 +1 @@
+-a
++b
+";
+        let git = "\
+diff --git a/docs/a\\b.md b/docs/a\\b.md
+--- a/docs/a\\b.md
++++ b/docs/a\\b.md
+@@ -1 +1 @@
+-a
++b
+";
+        let mut files = parse_unified_diff(plain);
+        assert_eq!(files[0].path, "src\\new\\main.c");
+        files.extend(parse_unified_diff(git));
+
+        let files = normalize_path_separators(files);
+        assert_eq!(files[0].path, "src/new/main.c");
+        assert_eq!(files[1].path, "docs/a/b.md");
+        let renamed = DiffFile {
+            old_path: Some("a\\x.txt".to_string()),
+            ..files[0].clone()
+        };
+        let renamed = normalize_path_separators(vec![renamed]);
+        assert_eq!(renamed[0].old_path.as_deref(), Some("a/x.txt"));
+    }
+
+    #[test]
+    fn test_apply_binary_overrides() {
+        let diff = "\
+diff --git a/icon.svg b/icon.svg
+--- a/icon.svg
++++ b/icon.svg
+@@ -1 +1 @@
+-<svg/>
++<svg></svg>
+diff --git a/data.DAT b/data.DAT
+index abc1234..def5678 100644
+Binary files a/data.DAT and b/data.DAT differ
+diff --git a/main.rs b/main.rs
+--- a/main.rs
++++ b/main.rs
+@@ -1 +1 @@
+-a
++b
+";
+        let overrides = vec![(".svg".to_string(), true), ("dat".to_string(), false)];
+        let files = apply_binary_overrides(parse_unified_diff(diff), &overrides);
+
+        // Forced binary
+        assert!(files[0].is_binary);
+        assert!(files[0].hunks.is_empty());
+        // Forced text
+        assert!(!files[1].is_binary);
+        // No override
+        assert!(!files[2].is_binary);
+        assert_eq!(files[2].hunks.len(), 1);
+
+        let overrides = vec![("rs".to_string(), false), ("rs".to_string(), true)];
+        let files = apply_binary_overrides(parse_unified_diff(diff), &overrides);
+        assert_eq!(files[2].hunks.len(), 1);
+    }
+
+    #[test]
+    fn test_run_git_diff_forced_text() {
+        let repo = crate::test_support::TempRepo::new("forced-text");
+        repo.write("data.dat", "one\0\ntwo\n");
+        repo.write("other.bin", "x\0\n");
+        repo.commit_all("initial");
+        repo.write("data.dat", "one\0\nthree\n");
+        repo.write("other.bin", "y\0\n");
+
+        let plain = DiffOptions::new();
+        let files = parse_unified_diff(&run_git_diff(&plain, repo.path_str()).unwrap());
+        assert!(files.iter().all(|f| f.is_binary && f.hunks.is_empty()));
+
+        let options = DiffOptions::new().binary_override(".DAT", false);
+        let text = run_git_diff(&options, repo.path_str()).unwrap();
+        let files = apply_binary_overrides(parse_unified_diff(&text), &options.binary_overrides);
+        let summary: Vec<(&str, bool, usize)> = files
+            .iter()
+            .map(|f| (f.path.as_str(), f.is_binary, f.hunks.len()))
+            .collect();
+        // Only the forced file was diffed as text, in its place in the diff
+        assert_eq!(
+            summary,
+            vec![("data.dat", false, 1), ("other.bin", true, 0)]
+        );
+        let added: Vec<&str> = files[0].hunks[0]
+            .lines
+            .iter()
+            .filter(|l| l.line_type == LineType::Addition)
+            .map(|l| l.content.as_str())
+            .collect();
+        assert_eq!(added, vec!["three"]);
+
+        let async_text =
+            tauri::async_runtime::block_on(run_git_diff_async(&options, repo.path_str()));
+        assert_eq!(async_text.unwrap(), text);
+        let err = run_remote_git_diff("host:/repo", &options, &SshOptions::default()).unwrap_err();
+        assert_eq!(err, FORCED_TEXT_LOCAL_ONLY);
+    }
+
+    #[test]
+    fn test_diff_no_index() {
+        let repo = crate::test_support::TempRepo::new("no-index");
+        repo.write("left.txt", "one\ntwo\n");
+        repo.write("right.txt", "one\nthree\n");
+        let left = repo.join("left.txt").display().to_string();
+        let right = repo.join("right.txt").display().to_string();
+
+        let patch = diff_no_index(&left, &right).unwrap();
+        let files = parse_unified_diff(&patch);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].hunks[0].lines[1].content, "two");
+        assert_eq!(files[0].hunks[0].lines[2].content, "three");
+
+        assert_eq!(diff_no_index(&left, &left).unwrap(), "");
+        let err = diff_no_index(&left, "/nonexistent/great-review/file").unwrap_err();
+        assert!(err.contains("Failed to diff"), "got: {err}");
+    }
+
+    #[test]
+    fn test_parse_oversized_hunk_range() {
+        let diff = "\
+diff --git a/big.txt b/big.txt
+--- a/big.txt
++++ b/big.txt
+@@ -1,2 +4294967295,5 @@
+ a
++b
+@@ -10,1 +10,99999999999 @@
+ c
+@@ -20,1 +20,2 @@
+ d
++e
+";
+        let (files, warnings) = parse_unified_diff_verbose(diff);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].line_no, 4);
+        assert_eq!(warnings[0].content, "@@ -1,2 +4294967295,5 @@");
+        assert_eq!(warnings[1].line_no, 7);
+
+        // The valid hunk keeps correct line numbers
+        assert_eq!(files[0].hunks.len(), 1);
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(lines[1].new_line_no, Some(21));
+
+        assert_eq!(parse_range("4294967295"), None);
+        assert_eq!(parse_range("7,x"), None);
+        assert_eq!(parse_range("0,0"), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_git_header_path_prefixes() {
+        assert_eq!(
+            git_header_path("diff --git a/src/main.rs b/src/main.rs"),
+            Some("src/main.rs")
+        );
+        assert_eq!(
+            git_header_path("diff --git i/a/b.rs w/a/b.rs"),
+            Some("a/b.rs")
+        );
+        assert_eq!(
+            git_header_path("diff --git main.rs main.rs"),
+            Some("main.rs")
+        );
+        assert_eq!(
+            git_header_path("diff --git old/my file new/my file"),
+            Some("my file")
+        );
+        assert_eq!(git_header_path("diff --git a/b/c b/b/c"), Some("b/c"));
+    }
+
+    #[test]
+    fn test_parse_custom_prefixes() {
+        let diff = "\
+diff --git i/src/lib.rs w/src/lib.rs
+index abc..def 100644
+--- i/src/lib.rs
++++ w/src/lib.rs
+@@ -1 +1 @@
+-old
++new
+diff --git c/notes b.txt w/notes b.txt
+new file mode 100644
+--- /dev/null
++++ w/notes b.txt
+@@ -0,0 +1 @@
++hello
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(files[1].path, "notes b.txt");
+        assert_eq!(files[1].status, FileStatus::Added);
+    }
+
+    #[test]
+    fn test_parse_custom_prefixes_from_real_git() {
+        let repo = crate::test_support::TempRepo::new("custom-prefixes");
+        repo.write("dir/file.txt", "one\n");
+        repo.commit_all("initial");
+        repo.write("dir/file.txt", "two\n");
+        // Same output as diff.srcPrefix/diff.dstPrefix, which need git 2.45
+        let diff = repo.git(&["diff", "--src-prefix=i/", "--dst-prefix=w/"]);
+        assert!(diff.starts_with("diff --git i/dir/file.txt w/dir/file.txt"));
+        assert_eq!(parse_unified_diff(&diff)[0].path, "dir/file.txt");
+
+        repo.git(&["config", "diff.noprefix", "true"]);
+        let diff = run_git_diff(&DiffOptions::default(), repo.path_str()).unwrap();
+        assert_eq!(parse_unified_diff(&diff)[0].path, "dir/file.txt");
+    }
+
+    #[test]
+    fn test_parse_empty_diff() {
+        let files = parse_unified_diff("");
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        let diff = "\
+diff --git a/nums.txt b/nums.txt
+index abc..def 100644
+--- a/nums.txt
++++ b/nums.txt
+@@ -10,4 +10,4 @@
+ context at 10
+-deleted at 11
++added at 11
+ context at 12
+";
+        let files = parse_unified_diff(diff);
+        let lines = &files[0].hunks[0].lines;
+
+        // Context line at old=10, new=10
+        assert_eq!(lines[0].old_line_no, Some(10));
+        assert_eq!(lines[0].new_line_no, Some(10));
+
+        // Deletion at old=11
+        assert_eq!(lines[1].old_line_no, Some(11));
+        assert!(lines[1].new_line_no.is_none());
+
+        // Addition at new=11
+        assert!(lines[2].old_line_no.is_none());
+        assert_eq!(lines[2].new_line_no, Some(11));
+
+        // Context at old=12, new=12
+        assert_eq!(lines[3].old_line_no, Some(12));
+        assert_eq!(lines[3].new_line_no, Some(12));
+    }
+
+    #[test]
+    fn test_locate_line() {
+        let diff = "\
+diff --git a/nums.txt b/nums.txt
+index abc..def 100644
+--- a/nums.txt
++++ b/nums.txt
+@@ -10,4 +10,4 @@
+ context at 10
+-deleted at 11
++added at 11
+ context at 12
+@@ -40,2 +40,3 @@
+ context at 40
++added at 41
+ context at 42
+";
+        let file = &parse_unified_diff(diff)[0];
+        assert_eq!(locate_line(file, 10), Some((0, 0)));
+        // The addition, not the deletion it replaced
+        assert_eq!(locate_line(file, 11), Some((0, 2)));
+        assert_eq!(locate_line(file, 41), Some((1, 1)));
+        assert_eq!(locate_line(file, 13), None);
+        assert_eq!(locate_line(file, 0), None);
+    }
+
+    #[test]
+    fn test_hunk_new_and_old_lines() {
+        let files = parse_unified_diff(
+            "\
+diff --git a/f.txt b/f.txt
+--- a/f.txt
++++ b/f.txt
+@@ -1,4 +1,4 @@
+ keep
+-old one
+-old two
++new one
++new two
+ tail
+",
+        );
+        let hunk = &files[0].hunks[0];
+        let contents = |lines: Vec<&DiffLine>| -> Vec<String> {
+            lines.into_iter().map(|l| l.content.clone()).collect()
+        };
+        assert_eq!(
+            contents(hunk_new_lines(hunk)),
+            vec!["keep", "new one", "new two", "tail"]
+        );
+        assert_eq!(
+            contents(hunk_old_lines(hunk)),
+            vec!["keep", "old one", "old two", "tail"]
+        );
+        assert!(hunk_new_lines(hunk)
+            .iter()
+            .all(|l| l.line_type != LineType::Deletion));
+        assert!(hunk_old_lines(hunk)
+            .iter()
+            .all(|l| l.line_type != LineType::Addition));
+    }
+
+    #[test]
+    fn test_leading_whitespace() {
+        let diff = "\
+diff --git a/main.go b/main.go
+--- a/main.go
++++ b/main.go
+@@ -1,3 +1,3 @@
+ func main() {
+-\t  old()
++\tnew()
+ }
+";
+        let lines = &parse_unified_diff(diff)[0].hunks[0].lines;
+        assert_eq!(lines[0].leading_whitespace, "");
+        assert_eq!(lines[1].leading_whitespace, "\t  ");
+        assert_eq!(lines[1].content, "\t  old()");
+        assert_eq!(lines[2].leading_whitespace, "\t");
+    }
+
+    #[test]
+    fn test_no_newline_at_end() {
+        let diff = "\
+diff --git a/no_nl.txt b/no_nl.txt
+index abc..def 100644
+--- a/no_nl.txt
++++ b/no_nl.txt
+@@ -1,1 +1,1 @@
+-old content
+\\ No newline at end of file
++new content
+\\ No newline at end of file
+";
+        let files = parse_unified_diff(diff);
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].line_type, LineType::Deletion);
+        assert_eq!(lines[0].content, "old content");
+        assert!(lines[0].no_newline);
+        assert_eq!(lines[1].line_type, LineType::Addition);
+        assert_eq!(lines[1].content, "new content");
+        assert!(lines[1].no_newline);
+    }
+
+    #[test]
+    fn test_single_line_hunk_ranges() {
+        let diff = "\
+diff --git a/one.txt b/one.txt
+index abc..def 100644
+--- a/one.txt
++++ b/one.txt
+@@ -1 +1 @@
+-old
++new
+";
+        let files = parse_unified_diff(diff);
+        let hunk = &files[0].hunks[0];
+        assert_eq!(
+            (
+                hunk.old_start,
+                hunk.old_count,
+                hunk.new_start,
+                hunk.new_count
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(hunk.lines.len(), 2);
+        assert_eq!(hunk.lines[0].old_line_no, Some(1));
+        assert_eq!(hunk.lines[0].new_line_no, None);
+        assert_eq!(hunk.lines[1].old_line_no, None);
+        assert_eq!(hunk.lines[1].new_line_no, Some(1));
+    }
+
+    #[test]
+    fn test_single_line_hunk_range_with_function_context() {
+        let diff = "\
+diff --git a/lib.rs b/lib.rs
+index abc..def 100644
+--- a/lib.rs
++++ b/lib.rs
+@@ -7 +7 @@ fn main() {
+-    old();
++    new();
+";
+        let files = parse_unified_diff(diff);
+        let hunk = &files[0].hunks[0];
+        assert_eq!(hunk.header, "@@ -7 +7 @@ fn main() {");
+        assert_eq!(
+            (
+                hunk.old_start,
+                hunk.old_count,
+                hunk.new_start,
+                hunk.new_count
+            ),
+            (7, 1, 7, 1)
+        );
+        assert_eq!(hunk.lines[0].old_line_no, Some(7));
+        assert_eq!(hunk.lines[1].new_line_no, Some(7));
+    }
+
+    #[test]
+    fn test_single_line_added_file_range() {
+        let diff = "\
+diff --git a/new.txt b/new.txt
+new file mode 100644
+index 0000000..abc1234
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++only line
+";
+        let files = parse_unified_diff(diff);
+        let hunk = &files[0].hunks[0];
+        assert_eq!(
+            (
+                hunk.old_start,
+                hunk.old_count,
+                hunk.new_start,
+                hunk.new_count
+            ),
+            (0, 0, 1, 1)
+        );
+        assert_eq!(hunk.lines.len(), 1);
+        assert_eq!(hunk.lines[0].old_line_no, None);
+        assert_eq!(hunk.lines[0].new_line_no, Some(1));
+    }
+
+    #[test]
+    fn test_single_line_deleted_file_range() {
+        let diff = "\
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index abc1234..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-only line
+";
+        let files = parse_unified_diff(diff);
+        let hunk = &files[0].hunks[0];
+        assert_eq!(
+            (
+                hunk.old_start,
+                hunk.old_count,
+                hunk.new_start,
+                hunk.new_count
+            ),
+            (1, 1, 0, 0)
+        );
+        assert_eq!(hunk.lines.len(), 1);
+        assert_eq!(hunk.lines[0].old_line_no, Some(1));
+        assert_eq!(hunk.lines[0].new_line_no, None);
+    }
+
+    #[test]
+    fn test_parse_blob_hashes() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+index abc1234..def5678 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-old
++new
+diff --git a/new.txt b/new.txt
+new file mode 100644
+index 0000000..1234567
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files[0].old_blob.as_deref(), Some("abc1234"));
+        assert_eq!(files[0].new_blob.as_deref(), Some("def5678"));
+        assert_eq!(files[1].old_blob, None);
+        assert_eq!(files[1].new_blob.as_deref(), Some("1234567"));
+    }
+
+    #[test]
+    fn test_parse_file_to_symlink() {
+        let diff = "\
+diff --git a/config b/config
+old mode 100644
+new mode 120000
+index abc1234..def5678
+--- a/config
++++ b/config
+@@ -1 +1 @@
+-key = value
++../shared/config
+\\ No newline at end of file
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert_eq!(files[0].old_mode.as_deref(), Some("100644"));
+        assert_eq!(files[0].new_mode.as_deref(), Some("120000"));
+        assert!(files[0].is_symlink);
+        assert_eq!(files[0].old_link_target, None);
+        assert_eq!(
+            files[0].new_link_target.as_deref(),
+            Some("../shared/config")
+        );
+    }
+
+    #[test]
+    fn test_parse_mode_change_with_hunks() {
+        let diff = "\
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+index abc1234..def5678
+--- a/run.sh
++++ b/run.sh
+@@ -1,2 +1,2 @@
+-#!/bin/sh
++#!/usr/bin/env bash
+ set -e
+@@ -10,1 +10,2 @@ main() {
+ run
++cleanup
+diff --git a/tool.sh b/tool.sh
+old mode 100755
+new mode 100644
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 2);
+
+        let script = &files[0];
+        assert_eq!(script.status, FileStatus::Modified);
+        assert_eq!(script.old_mode.as_deref(), Some("100644"));
+        assert_eq!(script.new_mode.as_deref(), Some("100755"));
+        assert_eq!(script.old_blob.as_deref(), Some("abc1234"));
+        assert_eq!(script.hunks.len(), 2);
+        assert_eq!(script.hunks[0].lines[1].content, "#!/usr/bin/env bash");
+        assert_eq!(script.hunks[1].new_start, 10);
+        assert_eq!(script.hunks[1].lines[1].new_line_no, Some(11));
+
+        // A mode-only change right after keeps its own modes and no hunks
+        assert_eq!(files[1].old_mode.as_deref(), Some("100755"));
+        assert_eq!(files[1].new_mode.as_deref(), Some("100644"));
+        assert!(files[1].hunks.is_empty());
+    }
+
+    #[test]
+    fn test_parse_submodule_bump() {
+        let diff = "\
+diff --git a/vendor/lib b/vendor/lib
+index 1111111..2222222 160000
+--- a/vendor/lib
++++ b/vendor/lib
+@@ -1 +1 @@
+-Subproject commit 1111111111111111111111111111111111111111
++Subproject commit 2222222222222222222222222222222222222222-dirty
+diff --git a/vendor/new b/vendor/new
+new file mode 160000
+index 0000000..3333333
+--- /dev/null
++++ b/vendor/new
+@@ -0,0 +1 @@
++Subproject commit 3333333333333333333333333333333333333333
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert_eq!(
+            files[0].old_submodule_sha.as_deref(),
+            Some("1111111111111111111111111111111111111111")
+        );
+        assert_eq!(
+            files[0].new_submodule_sha.as_deref(),
+            Some("2222222222222222222222222222222222222222")
+        );
+        assert_eq!(files[1].status, FileStatus::Added);
+        assert_eq!(files[1].old_submodule_sha, None);
+        assert_eq!(
+            files[1].new_submodule_sha.as_deref(),
+            Some("3333333333333333333333333333333333333333")
+        );
+        assert!(!files[1].is_symlink);
+    }
+
+    #[test]
+    fn test_parse_new_symlink_and_regular_modes() {
+        let diff = "\
+diff --git a/link b/link
+new file mode 120000
+index 0000000..abc1234
+--- /dev/null
++++ b/link
+@@ -0,0 +1 @@
++target.txt
+\\ No newline at end of file
+diff --git a/script.sh b/script.sh
+index abc1234..def5678 100755
+--- a/script.sh
++++ b/script.sh
+@@ -1 +1 @@
+-echo old
++echo new
+";
+        let files = parse_unified_diff(diff);
+        assert!(files[0].is_symlink);
+        assert_eq!(files[0].new_link_target.as_deref(), Some("target.txt"));
+        assert!(!files[1].is_symlink);
+        assert_eq!(files[1].old_mode.as_deref(), Some("100755"));
+        assert_eq!(files[1].new_mode.as_deref(), Some("100755"));
+        assert_eq!(files[1].new_link_target, None);
+    }
+
+    #[test]
+    fn test_whitespace_only_hunks() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index abc..def 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,4 +1,5 @@
+ fn main() {
+-  let a = 1;
+-  let b = 2;
++    let a = 1;
++
++    let b  =  2;
+ }
+@@ -10,3 +11,3 @@
+ fn other() {
+-    let c = 3;
++    let c = 4;
+ }
+";
+        let files = parse_unified_diff(diff);
+        assert!(files[0].hunks[0].is_whitespace_only);
+        assert!(!files[0].hunks[1].is_whitespace_only);
+    }
+
+    #[test]
+    fn test_additions_and_deletions() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+index abc1234..def5678 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1,3 +1,4 @@
+ line one
+-line two old
++line two new
++line two extra
+ line three
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files[0].additions(), 2);
+        assert_eq!(files[0].deletions(), 1);
+    }
+
+    #[test]
+    fn test_format_hunk() {
+        let diff = "\
+diff --git a/no_nl.txt b/no_nl.txt
+index abc..def 100644
+--- a/no_nl.txt
++++ b/no_nl.txt
+@@ -1,2 +1,2 @@ section
+ kept
+-old content
+\\ No newline at end of file
++new content
+\\ No newline at end of file
+";
+        let files = parse_unified_diff(diff);
+        let text = format_hunk(&files[0].hunks[0]);
+        assert_eq!(
+            text,
+            "@@ -1,2 +1,2 @@ section\n kept\n-old content\n\\ No newline at end of file\n+new content\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_format_hunk_round_trip() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index abc..def 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@ fn one()
+ fn one() {}
+-fn two() {}
++fn two() { todo!() }
+ fn three() {}
+@@ -10,2 +10,3 @@
+ fn ten() {}
++fn ten_and_a_half() {}
+ fn eleven() {}
+";
+        let files = parse_unified_diff(diff);
+        for hunk in &files[0].hunks {
+            let copied = format_hunk(hunk);
+            let reparsed = parse_unified_diff(&format!(
+                "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n{}",
+                copied
+            ));
+            assert_eq!(&reparsed[0].hunks[0], hunk);
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_unified_diff_round_trip() {
+        let fixtures = [
+            // added file
+            "\
+diff --git a/hello.txt b/hello.txt
+new file mode 100644
+index 0000000..e69de29
+--- /dev/null
++++ b/hello.txt
+@@ -0,0 +1,2 @@
++line one
++line two
+",
+            // deleted file
+            "\
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index abc1234..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+",
+            // modification with two hunks
+            "\
+diff --git a/file.txt b/file.txt
+index abc1234..def5678 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1,3 +1,3 @@ fn main()
+ line one
+-line two old
++line two new
+ line three
+@@ -20,2 +20,3 @@
+ twenty
++twenty and a half
+ twenty one
+",
+            // rename without content change
+            "\
+diff --git a/old_name.txt b/new_name.txt
+similarity index 100%
+rename from old_name.txt
+rename to new_name.txt
+",
+            // rename with edits
+            "\
+diff --git a/a.txt b/b.txt
+similarity index 80%
+rename from a.txt
+rename to b.txt
+index 111..222 100644
+--- a/a.txt
++++ b/b.txt
+@@ -1 +1 @@
+-x
++y
+",
+            // missing trailing newline on both sides
+            "\
+diff --git a/no_nl.txt b/no_nl.txt
+index abc..def 100644
+--- a/no_nl.txt
++++ b/no_nl.txt
+@@ -1 +1 @@
+-old
+\\ No newline at end of file
++new
+\\ No newline at end of file
+",
+            // file becomes a symlink
+            "\
+diff --git a/config b/config
+old mode 100644
+new mode 120000
+index abc1234..def5678
+--- a/config
++++ b/config
+@@ -1 +1 @@
+-key = value
++../shared/config
+\\ No newline at end of file
+",
+            // copy with edits
+            "\
+diff --git a/base.rs b/copy.rs
+similarity index 87%
+copy from base.rs
+copy to copy.rs
+index abc..def 100644
+--- a/base.rs
++++ b/copy.rs
+@@ -1 +1 @@
+-fn base() {}
++fn copy() {}
+",
+            // binary file
+            "\
+diff --git a/image.png b/image.png
+new file mode 100644
+index 0000000..abc1234
+Binary files /dev/null and b/image.png differ
+",
+        ];
+
+        for fixture in fixtures {
+            let parsed = parse_unified_diff(fixture);
+            let reparsed = parse_unified_diff(&reconstruct_unified_diff(&parsed));
+            assert_eq!(reparsed, parsed, "round trip changed:\n{}", fixture);
+        }
+    }
+
+    #[test]
+    fn test_parse_plain_diff_u() {
+        let diff = "\
+--- old/greeting.txt\t2026-01-01 10:00:00.000000000 +0000
++++ new/greeting.txt\t2026-01-02 10:00:00.000000000 +0000
+@@ -1,2 +1,2 @@
+ hello
+--- world
++++ there
+--- old/notes.txt\t2026-01-01 10:00:00.000000000 +0000
++++ new/notes.txt\t2026-01-02 10:00:00.000000000 +0000
+@@ -1 +1,2 @@
+ first
++second
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 2);
+
+        assert_eq!(files[0].path, "new/greeting.txt");
+        assert_eq!(files[0].status, FileStatus::Modified);
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].content, "-- world");
+        assert_eq!(lines[1].line_type, LineType::Deletion);
+        assert_eq!(lines[2].content, "++ there");
+
+        assert_eq!(files[1].path, "new/notes.txt");
+        assert_eq!(files[1].hunks[0].lines.len(), 2);
+        assert_eq!(files[1].additions(), 1);
+    }
+
+    #[test]
+    fn test_parse_plain_diff_u_added_and_deleted() {
+        let diff = "\
+--- /dev/null
++++ created.txt
+@@ -0,0 +1 @@
++new
+--- removed.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-old
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "created.txt");
+        assert_eq!(files[0].status, FileStatus::Added);
+        assert_eq!(files[1].path, "removed.txt");
+        assert_eq!(files[1].status, FileStatus::Deleted);
+    }
+
+    #[test]
+    fn test_parse_hg_diff() {
+        let diff = "\
+diff -r 9117c6561b0b src/main.rs
+--- a/src/main.rs\tThu Jan 01 00:00:00 1970 +0000
++++ b/src/main.rs\tFri Jan 02 00:00:00 1970 +0000
+@@ -1,2 +1,2 @@
+ fn main() {
+-    old();
++    new();
+diff -r 9117c6561b0b -r 273ce12ad8f1 docs/new file.md
+--- /dev/null\tThu Jan 01 00:00:00 1970 +0000
++++ b/docs/new file.md\tFri Jan 02 00:00:00 1970 +0000
+@@ -0,0 +1 @@
++# Notes
+";
+        let files = parse_unified_diff(diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/main.rs");
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert_eq!(files[0].hunks[0].lines.len(), 3);
+        assert_eq!(files[1].path, "docs/new file.md");
+        assert_eq!(files[1].status, FileStatus::Added);
+    }
+
+    #[test]
+    fn test_parse_unified_diff_lines_matches_collecting_parse() {
+        let git = "\
+From 1234567 Mon Sep 17 00:00:00 2001
+Subject: [PATCH] Example
+
+diff --git a/src/lib.rs b/src/lib.rs
+index abc1234..def5678 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+ fn a() {}
+-fn b() {}
++fn c() {}
+?? stray
+@@ -9,x +9 @@
+diff --git a/img.png b/img.png
+Binary files a/img.png and b/img.png differ
+";
+        let plain = "\
+--- old/a.txt
++++ new/a.txt
+@@ -1 +1 @@
+-a
++b
+\\ No newline at end of file
+";
+        let hg = "\
+diff -r 9117c6561b0b src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1 +1 @@
+-old();
++new();
+";
+        for diff in [git, plain, hg] {
+            let collected = parse_unified_diff_verbose(diff);
+            assert!(!collected.0.is_empty());
+            // Fed from two separate buffers, as chunks read off a pipe would be
+            let (head, tail) = diff.split_at(diff.find("\n+").unwrap() + 1);
+            let streamed = parse_unified_diff_lines(head.lines().chain(tail.lines()));
+            assert_eq!(streamed, collected);
+        }
+        assert_eq!(parse_unified_diff_verbose(git).1.len(), 2);
+    }
+
+    #[test]
+    fn test_hg_diff_args() {
+        assert_eq!(hg_diff_args(None), vec!["diff"]);
+        assert_eq!(hg_diff_args(Some("tip")), vec!["diff", "-r", "tip"]);
+        assert_eq!(
+            hg_diff_args(Some("a..b")),
+            vec!["diff", "-r", "a", "-r", "b"]
+        );
+        assert_eq!(
+            hg_diff_args(Some("default...feature")),
+            vec!["diff", "-r", "ancestor(default, feature)", "-r", "feature"]
+        );
+    }
+
+    #[test]
+    fn test_parse_name_status() {
+        let output = "M\0src/lib.rs\0A\0new file.txt\0D\0gone.txt\0R087\0old.rs\0new.rs\0C100\0base.rs\0copy.rs\0T\0link\0";
+        let files = parse_name_status(output);
+        assert_eq!(
+            files,
+            vec![
+                ChangedFile {
+                    path: "src/lib.rs".to_string(),
+                    old_path: None,
+                    status: FileStatus::Modified,
+                },
+                ChangedFile {
+                    path: "new file.txt".to_string(),
+                    old_path: None,
+                    status: FileStatus::Added,
+                },
+                ChangedFile {
+                    path: "gone.txt".to_string(),
+                    old_path: None,
+                    status: FileStatus::Deleted,
+                },
+                ChangedFile {
+                    path: "new.rs".to_string(),
+                    old_path: Some("old.rs".to_string()),
+                    status: FileStatus::Renamed,
+                },
+                ChangedFile {
+                    path: "copy.rs".to_string(),
+                    old_path: Some("base.rs".to_string()),
+                    status: FileStatus::Copied,
+                },
+                ChangedFile {
+                    path: "link".to_string(),
+                    old_path: None,
+                    status: FileStatus::Modified,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_git_name_status_detects_rename() {
+        let repo = crate::test_support::TempRepo::new("name-status-rename");
+        repo.write("before.txt", "one\ntwo\nthree\nfour\n");
+        repo.write("kept.txt", "kept\n");
+        repo.commit_all("initial");
+        repo.git(&["mv", "before.txt", "after.txt"]);
+        repo.write("kept.txt", "changed\n");
+
+        let files = run_git_name_status(None, repo.path_str()).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "after.txt");
+        assert_eq!(files[0].old_path.as_deref(), Some("before.txt"));
+        assert_eq!(files[0].status, FileStatus::Renamed);
+        assert_eq!(files[1].path, "kept.txt");
+        assert_eq!(files[1].status, FileStatus::Modified);
+    }
+
+    #[test]
+    fn test_split_file_chunks() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,1 +1,1 @@
+-old a
++new a
+diff --git a/b.txt b/b.txt
+--- a/b.txt
++++ b/b.txt
+@@ -1,1 +1,1 @@
+-old b
++new b
+";
+        let chunks = split_file_chunks(diff);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].starts_with("diff --git a/a.txt"));
+        assert!(chunks[1].starts_with("diff --git a/b.txt"));
+        assert_eq!(chunks.concat(), diff);
+
+        let files: Vec<DiffFile> = chunks
+            .iter()
+            .flat_map(|chunk| parse_unified_diff(chunk))
+            .collect();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].path, "b.txt");
+        assert_eq!(files[1].hunks[0].lines[1].content, "new b");
     }
 
     #[test]
-    fn test_parse_new_file() {
+    fn test_split_file_chunks_empty() {
+        assert!(split_file_chunks("").is_empty());
+    }
+
+    #[test]
+    fn test_split_file_chunks_plain_diff() {
         let diff = "\
-diff --git a/new.rs b/new.rs
-new file mode 100644
-index 0000000..abc1234
---- /dev/null
-+++ b/new.rs
-@@ -0,0 +1,2 @@
-+fn main() {}
-+// done
+--- a.txt\t2024-01-01
++++ a.txt\t2024-01-02
+@@ -1 +1 @@
+-old
++new
+--- b.txt
++++ b.txt
+@@ -1 +1 @@
+-x
++y
 ";
-        let files = parse_unified_diff(diff);
-        assert_eq!(files.len(), 1);
-        assert_eq!(files[0].status, FileStatus::Added);
-        assert_eq!(files[0].hunks[0].lines.len(), 2);
-        assert_eq!(files[0].hunks[0].lines[0].content, "fn main() {}");
+        let chunks = split_file_chunks(diff);
+        assert_eq!(chunks, vec![diff]);
+        assert_eq!(parse_unified_diff(chunks[0]).len(), 2);
     }
 
     #[test]
-    fn test_parse_deleted_file() {
+    fn test_diff_options_default_args() {
+        assert_eq!(
+            DiffOptions::default().to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--end-of-options",
+                "HEAD"
+            ]
+        );
+        assert_eq!(
+            DiffOptions::default().args_without_head(),
+            vec!["-c", "diff.indentHeuristic=true", "diff"]
+        );
+    }
+
+    #[test]
+    fn test_diff_options_full_args() {
+        let options = DiffOptions::new()
+            .range("main..feature")
+            .context_lines(5)
+            .whitespace(WhitespaceMode::IgnoreAll)
+            .algorithm(DiffAlgorithm::Histogram)
+            .rename_threshold(40)
+            .pathspec("src/")
+            .pathspec("docs/a b.md")
+            .reverse(true);
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "-U5",
+                "-w",
+                "--diff-algorithm=histogram",
+                "-M40%",
+                "-R",
+                "--end-of-options",
+                "main..feature",
+                "--",
+                "src/",
+                "docs/a b.md",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_options_no_renames() {
+        let options = DiffOptions::new()
+            .range("main..feature")
+            .rename_threshold(40)
+            .no_renames(true);
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--no-renames",
+                "--end-of-options",
+                "main..feature"
+            ]
+        );
+
+        let repo = crate::test_support::TempRepo::new("no-renames");
+        repo.write("old.txt", "same content\n");
+        repo.commit_all("initial");
+        repo.git(&["mv", "old.txt", "new.txt"]);
+        repo.commit_all("rename");
+
+        let options = DiffOptions::new().range("HEAD~1..HEAD").no_renames(true);
+        let files = parse_unified_diff(&run_git_diff(&options, repo.path_str()).unwrap());
+        let statuses: Vec<_> = files.iter().map(|f| (f.path.as_str(), &f.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("new.txt", &FileStatus::Added),
+                ("old.txt", &FileStatus::Deleted)
+            ]
+        );
+        assert!(files.iter().all(|f| f.old_path.is_none()));
+    }
+
+    #[test]
+    fn test_parse_word_diff() {
         let diff = "\
-diff --git a/gone.txt b/gone.txt
-deleted file mode 100644
-index abc1234..0000000
---- a/gone.txt
-+++ /dev/null
-@@ -1,1 +0,0 @@
--goodbye
+diff --git a/f b/f
+index ddc2b76..f94fda4 100644
+--- a/f
++++ b/f
+@@ -1,4 +1,5 @@
+ one 
+-two
++TWO
+  three 
++four
+~
+ keep
+~
+-removed line
+~
+ 
+~
+~
++added line
+~
 ";
-        let files = parse_unified_diff(diff);
+        let files = parse_word_diff(diff);
+        assert_eq!(files.len(), 1);
+        let lines: Vec<_> = files[0].hunks[0]
+            .lines
+            .iter()
+            .map(|l| (&l.line_type, l.content.as_str(), l.word_ranges.clone()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (&LineType::Deletion, "one two three ", vec![(4, 7)]),
+                (
+                    &LineType::Addition,
+                    "one TWO three four",
+                    vec![(4, 7), (14, 18)]
+                ),
+                (&LineType::Context, "keep", vec![]),
+                (&LineType::Deletion, "removed line", vec![(0, 12)]),
+                (&LineType::Context, "", vec![]),
+                (&LineType::Addition, "", vec![]),
+                (&LineType::Addition, "added line", vec![(0, 10)]),
+            ]
+        );
+        let hunk = &files[0].hunks[0];
+        assert_eq!(hunk.lines[2].old_line_no, Some(2));
+        assert_eq!(hunk.lines[6].new_line_no, Some(5));
+    }
+
+    #[test]
+    fn test_diff_options_word_diff() {
+        let options = DiffOptions::new().range("HEAD~1..HEAD").word_diff(true);
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--word-diff=porcelain",
+                "--end-of-options",
+                "HEAD~1..HEAD"
+            ]
+        );
+
+        let repo = crate::test_support::TempRepo::new("word-diff");
+        repo.write("a.txt", "let x = 1;\nuntouched\n");
+        repo.commit_all("initial");
+        repo.write("a.txt", "let y = 1;\nuntouched\n");
+        repo.commit_all("rename x");
+
+        let files = parse_word_diff(&run_git_diff(&options, repo.path_str()).unwrap());
+        let lines = &files[0].hunks[0].lines;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].content, "let y = 1;");
+        assert_eq!(lines[1].word_ranges, vec![(4, 5)]);
+        assert_eq!(lines[2].line_type, LineType::Context);
+    }
+
+    #[test]
+    fn test_diff_options_config_overrides() {
+        let options = DiffOptions::new()
+            .config_override("diff.indentHeuristic", "true")
+            .config_override("diff.\"my driver\".textconv", "cat -A")
+            .range("main..topic");
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "-c",
+                "diff.indentHeuristic=true",
+                "-c",
+                "diff.\"my driver\".textconv=cat -A",
+                "diff",
+                "--end-of-options",
+                "main..topic"
+            ]
+        );
+
+        let repo = crate::test_support::TempRepo::new("config-overrides");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("initial");
+        repo.write("a.txt", "two\n");
+        let options = DiffOptions::new().config_override("diff.noprefix", "true");
+        let diff = run_git_diff(&options, repo.path_str()).unwrap();
+        assert!(diff.contains("--- a.txt\n+++ a.txt"), "got: {diff}");
+
+        for bad in [
+            "noprefix",
+            "diff.",
+            ".noprefix",
+            "diff.no=prefix",
+            "diff.x=y.textconv",
+            "diff.1st",
+        ] {
+            let options = DiffOptions::new().config_override(bad, "true");
+            let err = run_git_diff(&options, repo.path_str()).unwrap_err();
+            assert!(err.starts_with("Invalid git config key"), "{bad}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_diff_options_indent_heuristic() {
+        // On unless turned off, also when the frontend leaves the field out
+        let options: DiffOptions = serde_json::from_str("{}").unwrap();
+        assert!(options.indent_heuristic);
+        let options = DiffOptions::new().indent_heuristic(false);
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=false",
+                "diff",
+                "--end-of-options",
+                "HEAD"
+            ]
+        );
+        let options = options
+            .indent_heuristic(true)
+            .config_override("diff.indentHeuristic", "false");
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "-c",
+                "diff.indentHeuristic=false",
+                "diff",
+                "--end-of-options",
+                "HEAD"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_options_merge_parent() {
+        let options = DiffOptions::new().range("abc123^!");
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--end-of-options",
+                "abc123^1",
+                "abc123"
+            ]
+        );
+        let options = options.merge_parent(2);
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--end-of-options",
+                "abc123^2",
+                "abc123"
+            ]
+        );
+        let options = options.range("main..topic");
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--end-of-options",
+                "main..topic"
+            ]
+        );
+
+        let repo = crate::test_support::TempRepo::new("merge-parent");
+        repo.write("base.txt", "base\n");
+        repo.commit_all("base");
+        repo.git(&["checkout", "-q", "-b", "topic"]);
+        repo.write("topic.txt", "topic\n");
+        repo.commit_all("topic");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("main.txt", "main\n");
+        repo.commit_all("main");
+        repo.git(&["merge", "-q", "--no-edit", "topic"]);
+
+        let paths = |options: &DiffOptions| -> Vec<String> {
+            let diff = run_git_diff(options, repo.path_str()).unwrap();
+            parse_unified_diff(&diff)
+                .into_iter()
+                .map(|f| f.path)
+                .collect()
+        };
+        let options = DiffOptions::new().range("HEAD^!");
+        // Against main's tip the merge brought in topic's file, and the other way round
+        assert_eq!(paths(&options), vec!["topic.txt"]);
+        assert_eq!(paths(&options.clone().merge_parent(2)), vec!["main.txt"]);
+    }
+
+    #[test]
+    fn test_diff_options_full_context() {
+        let options = DiffOptions::new().context_lines(3).full_context(true);
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "-U2147483647",
+                "--end-of-options",
+                "HEAD"
+            ]
+        );
+
+        let repo = crate::test_support::TempRepo::new("full-context");
+        let content: String = (1..=50).map(|n| format!("line {n}\n")).collect();
+        repo.write("a.txt", &content);
+        repo.commit_all("initial");
+        repo.write("a.txt", &content.replace("line 25\n", "line twenty-five\n"));
+
+        let files = parse_unified_diff(&run_git_diff(&options, repo.path_str()).unwrap());
+        assert_eq!(files[0].hunks.len(), 1);
+        assert_eq!(files[0].hunks[0].new_count, 50);
+    }
+
+    #[test]
+    fn test_diff_options_staged_against_args() {
+        let options = DiffOptions::new()
+            .range("ignored..range")
+            .context_lines(1)
+            .target(DiffTarget::StagedAgainst("main".to_string()))
+            .pathspec("src/");
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--cached",
+                "-U1",
+                "--end-of-options",
+                "main",
+                "--",
+                "src/"
+            ]
+        );
+
+        let options: DiffOptions =
+            serde_json::from_str(r#"{"target": {"StagedAgainst": "v1.0"}}"#).unwrap();
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--cached",
+                "--end-of-options",
+                "v1.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_git_diff_staged_against() {
+        let repo = crate::test_support::TempRepo::new("staged-against");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("first");
+        repo.git(&["tag", "base"]);
+        repo.write("a.txt", "two\n");
+        repo.commit_all("second");
+        repo.write("b.txt", "staged\n");
+        repo.git(&["add", "b.txt"]);
+        repo.write("c.txt", "unstaged\n");
+
+        let options = DiffOptions::new().target(DiffTarget::StagedAgainst("base".to_string()));
+        let files = parse_unified_diff(&run_git_diff(&options, repo.path_str()).unwrap());
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "b.txt"]);
+
+        // Taken as a revision, not as git's `--output` option
+        let written = repo.join("written.patch");
+        let commit = format!("--output={}", written.display());
+        let options = DiffOptions::new().target(DiffTarget::StagedAgainst(commit));
+        assert!(run_git_diff(&options, repo.path_str()).is_err());
+        assert!(!written.exists());
+    }
+
+    #[test]
+    fn test_diff_options_against_upstream_args() {
+        let options = DiffOptions::new()
+            .range("ignored..range")
+            .target(DiffTarget::AgainstUpstream);
+        assert_eq!(
+            options.to_args(),
+            vec![
+                "-c",
+                "diff.indentHeuristic=true",
+                "diff",
+                "--end-of-options",
+                "@{upstream}...HEAD"
+            ]
+        );
+
+        let options: DiffOptions =
+            serde_json::from_str(r#"{"target": "AgainstUpstream"}"#).unwrap();
+        assert_eq!(options.target, DiffTarget::AgainstUpstream);
+    }
+
+    #[test]
+    fn test_run_git_diff_merge_base_with() {
+        let repo = crate::test_support::TempRepo::new("merge-base-with");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("base");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.write("b.txt", "committed on feature\n");
+        repo.commit_all("on feature");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("a.txt", "main moved on\n");
+        repo.commit_all("on main");
+        repo.git(&["checkout", "-q", "feature"]);
+
+        repo.write("b.txt", "and edited\n");
+        repo.write("new dir/c.txt", "untracked\n");
+        repo.write(".gitignore", "*.log\n");
+        repo.write("debug.log", "ignored\n");
+
+        let options = DiffOptions::new().target(DiffTarget::MergeBaseWith("main".to_string()));
+        let files = parse_unified_diff(&run_git_diff(&options, repo.path_str()).unwrap());
+        let summary: Vec<(&str, &FileStatus)> =
+            files.iter().map(|f| (f.path.as_str(), &f.status)).collect();
+        // a.txt only changed on main, after the fork point
+        assert_eq!(
+            summary,
+            vec![
+                ("b.txt", &FileStatus::Added),
+                (".gitignore", &FileStatus::Added),
+                ("new dir/c.txt", &FileStatus::Added),
+            ]
+        );
+        assert_eq!(files[0].hunks[0].lines[0].content, "and edited");
+
+        let narrowed = options.clone().pathspec("new dir");
+        let files = parse_unified_diff(&run_git_diff(&narrowed, repo.path_str()).unwrap());
         assert_eq!(files.len(), 1);
+
+        // Untracked files are diffed with the same format flags as the rest
+        repo.write("new dir/c.txt", "untracked\nand more\n");
+        let reversed = narrowed.clone().reverse(true);
+        let diff_text =
+            tauri::async_runtime::block_on(run_git_diff_async(&reversed, repo.path_str()));
+        let files = parse_unified_diff(&diff_text.unwrap());
         assert_eq!(files[0].status, FileStatus::Deleted);
-        assert_eq!(files[0].hunks[0].lines.len(), 1);
-        assert_eq!(files[0].hunks[0].lines[0].content, "goodbye");
-        assert_eq!(files[0].hunks[0].lines[0].line_type, LineType::Deletion);
+        let words = narrowed.word_diff(true);
+        let files = parse_word_diff(&run_git_diff(&words, repo.path_str()).unwrap());
+        assert_eq!(files[0].hunks[0].lines.len(), 2);
+
+        let bad = DiffOptions::new().target(DiffTarget::MergeBaseWith("nope".to_string()));
+        assert!(run_git_diff(&bad, repo.path_str()).is_err());
+        let err = run_remote_git_diff("host:/repo", &options, &SshOptions::default()).unwrap_err();
+        assert!(err.contains("only supported for local"), "got: {err}");
     }
 
     #[test]
-    fn test_parse_binary_files_skipped() {
-        let diff = "\
-diff --git a/image.png b/image.png
-new file mode 100644
-Binary files /dev/null and b/image.png differ
-diff --git a/text.txt b/text.txt
-index abc..def 100644
---- a/text.txt
-+++ b/text.txt
-@@ -1,1 +1,1 @@
--old
-+new
-";
-        let files = parse_unified_diff(diff);
-        assert_eq!(files.len(), 2);
-        // Binary file should have no hunks
-        assert_eq!(files[0].path, "image.png");
+    fn test_run_git_diff_against_upstream() {
+        let repo = crate::test_support::TempRepo::new("against-upstream");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("first");
+        let options = DiffOptions::new().target(DiffTarget::AgainstUpstream);
+        let err = run_git_diff(&options, repo.path_str()).unwrap_err();
+        assert!(err.contains("No upstream branch"), "got: {err}");
+
+        repo.git(&["checkout", "-q", "-b", "feature", "--track", "main"]);
+        repo.write("b.txt", "feature\n");
+        repo.commit_all("on feature");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("a.txt", "upstream moved on\n");
+        repo.commit_all("on main");
+        repo.git(&["checkout", "-q", "feature"]);
+
+        assert_eq!(resolve_upstream(repo.path_str()).unwrap(), "main");
+        let files = parse_unified_diff(&run_git_diff(&options, repo.path_str()).unwrap());
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["b.txt"]);
+    }
+
+    #[test]
+    fn test_diff_options_deserialize_partial() {
+        let options: DiffOptions =
+            serde_json::from_str(r#"{"range": "HEAD~1", "whitespace": "IgnoreEol"}"#).unwrap();
+        assert_eq!(
+            options,
+            DiffOptions::new()
+                .range("HEAD~1")
+                .whitespace(WhitespaceMode::IgnoreEol)
+        );
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_output_or_cancel_kills_child() {
+        let cancel = Notify::new();
+        cancel.notify_one();
+        let mut cmd = Command::new("sleep");
+        cmd.arg("30");
+
+        let started = std::time::Instant::now();
+        let err =
+            tauri::async_runtime::block_on(output_or_cancel(cmd, &cancel, "sleep")).unwrap_err();
+        assert_eq!(err, DIFF_CANCELLED);
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_run_git_diff_async_matches_sync() {
+        let root = crate::repo_info::find_repo_root().unwrap();
+        let sync_diff = run_git_diff(&DiffOptions::new().range("HEAD"), &root).unwrap();
+        let async_diff = tauri::async_runtime::block_on(run_git_diff_async(
+            &DiffOptions::new().range("HEAD"),
+            &root,
+        ))
+        .unwrap();
+        assert_eq!(async_diff, sync_diff);
+    }
+
+    #[test]
+    fn test_git_command_forces_stable_output() {
+        let cmd = git_command();
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert!(envs.contains(&("LC_ALL".as_ref(), Some("C".as_ref()))));
+        assert!(envs.contains(&("GIT_PAGER".as_ref(), Some("cat".as_ref()))));
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(args, ["-c", "color.ui=false", "-c", "core.pager=cat"]);
+    }
+
+    #[test]
+    fn test_binary_detection_under_non_c_locale() {
+        let dir = std::env::temp_dir().join(format!("great-review-locale-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let left = dir.join("left.bin");
+        let right = dir.join("right.bin");
+        std::fs::write(&left, [0u8, 1, 2, 3]).unwrap();
+        std::fs::write(&right, [0u8, 4, 5, 6]).unwrap();
+
+        // git diff --no-index exits 1 when the files differ, so only stdout matters here
+        let output = git_command()
+            .env("LANG", "de_DE.UTF-8")
+            .env("LANGUAGE", "de")
+            .args(["diff", "--no-index", "--"])
+            .arg(&left)
+            .arg(&right)
+            .output()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let diff = String::from_utf8_lossy(&output.stdout);
+        assert!(diff.contains("Binary files"));
+        let files = parse_unified_diff(&diff);
+        assert_eq!(files.len(), 1);
         assert!(files[0].hunks.is_empty());
-        // Text file should parse normally
-        assert_eq!(files[1].path, "text.txt");
-        assert_eq!(files[1].hunks.len(), 1);
     }
 
     #[test]
-    fn test_parse_empty_diff() {
-        let files = parse_unified_diff("");
-        assert!(files.is_empty());
+    fn test_run_git_diff_range_in_bare_repo() {
+        let repo = crate::test_support::TempRepo::bare("diff-bare");
+        let diff = run_git_diff(&DiffOptions::new().range("HEAD..HEAD"), repo.path_str()).unwrap();
+        assert!(diff.is_empty());
     }
 
     #[test]
-    fn test_line_numbers() {
-        let diff = "\
-diff --git a/nums.txt b/nums.txt
-index abc..def 100644
---- a/nums.txt
-+++ b/nums.txt
-@@ -10,4 +10,4 @@
- context at 10
--deleted at 11
-+added at 11
- context at 12
-";
-        let files = parse_unified_diff(diff);
-        let lines = &files[0].hunks[0].lines;
+    fn test_run_git_diff_missing_repo_path() {
+        let err =
+            run_git_diff(&DiffOptions::default(), "/nonexistent/great-review/repo").unwrap_err();
+        assert_eq!(
+            err,
+            "Repository path '/nonexistent/great-review/repo' does not exist"
+        );
+    }
 
-        // Context line at old=10, new=10
-        assert_eq!(lines[0].old_line_no, Some(10));
-        assert_eq!(lines[0].new_line_no, Some(10));
+    #[test]
+    fn test_run_git_diff_repo_path_is_file() {
+        let repo = crate::test_support::TempRepo::new("path-is-file");
+        repo.write("file.txt", "x\n");
+        let file = repo.join("file.txt");
+        let err = run_git_diff(&DiffOptions::default(), file.to_str().unwrap()).unwrap_err();
+        assert!(err.ends_with("is not a directory"));
+    }
 
-        // Deletion at old=11
-        assert_eq!(lines[1].old_line_no, Some(11));
-        assert!(lines[1].new_line_no.is_none());
+    #[test]
+    fn test_run_git_diff_without_commits() {
+        let repo = crate::test_support::TempRepo::new("diff-no-commits");
+        repo.write("file.txt", "one\n");
+        repo.git(&["add", "file.txt"]);
+        repo.write("file.txt", "one\ntwo\n");
+        assert!(!has_commits(repo.path_str()).unwrap());
 
-        // Addition at new=11
-        assert!(lines[2].old_line_no.is_none());
-        assert_eq!(lines[2].new_line_no, Some(11));
+        let files =
+            parse_unified_diff(&run_git_diff(&DiffOptions::default(), repo.path_str()).unwrap());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].additions(), 1);
+        assert_eq!(run_git_name_status(None, repo.path_str()).unwrap().len(), 1);
 
-        // Context at old=12, new=12
-        assert_eq!(lines[3].old_line_no, Some(12));
-        assert_eq!(lines[3].new_line_no, Some(12));
+        let err =
+            run_git_diff(&DiffOptions::new().range("main..HEAD"), repo.path_str()).unwrap_err();
+        assert_eq!(
+            err,
+            "Repository has no commits yet; cannot diff range 'main..HEAD'"
+        );
+
+        let err = run_git_diff(&DiffOptions::new().strict(true), repo.path_str()).unwrap_err();
+        assert!(err.contains("ambiguous argument 'HEAD'"), "got: {err}");
     }
 
     #[test]
-    fn test_no_newline_at_end() {
-        let diff = "\
-diff --git a/no_nl.txt b/no_nl.txt
-index abc..def 100644
---- a/no_nl.txt
-+++ b/no_nl.txt
-@@ -1,1 +1,1 @@
--old content
-\\ No newline at end of file
-+new content
-\\ No newline at end of file
-";
-        let files = parse_unified_diff(diff);
-        let lines = &files[0].hunks[0].lines;
-        assert_eq!(lines.len(), 2);
-        assert_eq!(lines[0].line_type, LineType::Deletion);
-        assert_eq!(lines[0].content, "old content");
-        assert_eq!(lines[1].line_type, LineType::Addition);
-        assert_eq!(lines[1].content, "new content");
+    fn test_diff_branches() {
+        let repo = crate::test_support::TempRepo::new("diff-branches");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("base");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.write("feature.txt", "new\n");
+        repo.commit_all("feature");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("a.txt", "two\n");
+        repo.commit_all("main moves on");
+
+        let files = diff_branches("main", "feature", repo.path_str()).unwrap();
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["feature.txt"]);
+
+        let err = diff_branches("mian", "feature", repo.path_str()).unwrap_err();
+        assert_eq!(err, "Unknown base branch 'mian'");
+        let err = diff_branches("main", "featrue", repo.path_str()).unwrap_err();
+        assert_eq!(err, "Unknown head branch 'featrue'");
+    }
+
+    #[test]
+    fn test_run_stash_diff() {
+        let repo = crate::test_support::TempRepo::new("stash-diff");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("initial");
+        repo.write("a.txt", "one\ntwo\n");
+        repo.git(&["stash", "push", "-m", "first"]);
+        repo.write("a.txt", "zero\none\n");
+        repo.git(&["stash", "push", "-m", "second"]);
+
+        let latest = parse_unified_diff(&run_stash_diff(DEFAULT_STASH, repo.path_str()).unwrap());
+        assert_eq!(latest[0].hunks[0].lines[0].content, "zero");
+
+        let older = parse_unified_diff(&run_stash_diff("stash@{1}", repo.path_str()).unwrap());
+        assert_eq!(older[0].path, "a.txt");
+        assert_eq!(older[0].hunks[0].lines[1].content, "two");
+
+        let err = run_stash_diff("stash@{5}", repo.path_str()).unwrap_err();
+        assert!(
+            err.starts_with("Failed to show stash 'stash@{5}'"),
+            "got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_apply_patch_check_and_apply() {
+        let repo = crate::test_support::TempRepo::new("apply-patch");
+        repo.write("file.txt", "one\ntwo\nthree\n");
+        repo.commit_all("initial");
+        repo.write("file.txt", "one\nTWO\nthree\n");
+        let patch = repo.git(&["diff"]);
+        repo.git(&["checkout", "--", "file.txt"]);
+
+        apply_patch(&patch, repo.path_str(), true).unwrap();
+        // --check must not touch the working tree
+        assert_eq!(
+            std::fs::read_to_string(repo.join("file.txt")).unwrap(),
+            "one\ntwo\nthree\n"
+        );
+
+        apply_patch(&patch, repo.path_str(), false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(repo.join("file.txt")).unwrap(),
+            "one\nTWO\nthree\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_patch_reports_failed_hunks() {
+        let repo = crate::test_support::TempRepo::new("apply-patch-conflict");
+        repo.write("file.txt", "one\ntwo\nthree\n");
+        repo.commit_all("initial");
+        repo.write("file.txt", "one\nTWO\nthree\n");
+        let patch = repo.git(&["diff"]);
+        repo.write("file.txt", "one\nsomething else\nthree\n");
+
+        let err = apply_patch(&patch, repo.path_str(), true).unwrap_err();
+        assert!(err.starts_with("Patch does not apply. Failed hunks (file:line): file.txt:1"));
     }
 
     #[test]
@@ -533,14 +4808,54 @@ index abc..def 100644
     fn test_parse_remote_path_missing_colon() {
         let result = parse_remote_path("no-colon-here");
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Invalid remote path"));
+        let err = result.unwrap_err();
+        assert!(err.contains("Invalid remote path"));
+        assert!(err.contains("missing ':' between host and path"));
     }
 
     #[test]
     fn test_parse_remote_path_colon_at_end() {
-        let (host, path) = parse_remote_path("host:").unwrap();
-        assert_eq!(host, "host");
-        assert_eq!(path, "");
+        let err = parse_remote_path("host:").unwrap_err();
+        assert!(err.contains("repository path is empty"));
+    }
+
+    #[test]
+    fn test_parse_remote_path_empty_host() {
+        let err = parse_remote_path(":/home/user/repo").unwrap_err();
+        assert!(err.contains("host is empty"));
+
+        let err = parse_remote_path("user@:/home/user/repo").unwrap_err();
+        assert!(err.contains("host is empty"));
+    }
+
+    #[test]
+    fn test_parse_remote_path_option_host() {
+        let err = parse_remote_path("-oProxyCommand=touch /tmp/x:/r").unwrap_err();
+        assert!(err.contains("must not start with '-'"), "got: {err}");
+        // Only a leading `-` makes ssh read an option
+        assert!(parse_remote_path("user@-oProxyCommand=x:/r").is_ok());
+    }
+
+    #[test]
+    fn test_remote_diff_command_quotes_path() {
+        let cmd = remote_diff_command("/srv/it's here", &DiffOptions::new());
+        assert!(
+            cmd.starts_with("cd '/srv/it'\\''s here' && git "),
+            "got: {cmd}"
+        );
+    }
+
+    #[test]
+    fn test_parse_remote_path_empty_user() {
+        let err = parse_remote_path("@myhost:/opt/code").unwrap_err();
+        assert!(err.contains("user before '@' is empty"));
+    }
+
+    #[test]
+    fn test_parse_remote_path_relative_path() {
+        let err = parse_remote_path("myhost:dev/repo").unwrap_err();
+        assert!(err.contains("repository path 'dev/repo' must be absolute"));
+        assert!(err.contains("Expected format"));
     }
 
     #[test]
@@ -550,6 +4865,108 @@ index abc..def 100644
         assert_eq!(path, "/path:with:colons");
     }
 
+    #[test]
+    fn test_ssh_command_batch_mode() {
+        let cmd = ssh_command("myreview", "git diff HEAD", &SshOptions::default());
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            args,
+            [
+                "-o",
+                "ConnectTimeout=10",
+                "-o",
+                "BatchMode=yes",
+                "myreview",
+                "export LC_ALL=C GIT_PAGER=cat GIT_CONFIG_COUNT=2 \
+                 GIT_CONFIG_KEY_0=color.ui GIT_CONFIG_VALUE_0=false \
+                 GIT_CONFIG_KEY_1=core.pager GIT_CONFIG_VALUE_1=cat; git diff HEAD"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_transient_ssh_failure() {
+        let reset = "kex_exchange_identification: read: Connection reset by peer";
+        assert!(is_transient_ssh_failure(Some(255), reset));
+        assert!(!is_transient_ssh_failure(
+            Some(255),
+            "ssh: Could not resolve hostname nope"
+        ));
+        assert!(!is_transient_ssh_failure(
+            Some(255),
+            "Permission denied (publickey)."
+        ));
+        // git failing on the remote is not an ssh problem, whatever it prints
+        assert!(!is_transient_ssh_failure(
+            Some(128),
+            "fatal: bad revision 'nope' (Connection reset)"
+        ));
+    }
+
+    #[test]
+    fn test_remote_diff_async_retries() {
+        let repo = crate::test_support::TempRepo::new("ssh-retry");
+        let attempts = repo.join("attempts");
+        let ssh = SshOptions {
+            command: Some(format!(
+                "sh -c 'echo . >> {}; echo Connection reset >&2; exit 255'",
+                attempts.display()
+            )),
+            retries: 2,
+            retry_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let options = DiffOptions::default();
+        let result =
+            tauri::async_runtime::block_on(run_remote_git_diff_async("host:/repo", &options, &ssh));
+        assert!(result.is_err());
+        let count = std::fs::read_to_string(&attempts).unwrap().lines().count();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_ssh_command_custom_program() {
+        let options = SshOptions {
+            command: Some(r#"/opt/bin/ssh -J "jump host" -i '/keys/my key' -o\ X=1"#.to_string()),
+            ..Default::default()
+        };
+        let cmd = ssh_command("myreview", "git diff HEAD", &options);
+        assert_eq!(cmd.get_program(), "/opt/bin/ssh");
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            args[..6],
+            ["-J", "jump host", "-i", "/keys/my key", "-o X=1", "-o"]
+        );
+
+        let blank = SshOptions {
+            command: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ssh_command("h", "true", &blank).get_program(), "ssh");
+        assert_eq!(split_command_line(r#"a "" 'b'c"#), vec!["a", "", "bc"]);
+    }
+
+    #[test]
+    fn test_ssh_command_without_batch_mode() {
+        let options = SshOptions {
+            batch_mode: false,
+            ..Default::default()
+        };
+        let cmd = ssh_command("myreview", "git diff HEAD", &options);
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            args,
+            [
+                "-o",
+                "ConnectTimeout=10",
+                "myreview",
+                "export LC_ALL=C GIT_PAGER=cat GIT_CONFIG_COUNT=2 \
+                 GIT_CONFIG_KEY_0=color.ui GIT_CONFIG_VALUE_0=false \
+                 GIT_CONFIG_KEY_1=core.pager GIT_CONFIG_VALUE_1=cat; git diff HEAD"
+            ]
+        );
+    }
+
     #[test]
     fn test_multiple_hunks_in_one_file() {
         let diff = "\
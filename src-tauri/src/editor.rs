@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Resolves `path` (relative to the repo root, as in `DiffFile::path`) to an absolute
+/// path, rejecting anything that escapes the root through `..` or symlinks.
+pub fn resolve_in_repo(repo_root: &str, path: &str) -> Result<PathBuf, String> {
+    let root = Path::new(repo_root)
+        .canonicalize()
+        .map_err(|e| format!("Repository path '{}' is not accessible: {}", repo_root, e))?;
+    let resolved = root
+        .join(path)
+        .canonicalize()
+        .map_err(|e| format!("File '{}' is not accessible: {}", path, e))?;
+
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err(format!("File '{}' is outside the repository", path))
+    }
+}
+
+/// The editor from `$VISUAL`, then `$EDITOR`; either may carry extra arguments.
+fn configured_editor() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+}
+
+/// Builds the argv for opening `file` at `line` with `editor`, using each editor's
+/// own line syntax where it has one.
+fn editor_command(editor: &str, file: &Path, line: Option<u32>) -> Vec<String> {
+    let mut argv: Vec<String> = editor.split_whitespace().map(str::to_string).collect();
+    let name = argv
+        .first()
+        .and_then(|program| Path::new(program).file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let file = file.display().to_string();
+
+    match (name.as_str(), line) {
+        ("code" | "code-insiders" | "codium" | "cursor", Some(n)) => {
+            argv.extend(["--goto".to_string(), format!("{file}:{n}")]);
+        }
+        ("subl" | "zed" | "hx", Some(n)) => argv.push(format!("{file}:{n}")),
+        ("vim" | "nvim" | "vi" | "gvim" | "emacs" | "emacsclient" | "nano" | "kak", Some(n)) => {
+            argv.extend([format!("+{n}"), file]);
+        }
+        _ => argv.push(file),
+    }
+    argv
+}
+
+/// Opens `file` in the configured editor. Returns `Ok(false)` when no editor is
+/// configured so the caller can fall back to the system opener.
+pub fn open_with_editor(file: &Path, line: Option<u32>) -> Result<bool, String> {
+    let Some(editor) = configured_editor() else {
+        return Ok(false);
+    };
+    let argv = editor_command(&editor, file, line);
+    let Some((program, args)) = argv.split_first() else {
+        return Ok(false);
+    };
+
+    // Not waited on: the editor outlives the request
+    Command::new(program)
+        .args(args)
+        .spawn()
+        .map_err(|e| format!("Failed to launch editor '{}': {}", program, e))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    #[test]
+    fn test_editor_command_line_syntax() {
+        let file = Path::new("/repo/src/main.rs");
+        assert_eq!(
+            editor_command("code --wait", file, Some(12)),
+            vec!["code", "--wait", "--goto", "/repo/src/main.rs:12"]
+        );
+        assert_eq!(
+            editor_command("/usr/bin/vim", file, Some(3)),
+            vec!["/usr/bin/vim", "+3", "/repo/src/main.rs"]
+        );
+        assert_eq!(
+            editor_command("subl", file, Some(7)),
+            vec!["subl", "/repo/src/main.rs:7"]
+        );
+        assert_eq!(
+            editor_command("gedit", file, Some(7)),
+            vec!["gedit", "/repo/src/main.rs"]
+        );
+        assert_eq!(
+            editor_command("vim", file, None),
+            vec!["vim", "/repo/src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_in_repo() {
+        let repo = TempRepo::new("editor-resolve");
+        repo.write("src/main.rs", "fn main() {}\n");
+
+        let resolved = resolve_in_repo(repo.path_str(), "src/main.rs").unwrap();
+        assert!(resolved.ends_with("src/main.rs"));
+
+        let err = resolve_in_repo(repo.path_str(), "src/../../").unwrap_err();
+        assert!(err.contains("outside the repository"), "got: {err}");
+
+        let err = resolve_in_repo(repo.path_str(), "missing.rs").unwrap_err();
+        assert!(err.contains("not accessible"), "got: {err}");
+    }
+}
@@ -0,0 +1,300 @@
+use crate::diff_parser::{self, DiffFile, FileStatus, LineType};
+use crate::repo_info::RepoInfo;
+use crate::stats;
+
+/// Renders a Markdown digest of the reviewed files: a totals line, then one section
+/// per file with its status, paths and a fenced diff block. Files without hunks
+/// (binary or metadata-only changes) get a one-line note instead of a block.
+pub fn export_markdown(files: &[DiffFile]) -> String {
+    let mut out = String::from("# Review summary\n\n");
+
+    let summary = stats::summarize(files);
+    out.push_str(&format!(
+        "**{}, {}, {}**\n",
+        plural(summary.files_changed, "file changed", "files changed"),
+        plural(summary.insertions, "insertion(+)", "insertions(+)"),
+        plural(summary.deletions, "deletion(-)", "deletions(-)")
+    ));
+
+    for file in files {
+        out.push_str(&format!("\n## {}\n\n", file_heading(file)));
+
+        if file.hunks.is_empty() {
+            out.push_str("_No textual changes (binary or metadata-only)._\n");
+            continue;
+        }
+
+        let body: String = file.hunks.iter().map(diff_parser::format_hunk).collect();
+        let fence = fence_for(&body);
+        out.push_str(&format!("{}diff\n{}{}\n", fence, body, fence));
+    }
+
+    out
+}
+
+/// Styles for `export_html`, inlined so the page needs nothing else to display.
+const HTML_STYLE: &str = "\
+body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; margin: 2em; color: #1f2328; }
+h1 { font-size: 1.5em; }
+h2 { font-size: 1.1em; font-family: ui-monospace, monospace; }
+.meta, .summary, .note { color: #59636e; }
+.status { color: #59636e; font-weight: normal; }
+table.diff { border-collapse: collapse; width: 100%; font-family: ui-monospace, monospace; font-size: 0.85em; }
+table.diff td { padding: 0 0.5em; white-space: pre-wrap; vertical-align: top; }
+td.ln { color: #8c959f; text-align: right; user-select: none; width: 1%; }
+tr.hunk td { background: #ddf4ff; color: #59636e; }
+tr.add td { background: #e6ffec; }
+tr.del td { background: #ffebe9; }
+";
+
+/// Renders a standalone HTML page of the reviewed files, for people without the app: a
+/// title naming the repository and branch, the totals, then one section per file with
+/// its hunks as a table of old/new line numbers and color-coded lines.
+pub fn export_html(files: &[DiffFile], repo_info: &RepoInfo) -> String {
+    let title = format!("Review of {} ({})", repo_info.name, repo_info.branch);
+    let mut out = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    out.push_str("<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape_html(&title)));
+    out.push_str(&format!(
+        "<style>\n{}</style>\n</head>\n<body>\n",
+        HTML_STYLE
+    ));
+    out.push_str(&format!("<h1>{}</h1>\n", escape_html(&title)));
+    if let Some(sha) = &repo_info.head_sha {
+        let short = &sha[..sha.len().min(12)];
+        out.push_str(&format!(
+            "<p class=\"meta\">At commit <code>{}</code></p>\n",
+            escape_html(short)
+        ));
+    }
+
+    let summary = stats::summarize(files);
+    out.push_str(&format!(
+        "<p class=\"summary\">{}, {}, {}</p>\n",
+        plural(summary.files_changed, "file changed", "files changed"),
+        plural(summary.insertions, "insertion(+)", "insertions(+)"),
+        plural(summary.deletions, "deletion(-)", "deletions(-)")
+    ));
+
+    for file in files {
+        let path = match &file.old_path {
+            Some(old) => format!("{} → {}", old, file.path),
+            None => file.path.clone(),
+        };
+        out.push_str(&format!(
+            "<section class=\"file\">\n<h2>{} <span class=\"status\">({})</span></h2>\n",
+            escape_html(&path),
+            status_label(file)
+        ));
+
+        if file.hunks.is_empty() {
+            out.push_str(
+                "<p class=\"note\">No textual changes (binary or metadata-only).</p>\n</section>\n",
+            );
+            continue;
+        }
+
+        out.push_str("<table class=\"diff\">\n");
+        for hunk in &file.hunks {
+            out.push_str(&format!(
+                "<tr class=\"hunk\"><td colspan=\"3\">{}</td></tr>\n",
+                escape_html(&hunk.header)
+            ));
+            for line in &hunk.lines {
+                let (class, prefix) = match line.line_type {
+                    LineType::Addition => (" class=\"add\"", '+'),
+                    LineType::Deletion => (" class=\"del\"", '-'),
+                    LineType::Context => ("", ' '),
+                };
+                let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+                out.push_str(&format!(
+                    "<tr{}><td class=\"ln\">{}</td><td class=\"ln\">{}</td><td>{}{}</td></tr>\n",
+                    class,
+                    number(line.old_line_no),
+                    number(line.new_line_no),
+                    prefix,
+                    escape_html(&line.content)
+                ));
+            }
+        }
+        out.push_str("</table>\n</section>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Escapes the characters that would otherwise be read as markup, in text or in a
+/// quoted attribute.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("1 {}", singular)
+    } else {
+        format!("{} {}", count, plural)
+    }
+}
+
+/// `Modified`, `Renamed, 87% similar` and so on.
+fn status_label(file: &DiffFile) -> String {
+    let status = match file.status {
+        FileStatus::Added => "Added",
+        FileStatus::Modified => "Modified",
+        FileStatus::Deleted => "Deleted",
+        FileStatus::Renamed => "Renamed",
+        FileStatus::Copied => "Copied",
+    };
+    match file.similarity {
+        Some(percent) if percent < 100 => format!("{}, {}% similar", status, percent),
+        _ => status.to_string(),
+    }
+}
+
+fn file_heading(file: &DiffFile) -> String {
+    let status = status_label(file);
+    match &file.old_path {
+        Some(old) => format!("`{}` → `{}` ({})", old, file.path, status),
+        None => format!("`{}` ({})", file.path, status),
+    }
+}
+
+/// A backtick fence longer than any backtick run inside `body`, so diffs of Markdown
+/// files cannot close the block early.
+fn fence_for(body: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in body.chars() {
+        if c == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::parse_unified_diff;
+
+    #[test]
+    fn test_export_markdown() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+index abc1234..def5678 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1,2 +1,2 @@
+ line one
+-line two old
++line two new
+diff --git a/image.png b/image.png
+new file mode 100644
+Binary files /dev/null and b/image.png differ
+";
+        let markdown = export_markdown(&parse_unified_diff(diff));
+        assert_eq!(
+            markdown,
+            "# Review summary
+
+**2 files changed, 1 insertion(+), 1 deletion(-)**
+
+## `file.txt` (Modified)
+
+```diff
+@@ -1,2 +1,2 @@
+ line one
+-line two old
++line two new
+```
+
+## `image.png` (Added)
+
+_No textual changes (binary or metadata-only)._
+"
+        );
+    }
+
+    #[test]
+    fn test_export_markdown_rename_heading() {
+        let diff = "\
+diff --git a/old_name.txt b/new_name.txt
+similarity index 100%
+rename from old_name.txt
+rename to new_name.txt
+";
+        let markdown = export_markdown(&parse_unified_diff(diff));
+        assert!(markdown.contains("## `old_name.txt` → `new_name.txt` (Renamed)"));
+        assert!(markdown.contains("**1 file changed, 0 insertions(+), 0 deletions(-)**"));
+    }
+
+    fn repo_info() -> RepoInfo {
+        RepoInfo {
+            name: "demo".to_string(),
+            branch: "feature/<x>".to_string(),
+            head_sha: Some("0123456789abcdef0123".to_string()),
+            dirty: None,
+            path: "/tmp/demo".to_string(),
+            bare: false,
+            sparse: false,
+            sparse_patterns: Vec::new(),
+            working_tree_warning: None,
+        }
+    }
+
+    #[test]
+    fn test_export_html() {
+        let diff = "\
+diff --git a/index.html b/index.html
+--- a/index.html
++++ b/index.html
+@@ -1,2 +1,2 @@
+ <p>keep</p>
+-<b>old & \"quoted\"</b>
++<i>new</i>
+diff --git a/image.png b/image.png
+new file mode 100644
+Binary files /dev/null and b/image.png differ
+";
+        let html = export_html(&parse_unified_diff(diff), &repo_info());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Review of demo (feature/&lt;x&gt;)</title>"));
+        assert!(html.contains("<code>0123456789ab</code>"));
+        assert!(html.contains("2 files changed, 1 insertion(+), 1 deletion(-)"));
+        assert!(html.contains(
+            "<tr><td class=\"ln\">1</td><td class=\"ln\">1</td><td> &lt;p&gt;keep&lt;/p&gt;</td></tr>"
+        ));
+        assert!(html.contains(
+            "<tr class=\"del\"><td class=\"ln\">2</td><td class=\"ln\"></td><td>-&lt;b&gt;old &amp; &quot;quoted&quot;&lt;/b&gt;</td></tr>"
+        ));
+        assert!(html.contains("<tr class=\"add\"><td class=\"ln\"></td><td class=\"ln\">2</td>"));
+        assert!(html.contains("<h2>image.png <span class=\"status\">(Added)</span></h2>"));
+        assert!(html.contains("No textual changes"));
+        // Only the markup itself has tags: none leak in from the file content
+        assert!(!html.contains("<b>") && !html.contains("<i>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_fence_longer_than_content_backticks() {
+        assert_eq!(fence_for("no ticks"), "```");
+        assert_eq!(fence_for("+```rust"), "````");
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use crate::diff_parser::DiffFile;
+
+/// A directory or file in the changed-files tree. Files carry their index into the
+/// `files` they were built from; directories have `file: None`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TreeNode {
+    pub name: String,
+    pub children: Vec<TreeNode>,
+    pub file: Option<usize>,
+}
+
+fn insert(node: &mut TreeNode, components: &[&str], file: usize) {
+    let Some((name, rest)) = components.split_first() else {
+        return;
+    };
+    // A file and a directory of the same name (a file replaced by a directory) stay
+    // separate nodes
+    let is_file = rest.is_empty();
+    let position = node
+        .children
+        .iter()
+        .position(|c| c.name == *name && c.file.is_none() && !is_file);
+    let child = match position {
+        Some(i) => &mut node.children[i],
+        None => {
+            node.children.push(TreeNode {
+                name: name.to_string(),
+                ..Default::default()
+            });
+            node.children.last_mut().expect("just pushed")
+        }
+    };
+    if is_file {
+        child.file = Some(file);
+    } else {
+        insert(child, rest, file);
+    }
+}
+
+/// Merges each directory that only contains another directory into it, so `src/foo`
+/// is one node when `src` holds nothing else.
+fn collapse(node: &mut TreeNode) {
+    for child in &mut node.children {
+        collapse(child);
+    }
+    while node.file.is_none() && node.children.len() == 1 && node.children[0].file.is_none() {
+        let only = node.children.remove(0);
+        node.name = format!("{}/{}", node.name, only.name);
+        node.children = only.children;
+    }
+}
+
+/// Nests `files` by the `/`-separated components of their (new) paths, under an
+/// unnamed root. Directories come before files, each group sorted by name.
+pub fn build_file_tree(files: &[DiffFile]) -> TreeNode {
+    let mut root = TreeNode::default();
+    for (index, file) in files.iter().enumerate() {
+        let components: Vec<&str> = file.path.split('/').filter(|c| !c.is_empty()).collect();
+        insert(&mut root, &components, index);
+    }
+    for child in &mut root.children {
+        collapse(child);
+    }
+    sort(&mut root);
+    root
+}
+
+fn sort(node: &mut TreeNode) {
+    node.children
+        .sort_by(|a, b| (a.file.is_some(), &a.name).cmp(&(b.file.is_some(), &b.name)));
+    for child in &mut node.children {
+        sort(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::FileStatus;
+
+    fn file(path: &str) -> DiffFile {
+        DiffFile {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn names(node: &TreeNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_build_file_tree() {
+        let files = vec![
+            file("src/foo/bar.rs"),
+            file("README.md"),
+            file("src/foo/baz.rs"),
+            DiffFile {
+                old_path: Some("lib/old.rs".to_string()),
+                status: FileStatus::Renamed,
+                ..file("src/new.rs")
+            },
+            file("docs/guide/intro.md"),
+        ];
+        let tree = build_file_tree(&files);
+
+        assert_eq!(tree.name, "");
+        assert_eq!(names(&tree), vec!["docs/guide", "src", "README.md"]);
+        assert_eq!(tree.children[2].file, Some(1));
+
+        // `docs/guide` holds a single file, so it collapses into one directory node
+        let docs = &tree.children[0];
+        assert_eq!(docs.file, None);
+        assert_eq!(names(docs), vec!["intro.md"]);
+        assert_eq!(docs.children[0].file, Some(4));
+
+        // The rename is filed under its new path only
+        let src = &tree.children[1];
+        assert_eq!(names(src), vec!["foo", "new.rs"]);
+        assert_eq!(src.children[1].file, Some(3));
+        let foo_files: Vec<Option<usize>> =
+            src.children[0].children.iter().map(|c| c.file).collect();
+        assert_eq!(foo_files, vec![Some(0), Some(2)]);
+    }
+
+    #[test]
+    fn test_build_file_tree_empty() {
+        assert_eq!(build_file_tree(&[]), TreeNode::default());
+    }
+}
@@ -0,0 +1,439 @@
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Status code and body of an HTTP GET.
+struct HttpResponse {
+    status: u16,
+    body: String,
+}
+
+/// GETs `url` with curl. Headers go through a config file on stdin rather than argv
+/// so tokens never show up in the process list. With `follow_redirects`, redirects are
+/// followed to https URLs only; curl keeps sending custom headers other than
+/// `Authorization` to whatever host they point at, so a request carrying such a token
+/// must not follow them.
+fn http_get(url: &str, headers: &[String], follow_redirects: bool) -> Result<HttpResponse, String> {
+    let mut config = String::new();
+    for header in headers {
+        config.push_str(&curl_config_header(header)?);
+    }
+
+    let mut command = Command::new("curl");
+    command.args(["--silent", "--show-error", "--config", "-"]);
+    if follow_redirects {
+        command.args(["--location", "--proto-redir", "=https"]);
+    }
+    let mut child = command
+        .args(["--write-out", "\n%{http_code}", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {e}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(|e| format!("Failed to pass headers to curl: {e}"))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run curl: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Request to {} failed: {}", url, stderr.trim()));
+    }
+
+    split_status(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// A `header = "..."` line of a curl config file. A CR, LF or NUL would end the
+/// quoted value and let the rest be read as further curl options, so those are
+/// refused; `\` and `"` are escaped.
+fn curl_config_header(header: &str) -> Result<String, String> {
+    if header.contains(['\r', '\n', '\0']) {
+        return Err("Invalid HTTP header: contains a line break or NUL".to_string());
+    }
+    let escaped = header.replace('\\', "\\\\").replace('"', "\\\"");
+    Ok(format!("header = \"{}\"\n", escaped))
+}
+
+/// Splits curl's `--write-out` trailer (the status code on its own last line)
+/// from the response body.
+fn split_status(stdout: &str) -> Result<HttpResponse, String> {
+    let (body, status) = stdout
+        .rsplit_once('\n')
+        .ok_or_else(|| "Malformed curl output: missing status code".to_string())?;
+    let status = status
+        .trim()
+        .parse()
+        .map_err(|_| format!("Malformed curl output: bad status code '{}'", status.trim()))?;
+    Ok(HttpResponse {
+        status,
+        body: body.to_string(),
+    })
+}
+
+/// Extracts `(owner, repo, number)` from a pull request URL such as
+/// `https://github.com/owner/repo/pull/123` (trailing `/files` etc. is ignored).
+pub fn parse_github_pr_url(url: &str) -> Result<(String, String, u64), String> {
+    let invalid = |reason: &str| {
+        format!(
+            "Invalid pull request URL '{}': {reason}. Expected format: https://github.com/owner/repo/pull/123",
+            url
+        )
+    };
+
+    let rest = url
+        .trim()
+        .strip_prefix("https://")
+        .or_else(|| url.trim().strip_prefix("http://"))
+        .unwrap_or(url.trim());
+    let rest = rest
+        .strip_prefix("github.com/")
+        .or_else(|| rest.strip_prefix("www.github.com/"))
+        .ok_or_else(|| invalid("not a github.com URL"))?;
+
+    let parts: Vec<&str> = rest.split('/').collect();
+    match parts.as_slice() {
+        [owner, repo, "pull", number, ..] if !owner.is_empty() && !repo.is_empty() => {
+            let number = number
+                .split(['?', '#'])
+                .next()
+                .unwrap_or_default()
+                .parse()
+                .map_err(|_| invalid("pull request number is not a number"))?;
+            Ok((owner.to_string(), repo.to_string(), number))
+        }
+        _ => Err(invalid("missing owner, repo or pull number")),
+    }
+}
+
+fn github_status_error(status: u16, owner: &str, repo: &str, number: u64, body: &str) -> String {
+    match status {
+        404 => format!(
+            "Pull request {owner}/{repo}#{number} not found (404). Private repositories need a token."
+        ),
+        403 => format!(
+            "GitHub denied access to {owner}/{repo}#{number} (403): the rate limit was hit or the token lacks permission."
+        ),
+        401 => "GitHub rejected the token (401): check that it is valid and not expired.".to_string(),
+        _ => format!(
+            "GitHub API returned HTTP {status} for {owner}/{repo}#{number}: {}",
+            body.lines().next().unwrap_or_default()
+        ),
+    }
+}
+
+/// Fetches a pull request as unified diff text, ready for `parse_unified_diff`.
+pub fn fetch_github_pr(
+    owner: &str,
+    repo: &str,
+    number: u64,
+    token: Option<&str>,
+) -> Result<String, String> {
+    let url = format!("https://api.github.com/repos/{owner}/{repo}/pulls/{number}");
+    let mut headers = vec![
+        "Accept: application/vnd.github.v3.diff".to_string(),
+        "User-Agent: great-review".to_string(),
+    ];
+    if let Some(token) = token.filter(|t| !t.is_empty()) {
+        headers.push(format!("Authorization: Bearer {token}"));
+    }
+
+    // curl drops `Authorization` when a redirect leaves api.github.com
+    let response = http_get(&url, &headers, true)?;
+    if response.status != 200 {
+        return Err(github_status_error(
+            response.status,
+            owner,
+            repo,
+            number,
+            &response.body,
+        ));
+    }
+    Ok(response.body)
+}
+
+/// Page size for the merge request diffs API (GitLab caps it at 100).
+const GITLAB_PAGE_SIZE: usize = 100;
+
+/// Extracts `(base_url, project_path, iid)` from a merge request URL such as
+/// `https://gitlab.example.com/group/sub/project/-/merge_requests/12`. The project
+/// path is returned URL-encoded, which the API accepts in place of a numeric id.
+pub fn parse_gitlab_mr_url(url: &str) -> Result<(String, String, u64), String> {
+    let invalid = |reason: &str| {
+        format!(
+            "Invalid merge request URL '{}': {reason}. Expected format: https://gitlab.com/group/project/-/merge_requests/12",
+            url
+        )
+    };
+
+    let trimmed = url.trim();
+    let (scheme, rest) = trimmed
+        .split_once("://")
+        .ok_or_else(|| invalid("missing http:// or https://"))?;
+    let (host, path) = rest
+        .split_once('/')
+        .ok_or_else(|| invalid("missing project path"))?;
+    let (project, tail) = path
+        .split_once("/-/merge_requests/")
+        .ok_or_else(|| invalid("missing /-/merge_requests/<iid>"))?;
+    if host.is_empty() || project.is_empty() {
+        return Err(invalid("missing host or project path"));
+    }
+
+    let iid = tail
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default()
+        .parse()
+        .map_err(|_| invalid("merge request iid is not a number"))?;
+    Ok((
+        format!("{scheme}://{host}"),
+        project.replace('/', "%2F"),
+        iid,
+    ))
+}
+
+/// One entry of GitLab's `/merge_requests/:iid/diffs` response.
+#[derive(Deserialize)]
+struct GitlabDiff {
+    old_path: String,
+    new_path: String,
+    a_mode: String,
+    b_mode: String,
+    diff: String,
+    new_file: bool,
+    renamed_file: bool,
+    deleted_file: bool,
+}
+
+/// GitLab returns only the hunks of each file; rebuild the `diff --git` headers so
+/// the result parses like `git diff` output.
+fn gitlab_diff_to_unified(entry: &GitlabDiff) -> String {
+    let mut out = format!("diff --git a/{} b/{}\n", entry.old_path, entry.new_path);
+    if entry.new_file {
+        out.push_str(&format!("new file mode {}\n", entry.b_mode));
+    } else if entry.deleted_file {
+        out.push_str(&format!("deleted file mode {}\n", entry.a_mode));
+    } else if entry.a_mode != entry.b_mode {
+        out.push_str(&format!(
+            "old mode {}\nnew mode {}\n",
+            entry.a_mode, entry.b_mode
+        ));
+    }
+    if entry.renamed_file {
+        out.push_str(&format!(
+            "rename from {}\nrename to {}\n",
+            entry.old_path, entry.new_path
+        ));
+    }
+    if !entry.diff.is_empty() {
+        let old = if entry.new_file {
+            "/dev/null".to_string()
+        } else {
+            format!("a/{}", entry.old_path)
+        };
+        let new = if entry.deleted_file {
+            "/dev/null".to_string()
+        } else {
+            format!("b/{}", entry.new_path)
+        };
+        out.push_str(&format!("--- {old}\n+++ {new}\n"));
+        out.push_str(&entry.diff);
+        if !entry.diff.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn gitlab_status_error(status: u16, project: &str, iid: u64, body: &str) -> String {
+    match status {
+        404 => {
+            format!("Merge request {project}!{iid} not found (404). Private projects need a token.")
+        }
+        403 => format!(
+            "GitLab denied access to {project}!{iid} (403): the token lacks the read_api scope."
+        ),
+        401 => {
+            "GitLab rejected the token (401): check that it is valid and not expired.".to_string()
+        }
+        _ => format!(
+            "GitLab API returned HTTP {status} for {project}!{iid}: {}",
+            body.lines().next().unwrap_or_default()
+        ),
+    }
+}
+
+/// Fetches a merge request as unified diff text, walking every page of the diffs API.
+/// `base_url` defaults to `https://gitlab.com`; pass the instance root for self-hosted.
+pub fn fetch_gitlab_mr(
+    project_id: &str,
+    iid: u64,
+    token: Option<&str>,
+    base_url: Option<&str>,
+) -> Result<String, String> {
+    let base_url = base_url
+        .filter(|b| !b.is_empty())
+        .unwrap_or("https://gitlab.com")
+        .trim_end_matches('/');
+    let mut headers = vec!["User-Agent: great-review".to_string()];
+    if let Some(token) = token.filter(|t| !t.is_empty()) {
+        if !base_url.starts_with("https://") {
+            return Err(format!(
+                "Refusing to send a GitLab token to {base_url}: the instance must use https"
+            ));
+        }
+        headers.push(format!("PRIVATE-TOKEN: {token}"));
+    }
+
+    let mut diff_text = String::new();
+    let mut page = 1;
+    loop {
+        let url = format!(
+            "{base_url}/api/v4/projects/{project_id}/merge_requests/{iid}/diffs?page={page}&per_page={GITLAB_PAGE_SIZE}"
+        );
+        let response = http_get(&url, &headers, false)?;
+        if response.status != 200 {
+            return Err(gitlab_status_error(
+                response.status,
+                project_id,
+                iid,
+                &response.body,
+            ));
+        }
+
+        let entries: Vec<GitlabDiff> = serde_json::from_str(&response.body)
+            .map_err(|e| format!("Unexpected GitLab response for {project_id}!{iid}: {e}"))?;
+        for entry in &entries {
+            diff_text.push_str(&gitlab_diff_to_unified(entry));
+        }
+        if entries.len() < GITLAB_PAGE_SIZE {
+            return Ok(diff_text);
+        }
+        page += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_github_pr_url() {
+        assert_eq!(
+            parse_github_pr_url("https://github.com/pmatos/great-review/pull/42").unwrap(),
+            ("pmatos".to_string(), "great-review".to_string(), 42)
+        );
+        assert_eq!(
+            parse_github_pr_url("github.com/o/r/pull/7/files#diff-1").unwrap(),
+            ("o".to_string(), "r".to_string(), 7)
+        );
+    }
+
+    #[test]
+    fn test_parse_github_pr_url_errors() {
+        let err = parse_github_pr_url("https://gitlab.com/o/r/pull/1").unwrap_err();
+        assert!(err.contains("not a github.com URL"), "got: {err}");
+
+        let err = parse_github_pr_url("https://github.com/o/r/issues/1").unwrap_err();
+        assert!(
+            err.contains("missing owner, repo or pull number"),
+            "got: {err}"
+        );
+
+        let err = parse_github_pr_url("https://github.com/o/r/pull/abc").unwrap_err();
+        assert!(err.contains("not a number"), "got: {err}");
+    }
+
+    #[test]
+    fn test_github_status_errors_are_distinct() {
+        let not_found = github_status_error(404, "o", "r", 1, "");
+        let forbidden = github_status_error(403, "o", "r", 1, "");
+        assert!(not_found.contains("not found (404)"));
+        assert!(forbidden.contains("(403)"));
+        assert_ne!(not_found, forbidden);
+    }
+
+    #[test]
+    fn test_split_status() {
+        let response = split_status("diff --git a/x b/x\n\n404").unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, "diff --git a/x b/x\n");
+
+        assert!(split_status("no trailer").is_err());
+    }
+
+    #[test]
+    fn test_curl_config_header() {
+        assert_eq!(
+            curl_config_header(r#"Authorization: Bearer a"b\c"#).unwrap(),
+            "header = \"Authorization: Bearer a\\\"b\\\\c\"\n"
+        );
+        assert!(curl_config_header("X-Token: a\noutput = \"/tmp/x\"").is_err());
+        assert!(curl_config_header("X-Token: a\rb").is_err());
+        assert!(curl_config_header("X-Token: a\0b").is_err());
+    }
+
+    #[test]
+    fn test_parse_gitlab_mr_url() {
+        assert_eq!(
+            parse_gitlab_mr_url(
+                "https://gitlab.example.com/group/sub/project/-/merge_requests/12/diffs"
+            )
+            .unwrap(),
+            (
+                "https://gitlab.example.com".to_string(),
+                "group%2Fsub%2Fproject".to_string(),
+                12
+            )
+        );
+
+        let err = parse_gitlab_mr_url("https://gitlab.com/group/project/pull/1").unwrap_err();
+        assert!(err.contains("missing /-/merge_requests/"), "got: {err}");
+    }
+
+    #[test]
+    fn test_fetch_gitlab_mr_refuses_token_over_http() {
+        let err = fetch_gitlab_mr(
+            "group%2Fproject",
+            1,
+            Some("secret"),
+            Some("http://gitlab.lan"),
+        )
+        .unwrap_err();
+        assert!(err.contains("must use https"), "got: {err}");
+        assert!(!err.contains("secret"), "got: {err}");
+    }
+
+    #[test]
+    fn test_gitlab_diffs_to_unified() {
+        let body = r#"[
+            {"old_path": "src/lib.rs", "new_path": "src/lib.rs", "a_mode": "100644",
+             "b_mode": "100644", "diff": "@@ -1 +1 @@\n-old\n+new\n",
+             "new_file": false, "renamed_file": false, "deleted_file": false},
+            {"old_path": "added.txt", "new_path": "added.txt", "a_mode": "0",
+             "b_mode": "100644", "diff": "@@ -0,0 +1 @@\n+hello\n",
+             "new_file": true, "renamed_file": false, "deleted_file": false},
+            {"old_path": "before.txt", "new_path": "after.txt", "a_mode": "100644",
+             "b_mode": "100644", "diff": "",
+             "new_file": false, "renamed_file": true, "deleted_file": false}
+        ]"#;
+        let entries: Vec<GitlabDiff> = serde_json::from_str(body).unwrap();
+        let text: String = entries.iter().map(gitlab_diff_to_unified).collect();
+
+        let files = crate::diff_parser::parse_unified_diff(&text);
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(files[0].status, crate::diff_parser::FileStatus::Modified);
+        assert_eq!(files[0].hunks[0].lines.len(), 2);
+        assert_eq!(files[1].status, crate::diff_parser::FileStatus::Added);
+        assert_eq!(files[2].status, crate::diff_parser::FileStatus::Renamed);
+        assert_eq!(files[2].old_path.as_deref(), Some("before.txt"));
+    }
+}
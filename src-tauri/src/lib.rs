@@ -1,6 +1,24 @@
+use tauri::Manager;
+
 mod commands;
+pub mod comments;
+pub mod config;
+pub mod conflicts;
+pub mod coverage;
 pub mod diff_parser;
+pub mod editor;
+pub mod export;
+pub mod file_tree;
+pub mod forge;
+pub mod outline;
 pub mod repo_info;
+pub mod review_ignore;
+pub mod search;
+pub mod side_by_side;
+pub mod staging;
+pub mod stats;
+#[cfg(test)]
+mod test_support;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -8,9 +26,61 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_cli::init())
+        .manage(commands::DiffCache::default())
+        .manage(commands::DiffJobs::default())
+        .setup(|app| {
+            app.manage(commands::ssh_options_from_cli(app.handle()));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             commands::get_diff,
+            commands::get_diff_cancellable,
+            commands::cancel_diff,
+            commands::get_diff_stream,
+            commands::get_changed_files,
+            commands::get_diff_outline,
+            commands::get_file_diff,
+            commands::clear_diff_cache,
             commands::get_repo_info_cmd,
+            commands::get_remote_bundle,
+            commands::get_multi_diff,
+            commands::get_merge_base,
+            commands::get_blob_content,
+            commands::get_refs,
+            commands::get_remotes,
+            commands::normalize_range,
+            commands::resolve_ref,
+            commands::get_default_branch,
+            commands::get_commit_message,
+            commands::count_commits,
+            commands::get_tip_author,
+            commands::list_stashes,
+            commands::get_conflicts,
+            commands::get_stash_diff,
+            commands::get_branch_diff,
+            commands::diff_files,
+            commands::apply_patch,
+            commands::search_in_diff,
+            commands::find_trailing_whitespace,
+            commands::copy_hunk,
+            commands::apply_selected_hunks,
+            commands::open_in_editor,
+            commands::export_review_markdown,
+            commands::export_review_html,
+            commands::get_diff_summary,
+            commands::get_stats_by_extension,
+            commands::get_dominant_language,
+            commands::get_change_density,
+            commands::annotate_coverage,
+            commands::get_file_tree,
+            commands::get_side_by_side,
+            commands::get_github_pr_diff,
+            commands::get_gitlab_mr_diff,
+            commands::save_review,
+            commands::load_review,
+            commands::reanchor_review,
+            commands::mark_file_viewed,
+            commands::load_viewed_files,
             commands::get_startup_args,
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use crate::diff_parser::{DiffFile, DiffHunk, FileStatus};
+
+/// A hunk reduced to what an outline needs to jump to it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HunkOutline {
+    pub header: String,
+    /// Text after the closing `@@` (usually the enclosing function), if git found one.
+    pub section: Option<String>,
+    pub old_start: u32,
+    pub new_start: u32,
+}
+
+/// A file and its hunk headers, without any line content.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FileOutline {
+    pub path: String,
+    pub status: FileStatus,
+    pub hunks: Vec<HunkOutline>,
+}
+
+/// The section name git appends to a hunk header (`@@ -1,2 +1,2 @@ fn main()`).
+fn hunk_section(header: &str) -> Option<String> {
+    let rest = header.strip_prefix("@@ ")?;
+    let (_, section) = rest.split_once(" @@")?;
+    let section = section.trim();
+    (!section.is_empty()).then(|| section.to_string())
+}
+
+fn hunk_outline(hunk: &DiffHunk) -> HunkOutline {
+    HunkOutline {
+        header: hunk.header.clone(),
+        section: hunk_section(&hunk.header),
+        old_start: hunk.old_start,
+        new_start: hunk.new_start,
+    }
+}
+
+/// Strips `files` down to a table of contents of their hunks.
+pub fn outline(files: &[DiffFile]) -> Vec<FileOutline> {
+    files
+        .iter()
+        .map(|file| FileOutline {
+            path: file.path.clone(),
+            status: file.status.clone(),
+            hunks: file.hunks.iter().map(hunk_outline).collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::parse_unified_diff;
+
+    #[test]
+    fn test_outline_keeps_headers_and_sections() {
+        let files = parse_unified_diff(
+            "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+-a
++b
+ c
+@@ -10,2 +10,3 @@ fn parse() {
+ x
++y
+ z
+",
+        );
+        let outlines = outline(&files);
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(outlines[0].path, "src/lib.rs");
+        assert_eq!(outlines[0].status, FileStatus::Modified);
+
+        let hunks = &outlines[0].hunks;
+        assert_eq!(hunks[0].section, None);
+        assert_eq!(hunks[0].old_start, 1);
+        assert_eq!(hunks[1].header, "@@ -10,2 +10,3 @@ fn parse() {");
+        assert_eq!(hunks[1].section.as_deref(), Some("fn parse() {"));
+        assert_eq!(hunks[1].new_start, 10);
+    }
+}
@@ -1,99 +1,1298 @@
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::process::{Output, Stdio};
+use std::time::Duration;
+
+use crate::diff_parser::{check_repo_path, git_command, ssh_command, DiffOptions, SshOptions};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RepoInfo {
     pub name: String,
-    pub branch: String,
-    pub path: String,
+    /// The branch `HEAD` is on, even before its first commit; `HEAD` when detached.
+    pub branch: String,
+    /// `None` when the branch has no commits yet.
+    pub head_sha: Option<String>,
+    /// Tracked files have uncommitted changes. Only checked for remote repositories,
+    /// where a dirty checkout is easy to miss; `None` elsewhere.
+    pub dirty: Option<bool>,
+    pub path: String,
+    pub bare: bool,
+    /// `core.sparseCheckout` is enabled, so the working tree holds only part of the repo.
+    pub sparse: bool,
+    /// Patterns from `info/sparse-checkout`; empty unless `sparse` is set.
+    pub sparse_patterns: Vec<String>,
+    /// Explanation to show next to working-tree diffs, which cannot include files
+    /// outside the sparse patterns. Range diffs between commits are unaffected.
+    pub working_tree_warning: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Refs {
+    pub branches: Vec<String>,
+    pub tags: Vec<String>,
+    pub current: String,
+}
+
+/// Where local git commands for a repository should run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RepoRoot {
+    /// Working tree root, or the git directory itself for bare repositories.
+    pub path: String,
+    pub bare: bool,
+}
+
+/// Whether both ends of `range` are commits (`a..b`, `a...b`, `rev^!`). A single
+/// revision such as `HEAD~3` is diffed against the working tree instead.
+pub fn is_commit_range(range: &str) -> bool {
+    range.contains("..") || range.ends_with("^!")
+}
+
+impl RepoRoot {
+    /// Bare repositories have no working tree, so only commit ranges can be diffed: a
+    /// missing range or a single revision (e.g. `HEAD~3`) would compare against one.
+    pub fn check_diff_range(&self, range: Option<&str>) -> Result<(), String> {
+        if self.bare && !range.is_some_and(is_commit_range) {
+            Err(format!(
+                "'{}' is a bare repository with no working tree; pass a commit range to review instead",
+                self.path
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+const BARE_PROBE_ARGS: [&str; 3] = ["rev-parse", "--is-bare-repository", "--absolute-git-dir"];
+
+/// Names the current branch before its first commit, when `rev-parse --abbrev-ref HEAD`
+/// fails because `HEAD` does not resolve yet.
+const UNBORN_BRANCH_ARGS: [&str; 3] = ["symbolic-ref", "--short", "HEAD"];
+
+/// Prints the `HEAD` commit, or nothing (exiting 1) when there is none yet.
+const HEAD_SHA_ARGS: [&str; 4] = ["rev-parse", "--verify", "--quiet", "HEAD^{commit}"];
+
+/// Returns the absolute git directory if `output` (from `BARE_PROBE_ARGS`) reports a bare repo.
+fn bare_git_dir(output: &Output) -> Option<String> {
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    match (lines.next(), lines.next()) {
+        (Some("true"), Some(dir)) => Some(dir.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Resolves the repository to run git in: the current directory's repository, or the
+/// explicit `git_dir` (as in `GIT_DIR`) when given. Bare repositories are detected
+/// when `--show-toplevel` fails because there is no working tree.
+pub fn locate_repo(git_dir: Option<&str>) -> Result<RepoRoot, String> {
+    let Some(git_dir) = git_dir else {
+        let toplevel_err = match find_repo_root() {
+            Ok(path) => return Ok(RepoRoot { path, bare: false }),
+            Err(e) => e,
+        };
+        let probe = git_command()
+            .args(BARE_PROBE_ARGS)
+            .output()
+            .map_err(|e| format!("Failed to run git: {e}"))?;
+        return match bare_git_dir(&probe) {
+            Some(path) => Ok(RepoRoot { path, bare: true }),
+            None => Err(toplevel_err),
+        };
+    };
+
+    let probe = git_command()
+        .args(["--git-dir", git_dir])
+        .args(BARE_PROBE_ARGS)
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+
+    if !probe.status.success() {
+        return Err(format!(
+            "'{}' is not a git directory: {}",
+            git_dir,
+            String::from_utf8_lossy(&probe.stderr).trim()
+        ));
+    }
+    if let Some(path) = bare_git_dir(&probe) {
+        return Ok(RepoRoot { path, bare: true });
+    }
+
+    // A non-bare git dir: its work tree is resolved relative to the directory containing it
+    let abs_git_dir = String::from_utf8_lossy(&probe.stdout)
+        .lines()
+        .nth(1)
+        .unwrap_or(git_dir)
+        .trim()
+        .to_string();
+    let parent = std::path::Path::new(&abs_git_dir)
+        .parent()
+        .ok_or_else(|| format!("'{}' has no parent directory", abs_git_dir))?;
+    let output = git_command()
+        .args(["--git-dir", &abs_git_dir, "rev-parse", "--show-toplevel"])
+        .current_dir(parent)
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to find the working tree for '{}': {}",
+            git_dir,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(RepoRoot {
+        path: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        bare: false,
+    })
+}
+
+pub fn get_repo_info(repo_path: &str) -> Result<RepoInfo, String> {
+    check_repo_path(repo_path)?;
+
+    let root_output = git_command()
+        .args(["-C", repo_path, "rev-parse", "--show-toplevel"])
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+
+    let bare_output = if root_output.status.success() {
+        None
+    } else {
+        let output = git_command()
+            .args(["-C", repo_path])
+            .args(BARE_PROBE_ARGS)
+            .output()
+            .map_err(|e| format!("Failed to run git: {e}"))?;
+        Some(output)
+    };
+
+    let branch_output = git_command()
+        .args(["-C", repo_path, "rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .map_err(|e| format!("Failed to get branch: {e}"))?;
+    let unborn_output = if branch_output.status.success() {
+        None
+    } else {
+        let output = git_command()
+            .args(["-C", repo_path])
+            .args(UNBORN_BRANCH_ARGS)
+            .output()
+            .map_err(|e| format!("Failed to get branch: {e}"))?;
+        Some(output)
+    };
+    let head_output = git_command()
+        .args(["-C", repo_path])
+        .args(HEAD_SHA_ARGS)
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+
+    let mut info = repo_info_from_outputs(
+        &root_output,
+        bare_output.as_ref(),
+        &branch_output,
+        unborn_output.as_ref(),
+        &head_output,
+    )?;
+    if !info.bare {
+        let config_output = git_command()
+            .args(["-C", repo_path])
+            .args(SPARSE_CONFIG_ARGS)
+            .output()
+            .map_err(|e| format!("Failed to run git: {e}"))?;
+        let path_output = git_command()
+            .args(["-C", repo_path])
+            .args(SPARSE_FILE_ARGS)
+            .output()
+            .map_err(|e| format!("Failed to run git: {e}"))?;
+        set_sparse(&mut info, repo_path, &config_output, &path_output);
+    }
+    Ok(info)
+}
+
+const SPARSE_CONFIG_ARGS: [&str; 3] = ["config", "--bool", "core.sparseCheckout"];
+const SPARSE_FILE_ARGS: [&str; 3] = ["rev-parse", "--git-path", "info/sparse-checkout"];
+
+/// Fills in the sparse-checkout fields of `info` from `git config core.sparseCheckout`
+/// and the pattern file named by `git rev-parse --git-path`.
+fn set_sparse(info: &mut RepoInfo, repo_path: &str, config_output: &Output, path_output: &Output) {
+    if String::from_utf8_lossy(&config_output.stdout).trim() != "true" {
+        return;
+    }
+    // The pattern path is relative to the directory git ran in
+    let pattern_file =
+        std::path::Path::new(repo_path).join(String::from_utf8_lossy(&path_output.stdout).trim());
+    let patterns: Vec<String> = std::fs::read_to_string(pattern_file)
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+
+    info.working_tree_warning = Some(if patterns.is_empty() {
+        "Sparse checkout is active: files outside the checked-out paths are not shown in working-tree diffs".to_string()
+    } else {
+        format!(
+            "Sparse checkout is active: working-tree diffs only cover paths matching {}",
+            patterns.join(", ")
+        )
+    });
+    info.sparse = true;
+    info.sparse_patterns = patterns;
+}
+
+fn repo_info_from_outputs(
+    root_output: &Output,
+    bare_output: Option<&Output>,
+    branch_output: &Output,
+    unborn_output: Option<&Output>,
+    head_output: &Output,
+) -> Result<RepoInfo, String> {
+    let (root_path, bare) = if root_output.status.success() {
+        let root_path = String::from_utf8_lossy(&root_output.stdout)
+            .trim()
+            .to_string();
+        (root_path, false)
+    } else if let Some(git_dir) = bare_output.and_then(bare_git_dir) {
+        (git_dir, true)
+    } else {
+        return Err(format!(
+            "Not a git repository: {}",
+            String::from_utf8_lossy(&root_output.stderr).trim()
+        ));
+    };
+
+    let name = std::path::Path::new(&root_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| root_path.clone());
+    // Bare repositories are conventionally named `project.git`
+    let name = match name.strip_suffix(".git") {
+        Some(stripped) if bare && !stripped.is_empty() => stripped.to_string(),
+        _ => name,
+    };
+
+    let branch_output = match unborn_output {
+        Some(output) if output.status.success() => output,
+        _ => branch_output,
+    };
+    if !branch_output.status.success() {
+        return Err(format!(
+            "Failed to get current branch: {}",
+            String::from_utf8_lossy(&branch_output.stderr).trim()
+        ));
+    }
+
+    let branch = String::from_utf8_lossy(&branch_output.stdout)
+        .trim()
+        .to_string();
+    let head_sha = head_output.status.success().then(|| {
+        String::from_utf8_lossy(&head_output.stdout)
+            .trim()
+            .to_string()
+    });
+
+    Ok(RepoInfo {
+        name,
+        branch,
+        head_sha,
+        dirty: None,
+        path: root_path,
+        bare,
+        sparse: false,
+        sparse_patterns: Vec::new(),
+        working_tree_warning: None,
+    })
+}
+
+/// Separates the sections of `remote_info_command` output.
+const REMOTE_INFO_SEPARATOR: &str = "--great-review-info--";
+
+/// Shell command printing a remote repository's root and branch, then after a
+/// `REMOTE_INFO_SEPARATOR` line its `HEAD` sha (nothing before the first commit),
+/// then after another the tracked files with uncommitted changes. Untracked files are
+/// left out, since they do not affect a range.
+fn remote_info_command(path: &str) -> String {
+    format!(
+        "cd {} && git rev-parse --show-toplevel \
+         && {{ git symbolic-ref -q --short HEAD || git rev-parse --abbrev-ref HEAD; }} \
+         && echo '{sep}' && {{ git rev-parse -q --verify 'HEAD^{{commit}}' || true; }} \
+         && echo '{sep}' && git status --porcelain --untracked-files=no",
+        crate::diff_parser::shell_quote(path),
+        sep = REMOTE_INFO_SEPARATOR
+    )
+}
+
+pub fn get_remote_repo_info(remote: &str, ssh: &SshOptions) -> Result<RepoInfo, String> {
+    let (host, path) = crate::diff_parser::parse_remote_path(remote)?;
+
+    let cmd = remote_info_command(path);
+
+    let output = crate::diff_parser::ssh_output_with_retry(host, &cmd, ssh)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("Failed to get remote repo info: {}", stderr.trim()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    Ok(remote_repo_info_from_stdout(remote, &stdout))
+}
+
+/// Parses `remote_info_command` output. Missing sections leave `head_sha` and `dirty`
+/// unset.
+fn remote_repo_info_from_stdout(remote: &str, stdout: &str) -> RepoInfo {
+    let separator = format!("{}\n", REMOTE_INFO_SEPARATOR);
+    let mut sections = stdout.split(separator.as_str());
+    let mut lines = sections.next().unwrap_or_default().trim().lines();
+    let root_path = lines.next().unwrap_or("").to_string();
+    let branch = lines.next().unwrap_or("").to_string();
+    let head_sha = sections
+        .next()
+        .map(str::trim)
+        .filter(|sha| !sha.is_empty())
+        .map(str::to_string);
+    let dirty = sections.next().map(|status| !status.trim().is_empty());
+
+    let name = std::path::Path::new(&root_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| root_path.clone());
+
+    RepoInfo {
+        name,
+        branch,
+        head_sha,
+        dirty,
+        path: remote.to_string(),
+        bare: false,
+        sparse: false,
+        sparse_patterns: Vec::new(),
+        working_tree_warning: None,
+    }
+}
+
+/// Separates the repo-info section from the diff section in `get_remote_bundle` output.
+const REMOTE_BUNDLE_MARKER: &str = "--great-review-bundle-diff--";
+
+/// Fetches repo info and the diff over a single ssh session, halving round-trips
+/// compared to calling `get_remote_repo_info` and `run_remote_git_diff` separately.
+/// The diff is the one `run_remote_git_diff` would take with `options`. Returns the
+/// repo info and the raw diff text.
+pub fn get_remote_bundle(
+    remote: &str,
+    options: &DiffOptions,
+    ssh: &SshOptions,
+) -> Result<(RepoInfo, String), String> {
+    let (host, path) = crate::diff_parser::parse_remote_path(remote)?;
+    crate::diff_parser::check_remote_target(options)?;
+
+    let cmd = format!(
+        "{} && echo '{}' && {}",
+        remote_info_command(path),
+        REMOTE_BUNDLE_MARKER,
+        crate::diff_parser::remote_diff_command(path, options)
+    );
+
+    let output = crate::diff_parser::ssh_output_with_retry(host, &cmd, ssh)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(crate::diff_parser::describe_remote_error(
+            &stderr, host, path,
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let (info_section, diff_text) = split_bundle_output(&stdout)?;
+    Ok((
+        remote_repo_info_from_stdout(remote, info_section),
+        diff_text.to_string(),
+    ))
+}
+
+fn split_bundle_output(stdout: &str) -> Result<(&str, &str), String> {
+    let marker_line = format!("{}\n", REMOTE_BUNDLE_MARKER);
+    let pos = stdout
+        .find(&marker_line)
+        .ok_or_else(|| "Malformed remote output: diff marker not found".to_string())?;
+    Ok((&stdout[..pos], &stdout[pos + marker_line.len()..]))
+}
+
+/// One side of a range: the ref as the user wrote it and the commit it resolves to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RangeEndpoint {
+    pub ref_name: String,
+    pub sha: String,
+}
+
+/// A range with both endpoints resolved. `to` is `None` for a single revision, which
+/// git diffs against the working tree.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NormalizedRange {
+    pub range: String,
+    pub from: RangeEndpoint,
+    pub to: Option<RangeEndpoint>,
+    /// `a...b`: the diff starts at the merge base of `from` and `to`.
+    pub symmetric: bool,
+}
+
+/// Splits a range into `(from, to, symmetric)` the way `git diff` reads it: an empty
+/// side of `..`/`...` means `HEAD`, and `rev^!` compares `rev` to its first parent.
+fn split_range(range: &str) -> (String, Option<String>, bool) {
+    let or_head = |side: &str| {
+        if side.is_empty() {
+            "HEAD".to_string()
+        } else {
+            side.to_string()
+        }
+    };
+    if let Some((a, b)) = range.split_once("...") {
+        (or_head(a), Some(or_head(b)), true)
+    } else if let Some((a, b)) = range.split_once("..") {
+        (or_head(a), Some(or_head(b)), false)
+    } else if let Some(rev) = range.strip_suffix("^!") {
+        (format!("{}^", rev), Some(rev.to_string()), false)
+    } else {
+        (range.to_string(), None, false)
+    }
+}
+
+fn resolve_commit(rev: &str, range: &str, repo_path: &str) -> Result<String, String> {
+    resolve_ref(rev, repo_path).map_err(|e| format!("{} in range '{}'", e, range))
+}
+
+/// Resolves both endpoints of `range` (branches, tags, reflog entries such as
+/// `HEAD@{1}`, ...) to commit shas, so typos fail before any diff runs and the UI can
+/// show what each side points at.
+pub fn normalize_range(range: &str, repo_path: &str) -> Result<NormalizedRange, String> {
+    check_repo_path(repo_path)?;
+
+    let trimmed = range.trim();
+    if trimmed.is_empty() {
+        return Err("Range is empty".to_string());
+    }
+    let (from, to, symmetric) = split_range(trimmed);
+
+    let from = RangeEndpoint {
+        sha: resolve_commit(&from, trimmed, repo_path)?,
+        ref_name: from,
+    };
+    let to = match to {
+        Some(to) => Some(RangeEndpoint {
+            sha: resolve_commit(&to, trimmed, repo_path)?,
+            ref_name: to,
+        }),
+        None => None,
+    };
+    Ok(NormalizedRange {
+        range: trimmed.to_string(),
+        from,
+        to,
+        symmetric,
+    })
+}
+
+/// Interprets `git rev-parse --verify <ref>^{commit}` output. git only warns about a
+/// refname that is both a branch and a tag, so that case is rejected here too.
+fn rev_parse_result(ref_str: &str, output: &Output) -> Result<String, String> {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("is ambiguous") {
+        return Err(format!("Ambiguous revision '{}'", ref_str));
+    }
+    let sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || sha.is_empty() {
+        return Err(format!("Unknown revision '{}'", ref_str));
+    }
+    Ok(sha)
+}
+
+fn rev_parse_args(ref_str: &str) -> Vec<String> {
+    vec![
+        "rev-parse".to_string(),
+        "--verify".to_string(),
+        "--end-of-options".to_string(),
+        format!("{}^{{commit}}", ref_str),
+    ]
+}
+
+/// Resolves `ref_str` (branch, tag, short sha, `HEAD~2`, ...) to the full sha of the
+/// commit it names.
+pub fn resolve_ref(ref_str: &str, repo_path: &str) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path])
+        .args(rev_parse_args(ref_str))
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    rev_parse_result(ref_str, &output)
+}
+
+/// `resolve_ref` over ssh.
+pub fn resolve_remote_ref(remote: &str, ref_str: &str, ssh: &SshOptions) -> Result<String, String> {
+    let (host, path) = crate::diff_parser::parse_remote_path(remote)?;
+
+    let argv: Vec<String> = rev_parse_args(ref_str)
+        .iter()
+        .map(|a| crate::diff_parser::shell_quote(a))
+        .collect();
+    let cmd = format!(
+        "cd {} && git {}",
+        crate::diff_parser::shell_quote(path),
+        argv.join(" ")
+    );
+
+    let output = ssh_command(host, &cmd, ssh)
+        .output()
+        .map_err(|e| format!("Failed to execute ssh: {}", e))?;
+
+    // 255 is ssh's own failure; anything else came from the remote shell or git
+    if output.status.code() == Some(255) {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(crate::diff_parser::describe_remote_error(
+            &stderr, host, path,
+        ));
+    }
+    rev_parse_result(ref_str, &output)
+}
+
+/// Lists origin's `HEAD` (with the branch it points to) and every local branch, marking
+/// the current one: enough to pick a default branch from one git invocation.
+const DEFAULT_BRANCH_ARGS: [&str; 5] = [
+    "for-each-ref",
+    "--format=%(HEAD) %(refname) %(symref)",
+    "--",
+    "refs/remotes/origin/HEAD",
+    "refs/heads/",
+];
+
+/// The branch `refs/remotes/origin/HEAD` points to, else `main` or `master` if either
+/// exists locally, else the current branch.
+fn parse_default_branch(stdout: &str) -> Result<String, String> {
+    let refs: Vec<(bool, &str, &str)> = stdout
+        .lines()
+        .filter_map(|line| {
+            let (head, rest) = line.split_at_checked(1)?;
+            let mut parts = rest.split_whitespace();
+            let refname = parts.next()?;
+            Some((head == "*", refname, parts.next().unwrap_or_default()))
+        })
+        .collect();
+
+    let origin_head = refs
+        .iter()
+        .find(|(_, refname, _)| *refname == "refs/remotes/origin/HEAD")
+        .and_then(|(_, _, symref)| symref.strip_prefix("refs/remotes/origin/"));
+    let local = |name: &str| {
+        refs.iter()
+            .any(|(_, refname, _)| refname.strip_prefix("refs/heads/") == Some(name))
+            .then(|| name.to_string())
+    };
+    let current = refs
+        .iter()
+        .find(|(is_head, _, _)| *is_head)
+        .and_then(|(_, refname, _)| refname.strip_prefix("refs/heads/"));
+
+    origin_head
+        .map(str::to_string)
+        .or_else(|| local("main"))
+        .or_else(|| local("master"))
+        .or_else(|| current.map(str::to_string))
+        .ok_or_else(|| "Could not determine the default branch".to_string())
+}
+
+pub fn get_default_branch(repo_path: &str) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path])
+        .args(DEFAULT_BRANCH_ARGS)
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to list branches: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_default_branch(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Runs `git <args>` in the repository at `remote` and returns its stdout.
+fn run_remote_git(remote: &str, args: &[String], ssh: &SshOptions) -> Result<String, String> {
+    let (host, path) = crate::diff_parser::parse_remote_path(remote)?;
+
+    let argv: Vec<String> = args
+        .iter()
+        .map(|a| crate::diff_parser::shell_quote(a))
+        .collect();
+    let cmd = format!(
+        "cd {} && git {}",
+        crate::diff_parser::shell_quote(path),
+        argv.join(" ")
+    );
+
+    let output = ssh_command(host, &cmd, ssh)
+        .output()
+        .map_err(|e| format!("Failed to execute ssh: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(crate::diff_parser::describe_remote_error(
+            &stderr, host, path,
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `get_default_branch` over ssh.
+pub fn get_remote_default_branch(remote: &str, ssh: &SshOptions) -> Result<String, String> {
+    let args: Vec<String> = DEFAULT_BRANCH_ARGS.iter().map(|a| a.to_string()).collect();
+    parse_default_branch(&run_remote_git(remote, &args, ssh)?)
+}
+
+/// A commit's message split into its subject line and the rest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CommitMessage {
+    pub sha: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// `git log` arguments printing NUL-separated sha, subject and body per commit, each
+/// commit ending in a record separator (`\x1e`).
+///
+/// The tip is the commit a range ends at; a lone revision is diffed against the
+/// working tree, so its tip is `HEAD`. With `whole_range` every commit from the
+/// range's start to the tip is listed, newest first; with `first_parent` as well, only
+/// the tip's first-parent chain is, so a merged branch shows as its merge commit.
+///
+/// `--first-parent` is for the commit list and count alone. A diff compares two
+/// endpoints (`a..b` those two, `a...b` the merge base and `b`), so its patch is the
+/// same with or without it. Both ranges walk `a..b` here: the commits since the merge
+/// base either way.
+fn commit_message_args(range: &str, whole_range: bool, first_parent: bool) -> Vec<String> {
+    let (from, to, _) = split_range(range);
+    let tip = to.unwrap_or_else(|| "HEAD".to_string());
+    let mut args = vec!["log".to_string(), "--format=%H%x00%s%x00%b%x1e".to_string()];
+    if first_parent {
+        args.push("--first-parent".to_string());
+    }
+    let revision = if whole_range {
+        format!("{}..{}", from, tip)
+    } else {
+        args.push("-1".to_string());
+        tip
+    };
+    args.extend(["--end-of-options".to_string(), revision]);
+    args
+}
+
+fn parse_commit_messages(stdout: &str) -> Vec<CommitMessage> {
+    stdout
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').splitn(3, '\0');
+            let sha = fields.next().filter(|sha| !sha.is_empty())?;
+            Some(CommitMessage {
+                sha: sha.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+                body: fields.next().unwrap_or_default().trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Messages of the commits `range` reviews; see `commit_message_args`.
+pub fn get_commit_messages(
+    range: &str,
+    whole_range: bool,
+    first_parent: bool,
+    repo_path: &str,
+) -> Result<Vec<CommitMessage>, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path])
+        .args(commit_message_args(range, whole_range, first_parent))
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read commit messages for '{}': {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_commit_messages(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// `get_commit_messages` over ssh.
+pub fn get_remote_commit_messages(
+    remote: &str,
+    range: &str,
+    whole_range: bool,
+    first_parent: bool,
+    ssh: &SshOptions,
+) -> Result<Vec<CommitMessage>, String> {
+    let args = commit_message_args(range, whole_range, first_parent);
+    Ok(parse_commit_messages(&run_remote_git(remote, &args, ssh)?))
+}
+
+/// `git rev-list` arguments counting the commits `range` reviews: those reachable from
+/// its tip but not from its start. As with `git diff`, a lone revision is compared
+/// with `HEAD`, and `a...b` counts from the merge base like `a..b` does. `first_parent`
+/// counts as `commit_message_args` lists.
+fn commit_count_args(range: &str, first_parent: bool) -> Vec<String> {
+    let (from, to, _) = split_range(range);
+    let to = to.unwrap_or_else(|| "HEAD".to_string());
+    let mut args = vec!["rev-list".to_string(), "--count".to_string()];
+    if first_parent {
+        args.push("--first-parent".to_string());
+    }
+    args.extend(["--end-of-options".to_string(), format!("{}..{}", from, to)]);
+    args
+}
+
+fn parse_commit_count(stdout: &str) -> Result<u32, String> {
+    stdout
+        .trim()
+        .parse()
+        .map_err(|_| format!("Unexpected output from git rev-list: {}", stdout.trim()))
+}
+
+/// Number of commits in `range`, cheap enough to show before the diff has loaded.
+pub fn count_commits(range: &str, first_parent: bool, repo_path: &str) -> Result<u32, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path])
+        .args(commit_count_args(range, first_parent))
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Invalid range '{}': {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_commit_count(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `count_commits` over ssh.
+pub fn count_remote_commits(
+    remote: &str,
+    range: &str,
+    first_parent: bool,
+    ssh: &SshOptions,
+) -> Result<u32, String> {
+    let args = commit_count_args(range, first_parent);
+    parse_commit_count(&run_remote_git(remote, &args, ssh)?)
+}
+
+/// Who wrote a commit, as recorded in it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+}
+
+/// The commit at the top of `range`: its right-hand side, or `HEAD` for a lone
+/// revision, which is diffed against the working tree.
+fn tip_author_args(range: &str) -> Vec<String> {
+    let (_, to, _) = split_range(range);
+    vec![
+        "show".to_string(),
+        "-s".to_string(),
+        "--format=%an%n%ae".to_string(),
+        "--end-of-options".to_string(),
+        to.unwrap_or_else(|| "HEAD".to_string()),
+    ]
+}
+
+fn parse_identity(stdout: &str) -> Result<Identity, String> {
+    let mut lines = stdout.lines();
+    match (lines.next(), lines.next()) {
+        (Some(name), Some(email)) => Ok(Identity {
+            name: name.to_string(),
+            email: email.to_string(),
+        }),
+        _ => Err(format!(
+            "Unexpected output from git show: {}",
+            stdout.trim()
+        )),
+    }
+}
+
+/// Author of the most recent commit in `range`, for when the UI needs no more of the
+/// log than that.
+pub fn get_tip_author(range: &str, repo_path: &str) -> Result<Identity, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path])
+        .args(tip_author_args(range))
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read the tip commit of '{}': {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_identity(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `get_tip_author` over ssh.
+pub fn get_remote_tip_author(
+    remote: &str,
+    range: &str,
+    ssh: &SshOptions,
+) -> Result<Identity, String> {
+    parse_identity(&run_remote_git(remote, &tip_author_args(range), ssh)?)
+}
+
+pub fn get_head_sha(repo_path: &str) -> Result<String, String> {
+    let output = git_command()
+        .args(["-C", repo_path, "rev-parse", "HEAD"])
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to resolve HEAD: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub fn get_remote_head_sha(remote: &str, ssh: &SshOptions) -> Result<String, String> {
+    let (host, path) = crate::diff_parser::parse_remote_path(remote)?;
+
+    let cmd = format!(
+        "cd {} && git rev-parse HEAD",
+        crate::diff_parser::shell_quote(path)
+    );
+
+    let output = ssh_command(host, &cmd, ssh)
+        .output()
+        .map_err(|e| format!("Failed to execute ssh: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("Failed to resolve remote HEAD: {}", stderr.trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the best common ancestor of `a` and `b` (`git merge-base a b`).
+pub fn get_merge_base(a: &str, b: &str, repo_path: &str) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path, "merge-base", "--end-of-options", a, b])
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+
+    let sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !sha.is_empty() {
+        return Ok(sha);
+    }
+
+    // merge-base exits 1 with no output (and no error) when the histories are unrelated
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if output.status.code() == Some(1) && stderr.is_empty() {
+        Err(format!(
+            "'{}' and '{}' have no common ancestor (unrelated histories)",
+            a, b
+        ))
+    } else {
+        Err(format!(
+            "Failed to find merge base of '{}' and '{}': {}",
+            a, b, stderr
+        ))
+    }
+}
+
+/// How much of a blob is checked for a NUL byte, as git does to tell binary from text.
+const BINARY_CHECK_BYTES: usize = 8000;
+
+/// The content of the blob `sha` (any object name git accepts, such as the abbreviated
+/// hashes on a diff's `index` line or `HEAD:path`). Objects that are missing, are not
+/// blobs, or hold binary data are reported as errors.
+pub fn get_blob(sha: &str, repo_path: &str) -> Result<String, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path, "cat-file", "-t", "--end-of-options", sha])
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("No object '{}' in the repository", sha));
+    }
+    let kind = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if kind != "blob" {
+        return Err(format!("'{}' is a {}, not a blob", sha, kind));
+    }
+
+    let output = git_command()
+        .args(["-C", repo_path, "cat-file", "-p", "--end-of-options", sha])
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read blob '{}': {}",
+            sha,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let content = output.stdout;
+    let head = &content[..content.len().min(BINARY_CHECK_BYTES)];
+    if head.contains(&0) {
+        return Err(format!("Blob '{}' is binary", sha));
+    }
+    String::from_utf8(content).map_err(|_| format!("Blob '{}' is binary", sha))
+}
+
+const BRANCH_LIST_ARGS: [&str; 2] = ["branch", "--format=%(refname:short)"];
+
+/// Lists local branches (current branch first) and tags for a comparison-target picker.
+pub fn list_refs(repo_path: &str) -> Result<Refs, String> {
+    check_repo_path(repo_path)?;
+
+    let run = |args: &[&str]| -> Result<String, String> {
+        let output = git_command()
+            .args(["-C", repo_path])
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run git: {e}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to list refs: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    };
+
+    let current = run(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let branches = run(&BRANCH_LIST_ARGS)?;
+    let tags = run(&["tag", "--list"])?;
+    Ok(build_refs(&current, &branches, &tags))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub fetch_url: String,
+    /// Same as `fetch_url` unless a separate push URL is configured.
+    pub push_url: String,
 }
 
-pub fn get_repo_info(repo_path: &str) -> Result<RepoInfo, String> {
-    let root_output = Command::new("git")
-        .args(["-C", repo_path, "rev-parse", "--show-toplevel"])
+/// Folds the `(fetch)` and `(push)` lines `git remote -v` prints per remote into one
+/// entry each, keeping git's order.
+fn parse_remotes(stdout: &str) -> Vec<RemoteEntry> {
+    let mut remotes: Vec<RemoteEntry> = Vec::new();
+    for line in stdout.lines() {
+        let Some((name, rest)) = line.split_once('\t') else {
+            continue;
+        };
+        let (url, kind) = match rest.rsplit_once(' ') {
+            Some((url, kind)) => (url, kind),
+            None => (rest, "(fetch)"),
+        };
+
+        let pos = match remotes.iter().position(|r| r.name == name) {
+            Some(pos) => pos,
+            None => {
+                remotes.push(RemoteEntry {
+                    name: name.to_string(),
+                    fetch_url: url.to_string(),
+                    push_url: url.to_string(),
+                });
+                remotes.len() - 1
+            }
+        };
+        match kind {
+            "(push)" => remotes[pos].push_url = url.to_string(),
+            _ => remotes[pos].fetch_url = url.to_string(),
+        }
+    }
+    remotes
+}
+
+pub fn list_remotes(repo_path: &str) -> Result<Vec<RemoteEntry>, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path, "remote", "-v"])
         .output()
         .map_err(|e| format!("Failed to run git: {e}"))?;
-
-    if !root_output.status.success() {
+    if !output.status.success() {
         return Err(format!(
-            "Not a git repository: {}",
-            String::from_utf8_lossy(&root_output.stderr).trim()
+            "Failed to list remotes: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
         ));
     }
+    Ok(parse_remotes(&String::from_utf8_lossy(&output.stdout)))
+}
 
-    let root_path = String::from_utf8_lossy(&root_output.stdout)
-        .trim()
-        .to_string();
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StashEntry {
+    /// Reflog selector such as `stash@{0}`, usable as a stash reference.
+    pub name: String,
+    pub sha: String,
+    /// e.g. `WIP on main: 1a2b3c4 subject` or `On main: <message>`.
+    pub message: String,
+}
 
-    let name = std::path::Path::new(&root_path)
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| root_path.clone());
+const STASH_LIST_FORMAT: &str = "--format=%gd%x00%H%x00%gs";
 
-    let branch_output = Command::new("git")
-        .args(["-C", repo_path, "rev-parse", "--abbrev-ref", "HEAD"])
-        .output()
-        .map_err(|e| format!("Failed to get branch: {e}"))?;
+fn parse_stash_list(stdout: &str) -> Vec<StashEntry> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            Some(StashEntry {
+                name: fields.next()?.to_string(),
+                sha: fields.next()?.to_string(),
+                message: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
 
-    if !branch_output.status.success() {
+/// Stash entries, newest first; empty when nothing was ever stashed.
+pub fn list_stashes(repo_path: &str) -> Result<Vec<StashEntry>, String> {
+    check_repo_path(repo_path)?;
+
+    let output = git_command()
+        .args(["-C", repo_path, "stash", "list", STASH_LIST_FORMAT])
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
         return Err(format!(
-            "Failed to get current branch: {}",
-            String::from_utf8_lossy(&branch_output.stderr).trim()
+            "Failed to list stashes: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
         ));
     }
+    Ok(parse_stash_list(&String::from_utf8_lossy(&output.stdout)))
+}
 
-    let branch = String::from_utf8_lossy(&branch_output.stdout)
-        .trim()
-        .to_string();
+/// How long `fetch_range_refs` waits for each `git fetch`.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
 
-    Ok(RepoInfo {
-        name,
-        branch,
-        path: root_path,
-    })
+/// Remote-tracking refs (`<remote>/<branch>`) named by the endpoints of `range`, as
+/// `(remote, branch)` pairs. Revision suffixes such as `~2` or `^!` are ignored.
+fn remote_refs_in_range(range: &str, remotes: &[RemoteEntry]) -> Vec<(String, String)> {
+    let endpoints: Vec<&str> = match range.split_once("...") {
+        Some((a, b)) => vec![a, b],
+        None => match range.split_once("..") {
+            Some((a, b)) => vec![a, b],
+            None => vec![range],
+        },
+    };
+
+    let mut refs: Vec<(String, String)> = Vec::new();
+    for endpoint in endpoints {
+        let name = endpoint.split(['~', '^', '@']).next().unwrap_or_default();
+        let Some((remote, branch)) = name.split_once('/') else {
+            continue;
+        };
+        // A remote or branch such as `--upload-pack=<cmd>` is never fetched: git
+        // would take it as an option
+        if branch.is_empty() || remote.starts_with('-') || branch.starts_with('-') {
+            continue;
+        }
+        let pair = (remote.to_string(), branch.to_string());
+        if remotes.iter().any(|r| r.name == remote) && !refs.contains(&pair) {
+            refs.push(pair);
+        }
+    }
+    refs
+}
+
+/// Fetches the remote-tracking refs `range` refers to, so a diff against e.g.
+/// `origin/main` is not taken against a stale ref. Each fetch is bounded by `timeout`
+/// and never prompts for credentials; failures name the ref that may be stale.
+pub async fn fetch_range_refs(
+    range: Option<&str>,
+    repo_path: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let Some(range) = range else {
+        return Ok(());
+    };
+    let path = repo_path.to_string();
+    let remotes = tauri::async_runtime::spawn_blocking(move || list_remotes(&path))
+        .await
+        .unwrap_or_else(|e| Err(format!("Failed to list remotes: {e}")))?;
+
+    for (remote, branch) in remote_refs_in_range(range, &remotes) {
+        let child = tokio::process::Command::from(git_command())
+            .args(["-C", repo_path, "fetch", "--quiet", "--end-of-options"])
+            .args([&remote, &branch])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to run git fetch: {e}"))?;
+
+        let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => output.map_err(|e| format!("Failed to run git fetch: {e}"))?,
+            Err(_) => {
+                return Err(format!(
+                    "Fetching '{}' from '{}' timed out after {}s; '{}/{}' may be stale",
+                    branch,
+                    remote,
+                    timeout.as_secs(),
+                    remote,
+                    branch
+                ))
+            }
+        };
+        if !output.status.success() {
+            return Err(format!(
+                "Fetching '{}' from '{}' failed, so '{}/{}' may be stale: {}",
+                branch,
+                remote,
+                remote,
+                branch,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    Ok(())
 }
 
-pub fn get_remote_repo_info(remote: &str) -> Result<RepoInfo, String> {
+/// Separates the sections of `list_remote_refs` output.
+const REMOTE_REFS_MARKER: &str = "--great-review-refs--";
+
+pub fn list_remote_refs(remote: &str, ssh: &SshOptions) -> Result<Refs, String> {
     let (host, path) = crate::diff_parser::parse_remote_path(remote)?;
 
     let cmd = format!(
-        "cd '{}' && git rev-parse --show-toplevel && git rev-parse --abbrev-ref HEAD",
-        path
+        "cd {path} && git rev-parse --abbrev-ref HEAD && echo '{marker}' && git branch --format='%(refname:short)' && echo '{marker}' && git tag --list",
+        path = crate::diff_parser::shell_quote(path),
+        marker = REMOTE_REFS_MARKER
     );
 
-    let output = Command::new("ssh")
-        .args(["-o", "ConnectTimeout=10", "-o", "BatchMode=yes", host, &cmd])
+    let output = ssh_command(host, &cmd, ssh)
         .output()
         .map_err(|e| format!("Failed to execute ssh: {}", e))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(format!("Failed to get remote repo info: {}", stderr.trim()));
+        return Err(crate::diff_parser::describe_remote_error(
+            &stderr, host, path,
+        ));
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let mut lines = stdout.trim().lines();
-    let root_path = lines.next().unwrap_or("").to_string();
-    let branch = lines.next().unwrap_or("").to_string();
+    let marker_line = format!("{}\n", REMOTE_REFS_MARKER);
+    let sections: Vec<&str> = stdout.split(marker_line.as_str()).collect();
+    match sections.as_slice() {
+        [current, branches, tags] => Ok(build_refs(current, branches, tags)),
+        _ => Err("Malformed remote output: refs markers not found".to_string()),
+    }
+}
 
-    let name = std::path::Path::new(&root_path)
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| root_path.clone());
+fn build_refs(current: &str, branches: &str, tags: &str) -> Refs {
+    let current = current.trim().to_string();
+    let mut branches: Vec<String> = branches
+        .lines()
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(str::to_string)
+        .collect();
+    // Stable sort keeps git's alphabetical order for everything but the current branch
+    branches.sort_by_key(|b| *b != current);
+    let tags = tags
+        .lines()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+    Refs {
+        branches,
+        tags,
+        current,
+    }
+}
 
-    Ok(RepoInfo {
-        name,
-        branch,
-        path: remote.to_string(),
-    })
+/// CLI flags (from the `cli` section of `tauri.conf.json`) whose next argument is
+/// their value rather than a positional one.
+const FLAGS_WITH_VALUES: [&str; 6] = [
+    "--remote",
+    "-R",
+    "--ssh-retries",
+    "--ssh-retry-backoff-ms",
+    "--ssh-command",
+    "--diff-file",
+];
+
+/// The first positional argument of `argv` (program name first), skipping flags and
+/// the values of `FLAGS_WITH_VALUES`. Everything after `--` is positional.
+fn range_from_argv(argv: &[String]) -> Option<String> {
+    let mut args = argv.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            return args.next().cloned();
+        }
+        if FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            args.next();
+            continue;
+        }
+        if !arg.starts_with('-') {
+            return Some(arg.clone());
+        }
+    }
+    None
+}
+
+/// What the positional argument asks to review.
+#[derive(Debug, PartialEq)]
+pub enum Positional {
+    Range(String),
+    /// `great-review /tmp/review.patch`, the same as `--diff-file`.
+    DiffFile(String),
+}
+
+/// An existing file is a patch to review unless it also names a revision in
+/// `repo_path`, so a branch that happens to share a file's name stays a range.
+pub fn classify_positional(arg: String, repo_path: Option<&str>) -> Positional {
+    let is_ref = |path: &str| resolve_ref(&arg, path).is_ok();
+    if std::path::Path::new(&arg).is_file() && !repo_path.is_some_and(is_ref) {
+        Positional::DiffFile(arg)
+    } else {
+        Positional::Range(arg)
+    }
+}
+
+fn positional_from_argv(argv: &[String], repo_path: Option<&str>) -> Option<Positional> {
+    range_from_argv(argv).map(|arg| classify_positional(arg, repo_path))
 }
 
 pub fn get_diff_range_from_args() -> Option<String> {
-    std::env::args().nth(1).filter(|arg| !arg.starts_with('-'))
+    let repo = locate_repo(None).ok();
+    let argv = std::env::args().collect::<Vec<_>>();
+    match positional_from_argv(&argv, repo.as_ref().map(|r| r.path.as_str())) {
+        Some(Positional::Range(range)) => Some(range),
+        _ => None,
+    }
 }
 
 pub fn find_repo_root() -> Result<String, String> {
-    let output = Command::new("git")
+    let output = git_command()
         .args(["rev-parse", "--show-toplevel"])
         .output()
         .map_err(|e| format!("Failed to run git: {e}"))?;
@@ -108,9 +1307,28 @@ pub fn find_repo_root() -> Result<String, String> {
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
+/// Root of the Mercurial working copy containing the current directory.
+pub fn find_hg_root() -> Result<String, String> {
+    let output = crate::diff_parser::hg_command()
+        .arg("root")
+        .output()
+        .map_err(|e| format!("Failed to run hg: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Not inside a Mercurial repository: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::TempRepo;
+    use std::process::Command;
 
     #[test]
     fn test_find_repo_root() {
@@ -128,6 +1346,400 @@ mod tests {
         assert!(range.is_none() || !range.unwrap().is_empty());
     }
 
+    #[test]
+    fn test_range_from_argv_orderings() {
+        let argv = |args: &[&str]| -> Vec<String> {
+            std::iter::once("greview")
+                .chain(args.iter().copied())
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(
+            range_from_argv(&argv(&["main..HEAD"])).as_deref(),
+            Some("main..HEAD")
+        );
+        assert_eq!(
+            range_from_argv(&argv(&["--remote", "host:/repo", "main"])).as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            range_from_argv(&argv(&["-R", "host:/repo", "--no-batch-mode", "HEAD~2"])).as_deref(),
+            Some("HEAD~2")
+        );
+        assert_eq!(
+            range_from_argv(&argv(&["--remote=host:/repo", "v1..v2"])).as_deref(),
+            Some("v1..v2")
+        );
+        assert_eq!(
+            range_from_argv(&argv(&["--", "-weird-branch"])).as_deref(),
+            Some("-weird-branch")
+        );
+        assert_eq!(range_from_argv(&argv(&["--remote", "host:/repo"])), None);
+        assert_eq!(range_from_argv(&argv(&[])), None);
+    }
+
+    #[test]
+    fn test_positional_from_argv_diff_file() {
+        let repo = TempRepo::new("positional-diff-file");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.write("review.patch", "");
+        let patch = repo.join("review.patch").to_str().unwrap().to_string();
+        let argv = |arg: &str| vec!["greview".to_string(), arg.to_string()];
+
+        assert_eq!(
+            positional_from_argv(&argv(&patch), Some(repo.path_str())),
+            Some(Positional::DiffFile(patch.clone()))
+        );
+        assert_eq!(
+            positional_from_argv(&argv(&patch), None),
+            Some(Positional::DiffFile(patch))
+        );
+        assert_eq!(
+            positional_from_argv(&argv("main"), Some(repo.path_str())),
+            Some(Positional::Range("main".to_string()))
+        );
+        assert_eq!(
+            positional_from_argv(&argv("/nonexistent/review.patch"), None),
+            Some(Positional::Range("/nonexistent/review.patch".to_string()))
+        );
+
+        // Tests run in the crate directory, where Cargo.toml is a file
+        repo.git(&["tag", "Cargo.toml"]);
+        assert_eq!(
+            positional_from_argv(&argv("Cargo.toml"), Some(repo.path_str())),
+            Some(Positional::Range("Cargo.toml".to_string()))
+        );
+        assert_eq!(
+            positional_from_argv(&argv("Cargo.toml"), None),
+            Some(Positional::DiffFile("Cargo.toml".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_flags_with_values_match_cli_config() {
+        let conf: serde_json::Value =
+            serde_json::from_str(include_str!("../tauri.conf.json")).unwrap();
+        let args = conf["plugins"]["cli"]["args"].as_array().unwrap();
+        for arg in args {
+            let takes_value = arg["takesValue"].as_bool().unwrap_or(false);
+            if !takes_value || arg.get("index").is_some() {
+                continue;
+            }
+            let long = format!("--{}", arg["name"].as_str().unwrap());
+            assert!(
+                FLAGS_WITH_VALUES.contains(&long.as_str()),
+                "{long} is missing"
+            );
+            if let Some(short) = arg["short"].as_str() {
+                let short = format!("-{short}");
+                assert!(
+                    FLAGS_WITH_VALUES.contains(&short.as_str()),
+                    "{short} is missing"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_head_sha() {
+        let root = find_repo_root().unwrap();
+        let sha = get_head_sha(&root).unwrap();
+        assert_eq!(sha.len(), 40);
+        assert!(sha.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_split_bundle_output() {
+        let stdout = format!(
+            "/home/user/repo\nmain\n{}\ndiff --git a/a.txt b/a.txt\n",
+            REMOTE_BUNDLE_MARKER
+        );
+        let (info, diff) = split_bundle_output(&stdout).unwrap();
+        assert_eq!(info, "/home/user/repo\nmain\n");
+        assert_eq!(diff, "diff --git a/a.txt b/a.txt\n");
+
+        let repo = remote_repo_info_from_stdout("host:/home/user/repo", info);
+        assert_eq!(repo.name, "repo");
+        assert_eq!(repo.branch, "main");
+        assert_eq!(repo.path, "host:/home/user/repo");
+    }
+
+    #[test]
+    fn test_remote_repo_info_from_stdout_sections() {
+        let sep = REMOTE_INFO_SEPARATOR;
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        let stdout = format!("/srv/app\nmain\n{sep}\n{sha}\n{sep}\n M src/lib.rs\n");
+        let info = remote_repo_info_from_stdout("host:/srv/app", &stdout);
+        assert_eq!(info.branch, "main");
+        assert_eq!(info.head_sha.as_deref(), Some(sha));
+        assert_eq!(info.dirty, Some(true));
+
+        // Before the first commit, with a clean index and tree
+        let stdout = format!("/srv/app\ntrunk\n{sep}\n{sep}\n");
+        let info = remote_repo_info_from_stdout("host:/srv/app", &stdout);
+        assert_eq!(info.branch, "trunk");
+        assert_eq!(info.head_sha, None);
+        assert_eq!(info.dirty, Some(false));
+    }
+
+    #[test]
+    fn test_remote_info_command_runs_locally() {
+        let repo = TempRepo::new("remote-info-command");
+        let output = Command::new("sh")
+            .args(["-c", &remote_info_command(repo.path_str())])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let info =
+            remote_repo_info_from_stdout("host:/x", &String::from_utf8_lossy(&output.stdout));
+        assert_eq!(info.branch, "main");
+        assert_eq!(info.head_sha, None);
+
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.write("a.txt", "b\n");
+        let output = Command::new("sh")
+            .args(["-c", &remote_info_command(repo.path_str())])
+            .output()
+            .unwrap();
+        let info =
+            remote_repo_info_from_stdout("host:/x", &String::from_utf8_lossy(&output.stdout));
+        assert_eq!(
+            info.head_sha.as_deref(),
+            Some(repo.git(&["rev-parse", "HEAD"]).trim())
+        );
+        assert_eq!(info.dirty, Some(true));
+    }
+
+    /// Runs the remote command in a local shell instead of over ssh.
+    fn local_ssh() -> SshOptions {
+        SshOptions {
+            command: Some(r#"sh -c 'for a; do cmd=$a; done; sh -c "$cmd"' ssh"#.to_string()),
+            retries: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_remote_bundle() {
+        let repo = TempRepo::new("remote-bundle");
+        repo.write("a.txt", "1\n2\n3\n4\n5\n");
+        repo.commit_all("first");
+        repo.write("a.txt", "1\n2\nthree\n4\n5\n");
+        let remote = format!("host:{}", repo.path_str());
+
+        let options = DiffOptions::new().context_lines(0);
+        let (info, diff_text) = get_remote_bundle(&remote, &options, &local_ssh()).unwrap();
+        assert_eq!(info.dirty, Some(true));
+        assert!(diff_text.contains("@@ -3 +3 @@"), "got: {diff_text}");
+
+        // The range reaches git as one quoted revision, never as shell or an option
+        let pwned = repo.join("pwned");
+        for range in [
+            format!("HEAD; touch {}", pwned.display()),
+            format!("--output={}", pwned.display()),
+        ] {
+            let options = DiffOptions::new().range(range);
+            assert!(get_remote_bundle(&remote, &options, &local_ssh()).is_err());
+            assert!(!pwned.exists());
+        }
+    }
+
+    #[test]
+    fn test_split_bundle_output_empty_diff() {
+        let stdout = format!("/repo\nmain\n{}\n", REMOTE_BUNDLE_MARKER);
+        let (_, diff) = split_bundle_output(&stdout).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_split_bundle_output_missing_marker() {
+        let result = split_bundle_output("/repo\nmain\n");
+        assert!(result.unwrap_err().contains("marker not found"));
+    }
+
+    #[test]
+    fn test_locate_repo_bare() {
+        let repo = TempRepo::bare("locate-bare");
+        let root = locate_repo(Some(repo.path_str())).unwrap();
+        assert!(root.bare);
+        assert_eq!(
+            std::fs::canonicalize(&root.path).unwrap(),
+            std::fs::canonicalize(&repo.path).unwrap()
+        );
+
+        assert!(root.check_diff_range(Some("HEAD~1..HEAD")).is_ok());
+        assert!(root.check_diff_range(Some("HEAD^!")).is_ok());
+        let err = root.check_diff_range(None).unwrap_err();
+        assert!(err.contains("bare repository"));
+        assert!(root.check_diff_range(Some("HEAD")).is_err());
+    }
+
+    #[test]
+    fn test_is_commit_range() {
+        assert!(is_commit_range("main..topic"));
+        assert!(is_commit_range("main...topic"));
+        assert!(is_commit_range("HEAD^!"));
+        assert!(!is_commit_range("main"));
+        assert!(!is_commit_range("HEAD~3"));
+    }
+
+    #[test]
+    fn test_locate_repo_explicit_non_bare_git_dir() {
+        let repo = TempRepo::new("locate-git-dir");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+
+        let git_dir = repo.join(".git");
+        let root = locate_repo(Some(git_dir.to_str().unwrap())).unwrap();
+        assert!(!root.bare);
+        assert_eq!(
+            std::fs::canonicalize(&root.path).unwrap(),
+            std::fs::canonicalize(&repo.path).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_locate_repo_invalid_git_dir() {
+        let repo = TempRepo::new("locate-invalid");
+        let missing = repo.join("not-a-git-dir");
+        let err = locate_repo(Some(missing.to_str().unwrap())).unwrap_err();
+        assert!(err.contains("is not a git directory"));
+    }
+
+    #[test]
+    fn test_get_repo_info_bare() {
+        let repo = TempRepo::bare("info-bare");
+        let info = get_repo_info(repo.path_str()).unwrap();
+        assert!(info.bare);
+        assert_eq!(info.branch, "main");
+        assert!(info.name.starts_with("great-review-test-info-bare"));
+    }
+
+    #[test]
+    fn test_get_repo_info_unborn_branch() {
+        let repo = TempRepo::new("info-unborn");
+        repo.git(&["symbolic-ref", "HEAD", "refs/heads/trunk"]);
+        let info = get_repo_info(repo.path_str()).unwrap();
+        assert_eq!(info.branch, "trunk");
+        assert_eq!(info.head_sha, None);
+
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        let info = get_repo_info(repo.path_str()).unwrap();
+        assert_eq!(
+            info.head_sha.as_deref(),
+            Some(repo.git(&["rev-parse", "HEAD"]).trim())
+        );
+
+        repo.git(&["checkout", "-q", "--detach"]);
+        assert_eq!(get_repo_info(repo.path_str()).unwrap().branch, "HEAD");
+    }
+
+    #[test]
+    fn test_get_repo_info_missing_path() {
+        let err = get_repo_info("/nonexistent/great-review/repo").unwrap_err();
+        assert!(err.contains("does not exist"));
+    }
+
+    #[test]
+    fn test_get_merge_base() {
+        let repo = TempRepo::new("merge-base");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        let base = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.write("b.txt", "b\n");
+        repo.commit_all("feature work");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("c.txt", "c\n");
+        repo.commit_all("main work");
+
+        let merge_base = get_merge_base("main", "feature", repo.path_str()).unwrap();
+        assert_eq!(merge_base, base);
+    }
+
+    #[test]
+    fn test_get_merge_base_unrelated_histories() {
+        let repo = TempRepo::new("merge-base-unrelated");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.git(&["checkout", "-q", "--orphan", "other"]);
+        repo.write("b.txt", "b\n");
+        repo.commit_all("unrelated root");
+
+        let err = get_merge_base("main", "other", repo.path_str()).unwrap_err();
+        assert!(err.contains("no common ancestor"));
+    }
+
+    #[test]
+    fn test_get_merge_base_unknown_ref() {
+        let repo = TempRepo::new("merge-base-unknown");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+
+        let err = get_merge_base("main", "does-not-exist", repo.path_str()).unwrap_err();
+        assert!(err.starts_with("Failed to find merge base"));
+    }
+
+    #[test]
+    fn test_get_merge_base_option_like_ref() {
+        let repo = TempRepo::new("merge-base-option");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+
+        // Taken as a revision, not as merge-base's own `--all`
+        let err = get_merge_base("--all", "main", repo.path_str()).unwrap_err();
+        assert!(err.contains("Not a valid object name --all"), "{err}");
+    }
+
+    #[test]
+    fn test_get_blob() {
+        let repo = TempRepo::new("get-blob");
+        repo.write("a.txt", "hello\nworld\n");
+        repo.write("img.bin", "PNG\0\x01");
+        repo.commit_all("initial");
+        let sha = repo.git(&["rev-parse", "HEAD:a.txt"]).trim().to_string();
+
+        assert_eq!(get_blob(&sha, repo.path_str()).unwrap(), "hello\nworld\n");
+        assert_eq!(
+            get_blob(&sha[..7], repo.path_str()).unwrap(),
+            "hello\nworld\n"
+        );
+
+        let err = get_blob("HEAD:img.bin", repo.path_str()).unwrap_err();
+        assert_eq!(err, "Blob 'HEAD:img.bin' is binary");
+        let err = get_blob("HEAD", repo.path_str()).unwrap_err();
+        assert_eq!(err, "'HEAD' is a commit, not a blob");
+        let err = get_blob("0123456789abcdef", repo.path_str()).unwrap_err();
+        assert!(err.starts_with("No object"), "got: {err}");
+    }
+
+    #[test]
+    fn test_build_refs_current_first() {
+        let refs = build_refs("feature\n", "alpha\nfeature\nmain\n", "v1.0\nv2.0\n");
+        assert_eq!(refs.current, "feature");
+        assert_eq!(refs.branches, ["feature", "alpha", "main"]);
+        assert_eq!(refs.tags, ["v1.0", "v2.0"]);
+    }
+
+    #[test]
+    fn test_list_refs() {
+        let repo = TempRepo::new("list-refs");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.git(&["tag", "v1.0"]);
+        repo.git(&["branch", "alpha"]);
+        repo.git(&["checkout", "-q", "-b", "zeta"]);
+
+        let refs = list_refs(repo.path_str()).unwrap();
+        assert_eq!(refs.current, "zeta");
+        assert_eq!(refs.branches, ["zeta", "alpha", "main"]);
+        assert_eq!(refs.tags, ["v1.0"]);
+    }
+
     #[test]
     fn test_get_repo_info() {
         let root = find_repo_root().unwrap();
@@ -135,7 +1747,377 @@ mod tests {
         assert!(info.is_ok());
         let info = info.unwrap();
         assert_eq!(info.name, "great-review");
+        assert!(!info.bare);
         assert!(!info.branch.is_empty());
         assert!(!info.path.is_empty());
     }
+
+    #[test]
+    fn test_get_repo_info_sparse_checkout() {
+        let repo = TempRepo::new("repo-info-sparse");
+        repo.write("docs/readme.md", "docs\n");
+        repo.write("src/main.rs", "fn main() {}\n");
+        repo.commit_all("initial");
+
+        let info = get_repo_info(repo.path_str()).unwrap();
+        assert!(!info.sparse);
+        assert!(info.working_tree_warning.is_none());
+
+        repo.git(&["sparse-checkout", "set", "--no-cone", "/src/"]);
+        let info = get_repo_info(repo.path_str()).unwrap();
+        assert!(info.sparse);
+        assert_eq!(info.sparse_patterns, vec!["/src/"]);
+        assert!(info
+            .working_tree_warning
+            .unwrap()
+            .ends_with("matching /src/"));
+    }
+
+    #[test]
+    fn test_resolve_ref() {
+        let repo = TempRepo::new("resolve-ref");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("first");
+        let head = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+
+        assert_eq!(resolve_ref("main", repo.path_str()).unwrap(), head);
+        assert_eq!(resolve_ref(&head[..10], repo.path_str()).unwrap(), head);
+        assert_eq!(
+            resolve_ref("mian", repo.path_str()).unwrap_err(),
+            "Unknown revision 'mian'"
+        );
+
+        repo.git(&["tag", "dup"]);
+        repo.git(&["branch", "dup"]);
+        assert_eq!(
+            resolve_ref("dup", repo.path_str()).unwrap_err(),
+            "Ambiguous revision 'dup'"
+        );
+    }
+
+    #[test]
+    fn test_get_default_branch() {
+        let repo = TempRepo::new("default-branch");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("first");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        assert_eq!(get_default_branch(repo.path_str()).unwrap(), "main");
+
+        // Without main or master, the current branch is all there is
+        repo.git(&["branch", "-D", "main"]);
+        assert_eq!(get_default_branch(repo.path_str()).unwrap(), "feature");
+
+        repo.git(&["update-ref", "refs/remotes/origin/develop", "HEAD"]);
+        repo.git(&[
+            "symbolic-ref",
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/develop",
+        ]);
+        assert_eq!(get_default_branch(repo.path_str()).unwrap(), "develop");
+    }
+
+    #[test]
+    fn test_get_commit_messages() {
+        let repo = TempRepo::new("commit-messages");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("First commit");
+        repo.write("a.txt", "two\n");
+        repo.git(&[
+            "commit",
+            "-qam",
+            "Second commit\n\nWhy it changed.\n\nMore detail.",
+        ]);
+        repo.write("a.txt", "three\n");
+        repo.commit_all("Third commit");
+
+        // A lone revision is compared with the working tree, so its tip is HEAD
+        let tip = get_commit_messages("HEAD~1", false, false, repo.path_str()).unwrap();
+        assert_eq!(tip.len(), 1);
+        assert_eq!(tip[0].subject, "Third commit");
+        assert_eq!(tip[0].sha.len(), 40);
+        let since = get_commit_messages("HEAD~1", true, false, repo.path_str()).unwrap();
+        assert_eq!(since.len(), 1);
+
+        let tip = get_commit_messages("HEAD~2..HEAD~1", false, false, repo.path_str()).unwrap();
+        assert_eq!(tip[0].subject, "Second commit");
+        assert_eq!(tip[0].body, "Why it changed.\n\nMore detail.");
+
+        let all = get_commit_messages("HEAD~2..", true, false, repo.path_str()).unwrap();
+        let subjects: Vec<&str> = all.iter().map(|m| m.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["Third commit", "Second commit"]);
+        assert_eq!(all[0].body, "");
+
+        assert!(get_commit_messages("nope", true, false, repo.path_str()).is_err());
+    }
+
+    #[test]
+    fn test_count_commits() {
+        let repo = TempRepo::new("count-commits");
+        for n in 1..=3 {
+            repo.write("a.txt", &format!("{n}\n"));
+            repo.commit_all(&format!("Commit {n}"));
+        }
+
+        assert_eq!(
+            count_commits("HEAD~2..HEAD", false, repo.path_str()).unwrap(),
+            2
+        );
+        assert_eq!(
+            count_commits("HEAD~2...", false, repo.path_str()).unwrap(),
+            2
+        );
+        assert_eq!(count_commits("HEAD^!", false, repo.path_str()).unwrap(), 1);
+        assert_eq!(count_commits("HEAD~1", false, repo.path_str()).unwrap(), 1);
+        assert_eq!(count_commits("HEAD", false, repo.path_str()).unwrap(), 0);
+
+        let err = count_commits("nope..HEAD", false, repo.path_str()).unwrap_err();
+        assert!(err.starts_with("Invalid range 'nope..HEAD'"), "got: {err}");
+
+        let base = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+        repo.git(&["checkout", "-q", "-b", "topic"]);
+        repo.write("b.txt", "b\n");
+        repo.commit_all("Topic 1");
+        repo.write("b.txt", "c\n");
+        repo.commit_all("Topic 2");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.git(&["merge", "-q", "--no-ff", "-m", "Merge topic", "topic"]);
+        let range = format!("{base}..HEAD");
+        assert_eq!(count_commits(&range, false, repo.path_str()).unwrap(), 3);
+        assert_eq!(count_commits(&range, true, repo.path_str()).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_commit_count_args() {
+        assert_eq!(
+            commit_count_args("main...topic", true),
+            [
+                "rev-list",
+                "--count",
+                "--first-parent",
+                "--end-of-options",
+                "main..topic"
+            ]
+        );
+        assert_eq!(
+            commit_count_args("v1.0", false),
+            ["rev-list", "--count", "--end-of-options", "v1.0..HEAD"]
+        );
+    }
+
+    #[test]
+    fn test_get_tip_author() {
+        let repo = TempRepo::new("tip-author");
+        repo.write("a.txt", "1\n");
+        repo.commit_all("first");
+        repo.write("a.txt", "2\n");
+        repo.git(&[
+            "-c",
+            "user.name=Grace Hopper",
+            "-c",
+            "user.email=grace@example.com",
+            "commit",
+            "-q",
+            "-am",
+            "second",
+        ]);
+
+        let grace = Identity {
+            name: "Grace Hopper".to_string(),
+            email: "grace@example.com".to_string(),
+        };
+        assert_eq!(
+            get_tip_author("HEAD~1..HEAD", repo.path_str()).unwrap(),
+            grace
+        );
+        assert_eq!(get_tip_author("HEAD^!", repo.path_str()).unwrap(), grace);
+        assert_eq!(get_tip_author("HEAD~1", repo.path_str()).unwrap(), grace);
+        let first = get_tip_author("HEAD~1^!", repo.path_str()).unwrap();
+        assert_eq!(first.email, "test@example.com");
+
+        let err = get_tip_author("HEAD..nope", repo.path_str()).unwrap_err();
+        assert!(
+            err.starts_with("Failed to read the tip commit"),
+            "got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_commit_message_args() {
+        assert_eq!(
+            commit_message_args("abc^!", true, false),
+            vec![
+                "log",
+                "--format=%H%x00%s%x00%b%x1e",
+                "--end-of-options",
+                "abc^..abc"
+            ]
+        );
+        assert_eq!(
+            commit_message_args("main...topic", false, false)[2..],
+            ["-1", "--end-of-options", "topic"]
+        );
+        assert_eq!(
+            commit_message_args("main...topic", true, true)[2..],
+            ["--first-parent", "--end-of-options", "main..topic"]
+        );
+        assert_eq!(
+            commit_message_args("v1.0", true, false)[2..],
+            ["--end-of-options", "v1.0..HEAD"]
+        );
+        assert_eq!(
+            commit_message_args("v1.0", false, false)[2..],
+            ["-1", "--end-of-options", "HEAD"]
+        );
+    }
+
+    #[test]
+    fn test_parse_default_branch() {
+        let stdout = "  refs/heads/dev \n* refs/heads/topic \n  refs/heads/master \n";
+        assert_eq!(parse_default_branch(stdout).unwrap(), "master");
+        assert!(parse_default_branch("").is_err());
+    }
+
+    #[test]
+    fn test_list_stashes() {
+        let repo = TempRepo::new("list-stashes");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("initial");
+        assert!(list_stashes(repo.path_str()).unwrap().is_empty());
+
+        repo.write("a.txt", "two\n");
+        repo.git(&["stash", "push", "-m", "try: colons"]);
+        let stashes = list_stashes(repo.path_str()).unwrap();
+        assert_eq!(stashes.len(), 1);
+        assert_eq!(stashes[0].name, "stash@{0}");
+        assert_eq!(stashes[0].sha.len(), 40);
+        assert_eq!(stashes[0].message, "On main: try: colons");
+    }
+
+    #[test]
+    fn test_parse_remotes() {
+        let stdout = "\
+origin\tgit@github.com:pmatos/great-review.git (fetch)
+origin\tgit@github.com:pmatos/great-review.git (push)
+upstream\thttps://example.com/upstream.git (fetch)
+upstream\tssh://push.example.com/upstream.git (push)
+";
+        assert_eq!(
+            parse_remotes(stdout),
+            vec![
+                RemoteEntry {
+                    name: "origin".to_string(),
+                    fetch_url: "git@github.com:pmatos/great-review.git".to_string(),
+                    push_url: "git@github.com:pmatos/great-review.git".to_string(),
+                },
+                RemoteEntry {
+                    name: "upstream".to_string(),
+                    fetch_url: "https://example.com/upstream.git".to_string(),
+                    push_url: "ssh://push.example.com/upstream.git".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_remotes() {
+        let repo = TempRepo::new("list-remotes");
+        assert!(list_remotes(repo.path_str()).unwrap().is_empty());
+
+        repo.git(&["remote", "add", "origin", "/srv/git/project.git"]);
+        let remotes = list_remotes(repo.path_str()).unwrap();
+        assert_eq!(remotes.len(), 1);
+        assert_eq!(remotes[0].name, "origin");
+        assert_eq!(remotes[0].push_url, "/srv/git/project.git");
+    }
+
+    #[test]
+    fn test_remote_refs_in_range() {
+        let remotes =
+            parse_remotes("origin\t/srv/origin.git (fetch)\nfork\t/srv/fork.git (fetch)\n");
+        assert_eq!(
+            remote_refs_in_range("origin/main...fork/feature/x~1", &remotes),
+            vec![
+                ("origin".to_string(), "main".to_string()),
+                ("fork".to_string(), "feature/x".to_string()),
+            ]
+        );
+        // Local branches with slashes are not remote-tracking refs
+        assert!(remote_refs_in_range("topic/a..main", &remotes).is_empty());
+        assert_eq!(remote_refs_in_range("origin/main^!", &remotes).len(), 1);
+        assert!(remote_refs_in_range("origin/--upload-pack=touch x..HEAD", &remotes).is_empty());
+    }
+
+    #[test]
+    fn test_fetch_range_refs() {
+        let upstream = TempRepo::bare("fetch-upstream");
+        let repo = TempRepo::new("fetch-local");
+        repo.git(&["remote", "add", "origin", upstream.path_str()]);
+
+        tauri::async_runtime::block_on(fetch_range_refs(
+            Some("origin/main..HEAD"),
+            repo.path_str(),
+            FETCH_TIMEOUT,
+        ))
+        .unwrap();
+        assert!(!repo.git(&["rev-parse", "origin/main"]).trim().is_empty());
+
+        repo.git(&[
+            "remote",
+            "add",
+            "broken",
+            "/nonexistent/great-review/remote.git",
+        ]);
+        let err = tauri::async_runtime::block_on(fetch_range_refs(
+            Some("broken/main"),
+            repo.path_str(),
+            FETCH_TIMEOUT,
+        ))
+        .unwrap_err();
+        assert!(err.contains("'broken/main' may be stale"), "got: {err}");
+    }
+
+    #[test]
+    fn test_split_range() {
+        assert_eq!(
+            split_range("v1.0..v2.0"),
+            ("v1.0".to_string(), Some("v2.0".to_string()), false)
+        );
+        assert_eq!(
+            split_range("main..."),
+            ("main".to_string(), Some("HEAD".to_string()), true)
+        );
+        assert_eq!(
+            split_range("abc123^!"),
+            ("abc123^".to_string(), Some("abc123".to_string()), false)
+        );
+        assert_eq!(
+            split_range("HEAD@{1}"),
+            ("HEAD@{1}".to_string(), None, false)
+        );
+    }
+
+    #[test]
+    fn test_normalize_range() {
+        let repo = TempRepo::new("normalize-range");
+        repo.write("a.txt", "one\n");
+        repo.commit_all("first");
+        repo.git(&["tag", "v1.0"]);
+        repo.write("a.txt", "two\n");
+        repo.commit_all("second");
+
+        let head = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+        let first = repo.git(&["rev-parse", "v1.0"]).trim().to_string();
+
+        let normalized = normalize_range("v1.0..HEAD", repo.path_str()).unwrap();
+        assert_eq!(normalized.from.ref_name, "v1.0");
+        assert_eq!(normalized.from.sha, first);
+        assert_eq!(normalized.to.unwrap().sha, head);
+
+        let reflog = normalize_range("HEAD@{1}", repo.path_str()).unwrap();
+        assert_eq!(reflog.from.sha, first);
+        assert!(reflog.to.is_none());
+
+        let err = normalize_range("v1.0..mian", repo.path_str()).unwrap_err();
+        assert_eq!(err, "Unknown revision 'mian' in range 'v1.0..mian'");
+    }
 }
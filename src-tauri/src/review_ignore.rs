@@ -0,0 +1,276 @@
+use std::path::Path;
+
+use crate::diff_parser::DiffFile;
+
+/// Files never worth reviewing (generated assets, snapshots), listed one gitignore-style
+/// pattern per line at the repo root.
+pub const IGNORE_FILE: &str = ".great-review-ignore";
+
+#[derive(Clone, Debug, PartialEq)]
+struct IgnoreRule {
+    pattern: Vec<char>,
+    /// `!pattern`: files it matches are shown again.
+    negated: bool,
+    /// `pattern/`: only matches directories, i.e. the files under them.
+    dir_only: bool,
+    /// The pattern has a `/` before its end, so it matches from the repo root instead
+    /// of against a single path component at any depth.
+    anchored: bool,
+}
+
+/// Parsed `.great-review-ignore` rules. As in gitignore the last matching rule wins,
+/// but unlike git a negation can re-include a file under an ignored directory, since
+/// only changed files are checked and no directory is skipped wholesale.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_end();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let anchored = line.contains('/');
+                let line = line.strip_prefix('/').unwrap_or(line);
+                if line.is_empty() {
+                    return None;
+                }
+                Some(IgnoreRule {
+                    pattern: line.chars().collect(),
+                    negated,
+                    dir_only,
+                    anchored,
+                })
+            })
+            .collect();
+        IgnoreRules { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether the repo-relative file `path` is ignored.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let mut ignored = false;
+        for rule in &self.rules {
+            let matches = (1..=components.len()).any(|depth| {
+                let is_dir = depth < components.len();
+                if rule.dir_only && !is_dir {
+                    return false;
+                }
+                let subject = if rule.anchored {
+                    components[..depth].join("/")
+                } else {
+                    components[depth - 1].to_string()
+                };
+                let subject: Vec<char> = subject.chars().collect();
+                glob_match(&rule.pattern, &subject)
+            });
+            if matches {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+
+    /// Drops the ignored files, returning the rest and how many were dropped.
+    pub fn filter(&self, files: Vec<DiffFile>) -> (Vec<DiffFile>, usize) {
+        if self.is_empty() {
+            return (files, 0);
+        }
+        let total = files.len();
+        let kept: Vec<DiffFile> = files
+            .into_iter()
+            .filter(|f| !self.is_ignored(&f.path))
+            .collect();
+        let ignored = total - kept.len();
+        (kept, ignored)
+    }
+}
+
+/// Reads `<repo_root>/.great-review-ignore`; a missing file ignores nothing.
+pub fn load_ignore_rules(repo_root: &str) -> Result<IgnoreRules, String> {
+    let path = Path::new(repo_root).join(IGNORE_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => Ok(IgnoreRules::parse(&text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(IgnoreRules::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Glob matching as gitignore does it: `*` and `?` stay within one path component,
+/// `**` spans any number of them (`**/` also none), `[...]` is a character class
+/// (negated with `!` or `^`) and `\` escapes the next character.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    // Results are memoized per (pattern, text) position: without that, patterns with
+    // many stars backtrack exponentially
+    let mut memo = vec![None; (pattern.len() + 1) * (text.len() + 1)];
+    glob_match_at(pattern, text, 0, 0, &mut memo)
+}
+
+/// Whether `pattern[pi..]` matches `text[ti..]`.
+fn glob_match_at(
+    pattern: &[char],
+    text: &[char],
+    pi: usize,
+    ti: usize,
+    memo: &mut [Option<bool>],
+) -> bool {
+    let key = pi * (text.len() + 1) + ti;
+    if let Some(matched) = memo[key] {
+        return matched;
+    }
+    let next = text.get(ti).copied();
+    let matched = match pattern.get(pi) {
+        None => next.is_none(),
+        Some('*') if pattern.get(pi + 1) == Some(&'*') => {
+            (pattern.get(pi + 2) == Some(&'/') && glob_match_at(pattern, text, pi + 3, ti, memo))
+                || (ti..=text.len()).any(|i| glob_match_at(pattern, text, pi + 2, i, memo))
+        }
+        Some('*') => (ti..=text.len())
+            .take_while(|&i| i == ti || text[i - 1] != '/')
+            .any(|i| glob_match_at(pattern, text, pi + 1, i, memo)),
+        Some('?') => {
+            next.is_some_and(|c| c != '/') && glob_match_at(pattern, text, pi + 1, ti + 1, memo)
+        }
+        Some('[') => match match_class(&pattern[pi + 1..], next) {
+            Some((matched, class_len)) => {
+                matched && glob_match_at(pattern, text, pi + 1 + class_len, ti + 1, memo)
+            }
+            // No closing `]`: a literal `[`
+            None => next == Some('[') && glob_match_at(pattern, text, pi + 1, ti + 1, memo),
+        },
+        Some('\\') if pi + 1 < pattern.len() => {
+            next == Some(pattern[pi + 1]) && glob_match_at(pattern, text, pi + 2, ti + 1, memo)
+        }
+        Some(&c) => next == Some(c) && glob_match_at(pattern, text, pi + 1, ti + 1, memo),
+    };
+    memo[key] = Some(matched);
+    matched
+}
+
+/// Matches `c` against the class whose body starts at `class` (just after the `[`).
+/// Returns whether it matched and the class length including the closing `]`, or
+/// `None` when the class is not closed.
+fn match_class(class: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let negated = matches!(class.first(), Some('!') | Some('^'));
+    let start = usize::from(negated);
+    // A `]` right after the opening bracket is part of the class
+    let end = class
+        .iter()
+        .enumerate()
+        .skip(start + 1)
+        .find(|(_, ch)| **ch == ']')
+        .map(|(i, _)| i)?;
+    let Some(c) = c.filter(|c| *c != '/') else {
+        return Some((false, end + 1));
+    };
+
+    let body = &class[start..end];
+    let mut found = false;
+    let mut i = 0;
+    while i < body.len() {
+        if i + 2 < body.len() && body[i + 1] == '-' {
+            found |= (body[i]..=body[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= body[i] == c;
+            i += 1;
+        }
+    }
+    Some((found != negated, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempRepo;
+
+    fn file(path: &str) -> DiffFile {
+        DiffFile {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let rules = IgnoreRules::parse(
+            "\
+# generated
+*.min.js
+/dist/
+assets/**/*.png
+__snapshots__/
+!keep.snap
+[Cc]argo.lock
+",
+        );
+        assert!(rules.is_ignored("web/app.min.js"));
+        assert!(!rules.is_ignored("web/app.js"));
+        assert!(rules.is_ignored("dist/bundle.js"));
+        assert!(!rules.is_ignored("src/dist/bundle.js"));
+        assert!(rules.is_ignored("assets/logo.png"));
+        assert!(rules.is_ignored("assets/icons/big/logo.png"));
+        assert!(!rules.is_ignored("docs/assets/logo.png"));
+        assert!(rules.is_ignored("tests/__snapshots__/a.snap"));
+        assert!(!rules.is_ignored("tests/__snapshots__/keep.snap"));
+        assert!(rules.is_ignored("Cargo.lock"));
+        assert!(rules.is_ignored("sub/cargo.lock"));
+        assert!(!rules.is_ignored("Cargo.toml"));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let glob = |pattern: &str, text: &str| {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let text: Vec<char> = text.chars().collect();
+            glob_match(&pattern, &text)
+        };
+        assert!(glob("a?c", "abc"));
+        assert!(!glob("a*c", "a/c"));
+        assert!(glob("a/**/c", "a/c"));
+        assert!(glob("a/**", "a/b/c"));
+        assert!(glob("[!a]x", "bx"));
+        assert!(!glob("[a-c]x", "dx"));
+        assert!(glob("[x", "[x"));
+        assert!(glob("\\*", "*"));
+        assert!(!glob("\\*", "a"));
+        // Would backtrack exponentially without memoization
+        assert!(!glob("*a*a*a*a*a*a*a*a*a*b", &"a".repeat(40)));
+        assert!(!glob("**a**a**a**a**a**a**a**a**b", &"a/".repeat(20)));
+    }
+
+    #[test]
+    fn test_filter_and_load() {
+        let repo = TempRepo::new("review-ignore");
+        assert!(load_ignore_rules(repo.path_str()).unwrap().is_empty());
+
+        repo.write(IGNORE_FILE, "*.snap\n!important.snap\n");
+        let rules = load_ignore_rules(repo.path_str()).unwrap();
+        let (kept, ignored) = rules.filter(vec![
+            file("src/lib.rs"),
+            file("tests/a.snap"),
+            file("tests/important.snap"),
+            file("tests/b.snap"),
+        ]);
+        let paths: Vec<&str> = kept.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["src/lib.rs", "tests/important.snap"]);
+        assert_eq!(ignored, 2);
+    }
+}
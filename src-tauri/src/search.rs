@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use crate::diff_parser::{DiffFile, LineType};
+
+/// Which lines of the diff a search looks at.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum SearchFilter {
+    /// Additions, deletions and context lines.
+    #[default]
+    All,
+    Additions,
+    Deletions,
+}
+
+impl SearchFilter {
+    fn accepts(self, line_type: &LineType) -> bool {
+        match self {
+            SearchFilter::All => true,
+            SearchFilter::Additions => *line_type == LineType::Addition,
+            SearchFilter::Deletions => *line_type == LineType::Deletion,
+        }
+    }
+}
+
+/// A line containing the query, located by indices into the parsed files.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Match {
+    pub file_index: usize,
+    pub hunk_index: usize,
+    pub line_index: usize,
+    /// `[start, end)` byte offsets of each occurrence within `DiffLine::content`.
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// Byte ranges of non-overlapping occurrences of `needle` in `haystack`. Case-insensitive
+/// matching compares lowercased chars, so the offsets always refer to the original text.
+fn find_ranges(haystack: &str, needle: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+    if case_sensitive {
+        return haystack
+            .match_indices(needle)
+            .map(|(start, m)| (start, start + m.len()))
+            .collect();
+    }
+
+    let mut ranges = Vec::new();
+    let mut search_from = 0;
+    for (start, _) in haystack.char_indices() {
+        if start < search_from {
+            continue;
+        }
+        let mut rest = haystack[start..].char_indices();
+        let mut end = start;
+        let matched = needle.chars().all(|n| match rest.next() {
+            Some((offset, h)) => {
+                end = start + offset + h.len_utf8();
+                h.to_lowercase().eq(n.to_lowercase())
+            }
+            None => false,
+        });
+        if matched {
+            ranges.push((start, end));
+            search_from = end;
+        }
+    }
+    ranges
+}
+
+/// Finds every line of `files` containing `query`, in file, hunk and line order.
+pub fn search_diff(
+    files: &[DiffFile],
+    query: &str,
+    case_sensitive: bool,
+    filter: SearchFilter,
+) -> Vec<Match> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    for (file_index, file) in files.iter().enumerate() {
+        for (hunk_index, hunk) in file.hunks.iter().enumerate() {
+            for (line_index, line) in hunk.lines.iter().enumerate() {
+                if !filter.accepts(&line.line_type) {
+                    continue;
+                }
+                let ranges = find_ranges(&line.content, query, case_sensitive);
+                if !ranges.is_empty() {
+                    matches.push(Match {
+                        file_index,
+                        hunk_index,
+                        line_index,
+                        ranges,
+                    });
+                }
+            }
+        }
+    }
+    matches
+}
+
+/// An added line that ends in whitespace, located like a `Match`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WsIssue {
+    pub file_index: usize,
+    pub hunk_index: usize,
+    pub line_index: usize,
+}
+
+/// Finds the added lines ending in spaces, tabs or other whitespace, which many CI
+/// checks (and `git diff --check`) reject. Context and deleted lines are not the
+/// change's doing, so they are skipped.
+pub fn find_trailing_whitespace(files: &[DiffFile]) -> Vec<WsIssue> {
+    let mut issues = Vec::new();
+    for (file_index, file) in files.iter().enumerate() {
+        for (hunk_index, hunk) in file.hunks.iter().enumerate() {
+            for (line_index, line) in hunk.lines.iter().enumerate() {
+                if line.line_type == LineType::Addition
+                    && line
+                        .content
+                        .chars()
+                        .next_back()
+                        .is_some_and(char::is_whitespace)
+                {
+                    issues.push(WsIssue {
+                        file_index,
+                        hunk_index,
+                        line_index,
+                    });
+                }
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::parse_unified_diff;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index abc..def 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn parse() {}
+-fn Parse_all() { parse(); }
++fn parse_all() { parse(); parse(); }
+ fn other() {}
+";
+
+    #[test]
+    fn test_search_diff_case_sensitive() {
+        let files = parse_unified_diff(DIFF);
+        let matches = search_diff(&files, "parse", true, SearchFilter::All);
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[0].line_index, 0);
+        assert_eq!(matches[0].ranges, vec![(3, 8)]);
+        // Deletion: only the call matches, not `Parse_all`
+        assert_eq!(matches[1].line_index, 1);
+        assert_eq!(matches[1].ranges, vec![(17, 22)]);
+        assert_eq!(matches[2].ranges, vec![(3, 8), (17, 22), (26, 31)]);
+    }
+
+    #[test]
+    fn test_search_diff_case_insensitive_and_filters() {
+        let files = parse_unified_diff(DIFF);
+
+        let deletions = search_diff(&files, "PARSE", false, SearchFilter::Deletions);
+        assert_eq!(deletions.len(), 1);
+        assert_eq!(deletions[0].ranges, vec![(3, 8), (17, 22)]);
+
+        let additions = search_diff(&files, "parse_ALL", false, SearchFilter::Additions);
+        assert_eq!(additions.len(), 1);
+        assert_eq!(additions[0].line_index, 2);
+
+        assert!(search_diff(&files, "", false, SearchFilter::All).is_empty());
+    }
+
+    #[test]
+    fn test_find_trailing_whitespace() {
+        let files = parse_unified_diff(
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,4 @@\n \
+             context  \n-deleted \n+spaces  \n+clean\n+tab\t\n",
+        );
+        assert_eq!(
+            find_trailing_whitespace(&files),
+            vec![
+                WsIssue {
+                    file_index: 0,
+                    hunk_index: 0,
+                    line_index: 2,
+                },
+                WsIssue {
+                    file_index: 0,
+                    hunk_index: 0,
+                    line_index: 4,
+                },
+            ]
+        );
+        assert!(find_trailing_whitespace(&parse_unified_diff(DIFF)).is_empty());
+    }
+
+    #[test]
+    fn test_find_ranges_non_ascii() {
+        assert_eq!(find_ranges("Größe größe", "GRÖSSE", false), vec![]);
+        assert_eq!(
+            find_ranges("Größe größe", "GRÖßE", false),
+            vec![(0, 7), (8, 15)]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use crate::diff_parser::{DiffFile, DiffLine, LineType};
+
+/// One row of a two-column view. Context lines appear on both sides; `None` pads the
+/// shorter side of an unbalanced change.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SideBySideRow {
+    pub old: Option<DiffLine>,
+    pub new: Option<DiffLine>,
+}
+
+/// Lays out `file` in two aligned columns: each run of deletions is paired line by line
+/// with the additions that directly follow it.
+pub fn to_side_by_side(file: &DiffFile) -> Vec<SideBySideRow> {
+    let mut rows = Vec::new();
+    for hunk in &file.hunks {
+        let mut deletions: Vec<&DiffLine> = Vec::new();
+        let mut additions: Vec<&DiffLine> = Vec::new();
+        for line in &hunk.lines {
+            match line.line_type {
+                LineType::Deletion => {
+                    // Deletions after additions start a new change
+                    if !additions.is_empty() {
+                        flush_change(&mut rows, &mut deletions, &mut additions);
+                    }
+                    deletions.push(line);
+                }
+                LineType::Addition => additions.push(line),
+                LineType::Context => {
+                    flush_change(&mut rows, &mut deletions, &mut additions);
+                    rows.push(SideBySideRow {
+                        old: Some(line.clone()),
+                        new: Some(line.clone()),
+                    });
+                }
+            }
+        }
+        flush_change(&mut rows, &mut deletions, &mut additions);
+    }
+    rows
+}
+
+fn flush_change(
+    rows: &mut Vec<SideBySideRow>,
+    deletions: &mut Vec<&DiffLine>,
+    additions: &mut Vec<&DiffLine>,
+) {
+    for i in 0..deletions.len().max(additions.len()) {
+        rows.push(SideBySideRow {
+            old: deletions.get(i).map(|l| (*l).clone()),
+            new: additions.get(i).map(|l| (*l).clone()),
+        });
+    }
+    deletions.clear();
+    additions.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::parse_unified_diff;
+
+    fn contents(rows: &[SideBySideRow]) -> Vec<(Option<&str>, Option<&str>)> {
+        rows.iter()
+            .map(|r| {
+                (
+                    r.old.as_ref().map(|l| l.content.as_str()),
+                    r.new.as_ref().map(|l| l.content.as_str()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_side_by_side_mixed_changes() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+index abc1234..def5678 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1,5 +1,5 @@
+ line one
+-line two old
++line two new
+ line three
+-line four old
++line four new
+";
+        let rows = to_side_by_side(&parse_unified_diff(diff)[0]);
+        assert_eq!(
+            contents(&rows),
+            vec![
+                (Some("line one"), Some("line one")),
+                (Some("line two old"), Some("line two new")),
+                (Some("line three"), Some("line three")),
+                (Some("line four old"), Some("line four new")),
+            ]
+        );
+        assert_eq!(rows[1].old.as_ref().unwrap().old_line_no, Some(2));
+        assert_eq!(rows[1].new.as_ref().unwrap().new_line_no, Some(2));
+    }
+
+    #[test]
+    fn test_side_by_side_unbalanced_runs() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -1,5 +1,4 @@
+ keep
+-a
+-b
+-c
++ABC
+ keep
++added
+";
+        let rows = to_side_by_side(&parse_unified_diff(diff)[0]);
+        assert_eq!(
+            contents(&rows),
+            vec![
+                (Some("keep"), Some("keep")),
+                (Some("a"), Some("ABC")),
+                (Some("b"), None),
+                (Some("c"), None),
+                (Some("keep"), Some("keep")),
+                (None, Some("added")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::diff_parser::{hunk_old_lines, DiffFile, DiffHunk, LineType};
+
+/// The `[start, end)` 0-based line span a hunk replaces in the old file. A hunk that
+/// removes nothing (`-n,0`) inserts after line `n`, so its span is empty.
+fn old_span(hunk: &DiffHunk) -> (usize, usize) {
+    let start = hunk.old_start as usize;
+    if hunk.old_count == 0 {
+        (start, start)
+    } else {
+        let start = start.saturating_sub(1);
+        (start, start + hunk.old_count as usize)
+    }
+}
+
+fn without_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Applies only the hunks of `file` at `hunk_indices` to `base_content` (the old side of
+/// the file) and returns the resulting text, as staging those hunks alone would.
+///
+/// Each selected hunk's context and deleted lines must match `base_content` where the
+/// hunk says they are. Selected hunks may not overlap, since after applying one the
+/// other's line numbers no longer hold.
+pub fn apply_selected_hunks(
+    file: &DiffFile,
+    hunk_indices: &[usize],
+    base_content: &str,
+) -> Result<String, String> {
+    let mut selected: Vec<(usize, &DiffHunk)> = Vec::with_capacity(hunk_indices.len());
+    for &index in hunk_indices {
+        let hunk = file
+            .hunks
+            .get(index)
+            .ok_or_else(|| format!("No hunk at index {} in '{}'", index, file.path))?;
+        if !selected.iter().any(|(i, _)| *i == index) {
+            selected.push((index, hunk));
+        }
+    }
+    selected.sort_by_key(|(_, hunk)| old_span(hunk));
+
+    for pair in selected.windows(2) {
+        let ((a, first), (b, second)) = (pair[0], pair[1]);
+        if old_span(second).0 < old_span(first).1 {
+            return Err(format!(
+                "Hunks {} and {} of '{}' overlap and cannot be applied together",
+                a, b, file.path
+            ));
+        }
+    }
+
+    let base: Vec<&str> = base_content.split_inclusive('\n').collect();
+    let mut out = String::with_capacity(base_content.len());
+    let mut cursor = 0;
+
+    for (index, hunk) in selected {
+        let (start, end) = old_span(hunk);
+        if end > base.len() {
+            return Err(format!(
+                "Hunk {} of '{}' extends past the end of the file",
+                index, file.path
+            ));
+        }
+
+        for (offset, line) in hunk_old_lines(hunk).into_iter().enumerate() {
+            if base.get(start + offset).copied().map(without_line_ending) != Some(&line.content) {
+                return Err(format!(
+                    "Hunk {} of '{}' does not apply: line {} differs from the base content",
+                    index,
+                    file.path,
+                    start + offset + 1
+                ));
+            }
+        }
+
+        out.push_str(&base[cursor..start].concat());
+        let mut old_offset = start;
+        for line in &hunk.lines {
+            match line.line_type {
+                // Copied from the base so its line ending is kept
+                LineType::Context => {
+                    out.push_str(base[old_offset]);
+                    old_offset += 1;
+                }
+                LineType::Deletion => old_offset += 1,
+                LineType::Addition => {
+                    out.push_str(&line.content);
+                    if !line.no_newline {
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+        cursor = end;
+    }
+
+    out.push_str(&base[cursor..].concat());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::parse_unified_diff;
+
+    const BASE: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n";
+
+    fn two_hunk_file() -> DiffFile {
+        parse_unified_diff(
+            "\
+diff --git a/f.txt b/f.txt
+--- a/f.txt
++++ b/f.txt
+@@ -1,2 +1,2 @@
+-one
++ONE
+ two
+@@ -6,3 +6,4 @@
+ six
++six and a half
+ seven
+-eight
++EIGHT
+",
+        )
+        .remove(0)
+    }
+
+    #[test]
+    fn test_apply_selected_hunks() {
+        let file = two_hunk_file();
+        assert_eq!(
+            apply_selected_hunks(&file, &[0], BASE).unwrap(),
+            "ONE\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n"
+        );
+        assert_eq!(
+            apply_selected_hunks(&file, &[1], BASE).unwrap(),
+            "one\ntwo\nthree\nfour\nfive\nsix\nsix and a half\nseven\nEIGHT\n"
+        );
+        assert_eq!(
+            apply_selected_hunks(&file, &[1, 0], BASE).unwrap(),
+            "ONE\ntwo\nthree\nfour\nfive\nsix\nsix and a half\nseven\nEIGHT\n"
+        );
+        assert_eq!(apply_selected_hunks(&file, &[], BASE).unwrap(), BASE);
+    }
+
+    #[test]
+    fn test_apply_selected_hunks_errors() {
+        let file = two_hunk_file();
+        let err = apply_selected_hunks(&file, &[2], BASE).unwrap_err();
+        assert_eq!(err, "No hunk at index 2 in 'f.txt'");
+
+        let err = apply_selected_hunks(&file, &[0], "uno\ntwo\n").unwrap_err();
+        assert!(err.contains("line 1 differs"), "got: {err}");
+
+        let err = apply_selected_hunks(&file, &[1], "one\ntwo\n").unwrap_err();
+        assert!(err.contains("past the end"), "got: {err}");
+
+        let mut overlapping = file.clone();
+        overlapping.hunks[1].old_start = 2;
+        let err = apply_selected_hunks(&overlapping, &[0, 1], BASE).unwrap_err();
+        assert!(err.contains("overlap"), "got: {err}");
+    }
+
+    #[test]
+    fn test_apply_selected_hunks_insertion_and_missing_newline() {
+        let file = parse_unified_diff(
+            "\
+diff --git a/f.txt b/f.txt
+--- a/f.txt
++++ b/f.txt
+@@ -0,0 +1 @@
++zero
+@@ -2 +3 @@
+-two
++two, no newline
+\\ No newline at end of file
+",
+        )
+        .remove(0);
+        assert_eq!(
+            apply_selected_hunks(&file, &[0, 1], "one\ntwo\n").unwrap(),
+            "zero\none\ntwo, no newline"
+        );
+    }
+}
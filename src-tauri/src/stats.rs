@@ -0,0 +1,445 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::diff_parser::{DiffFile, FileStatus, LineType};
+
+/// Totals for a whole changeset, like `git diff --shortstat` plus rename and binary
+/// counts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChangeSummary {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub renames: usize,
+    pub binary_files: usize,
+}
+
+/// Computes the totals from parsed files, so they always agree with what is rendered.
+pub fn summarize(files: &[DiffFile]) -> ChangeSummary {
+    files.iter().fold(
+        ChangeSummary {
+            files_changed: files.len(),
+            ..Default::default()
+        },
+        |mut summary, file| {
+            summary.insertions += file.additions();
+            summary.deletions += file.deletions();
+            if file.status == FileStatus::Renamed {
+                summary.renames += 1;
+            }
+            if file.is_binary {
+                summary.binary_files += 1;
+            }
+            summary
+        },
+    )
+}
+
+/// Changes to the files sharing one extension.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExtStat {
+    /// Lowercased and without the dot; empty for files without one (`Makefile`,
+    /// `.gitignore`).
+    pub extension: String,
+    pub files: usize,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// Groups the per-file counts by extension, most changed lines first (ties by
+/// extension).
+pub fn stats_by_extension(files: &[DiffFile]) -> Vec<ExtStat> {
+    let mut stats: Vec<ExtStat> = Vec::new();
+    for file in files {
+        let extension = Path::new(&file.path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let index = match stats.iter().position(|s| s.extension == extension) {
+            Some(index) => index,
+            None => {
+                stats.push(ExtStat {
+                    extension,
+                    files: 0,
+                    additions: 0,
+                    deletions: 0,
+                });
+                stats.len() - 1
+            }
+        };
+        let stat = &mut stats[index];
+        stat.files += 1;
+        stat.additions += file.additions();
+        stat.deletions += file.deletions();
+    }
+    stats.sort_by(|a, b| {
+        (b.additions + b.deletions)
+            .cmp(&(a.additions + a.deletions))
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+    stats
+}
+
+/// Language names by file extension (lowercased, without the dot).
+const LANGUAGES_BY_EXTENSION: &[(&str, &str)] = &[
+    ("c", "C"),
+    ("h", "C"),
+    ("cc", "C++"),
+    ("cpp", "C++"),
+    ("cxx", "C++"),
+    ("hpp", "C++"),
+    ("cs", "C#"),
+    ("css", "CSS"),
+    ("go", "Go"),
+    ("html", "HTML"),
+    ("java", "Java"),
+    ("js", "JavaScript"),
+    ("jsx", "JavaScript"),
+    ("mjs", "JavaScript"),
+    ("json", "JSON"),
+    ("kt", "Kotlin"),
+    ("md", "Markdown"),
+    ("php", "PHP"),
+    ("py", "Python"),
+    ("rb", "Ruby"),
+    ("rs", "Rust"),
+    ("scss", "SCSS"),
+    ("sh", "Shell"),
+    ("bash", "Shell"),
+    ("sql", "SQL"),
+    ("swift", "Swift"),
+    ("toml", "TOML"),
+    ("ts", "TypeScript"),
+    ("tsx", "TypeScript"),
+    ("yaml", "YAML"),
+    ("yml", "YAML"),
+];
+
+/// Languages of files known by name rather than extension.
+const LANGUAGES_BY_FILE_NAME: &[(&str, &str)] = &[
+    ("Dockerfile", "Dockerfile"),
+    ("Makefile", "Makefile"),
+    ("CMakeLists.txt", "CMake"),
+];
+
+/// The language of the file at `path`, from its name or extension; `None` for files
+/// it cannot tell (plain text, unknown extensions).
+pub fn language_for_path(path: &str) -> Option<&'static str> {
+    let path = Path::new(path);
+    let file_name = path.file_name()?.to_str()?;
+    if let Some((_, language)) = LANGUAGES_BY_FILE_NAME.iter().find(|(n, _)| *n == file_name) {
+        return Some(language);
+    }
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    LANGUAGES_BY_EXTENSION
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
+
+/// The language with the most changed lines across `files`, ties going to the name
+/// that sorts first. Files of no known language (and binary files, which have no
+/// lines) do not count; `None` when nothing is left.
+pub fn dominant_language(files: &[DiffFile]) -> Option<String> {
+    let mut churn: Vec<(&str, usize)> = Vec::new();
+    for file in files {
+        let Some(language) = language_for_path(&file.path) else {
+            continue;
+        };
+        let changed = file.additions() + file.deletions();
+        match churn.iter_mut().find(|(l, _)| *l == language) {
+            Some((_, total)) => *total += changed,
+            None => churn.push((language, changed)),
+        }
+    }
+    churn
+        .into_iter()
+        .filter(|(_, changed)| *changed > 0)
+        .min_by(|(a, a_changed), (b, b_changed)| b_changed.cmp(a_changed).then(a.cmp(b)))
+        .map(|(language, _)| language.to_string())
+}
+
+/// How densely `file` changes along its length, for a minimap: its lines are split
+/// into `buckets` equal bins, each given an intensity from 0 (no changed line) to 255
+/// (the busiest bin). Positions are new-side line numbers, a deletion counting at the
+/// line after it, except in deleted files, which only have an old side.
+///
+/// A diff only shows how far its last hunk reaches, so that stands in for the file's
+/// length; with `DiffOptions::full_context` the bins cover the whole file.
+pub fn change_density(file: &DiffFile, buckets: usize) -> Vec<u8> {
+    if buckets == 0 {
+        return Vec::new();
+    }
+    let deleted = file.status == FileStatus::Deleted;
+
+    let mut positions: Vec<u32> = Vec::new();
+    let mut length = 0;
+    for hunk in &file.hunks {
+        let (start, count) = if deleted {
+            (hunk.old_start, hunk.old_count)
+        } else {
+            (hunk.new_start, hunk.new_count)
+        };
+        length = length.max(start + count.saturating_sub(1));
+
+        let mut next_new = hunk.new_start.max(1);
+        for line in &hunk.lines {
+            if let Some(n) = line.new_line_no {
+                next_new = n + 1;
+            }
+            match line.line_type {
+                LineType::Context => {}
+                LineType::Addition => positions.extend(line.new_line_no),
+                LineType::Deletion if deleted => positions.extend(line.old_line_no),
+                LineType::Deletion => positions.push(next_new),
+            }
+        }
+    }
+    let length = positions.iter().copied().fold(length, u32::max).max(1) as usize;
+
+    let mut counts = vec![0usize; buckets];
+    for position in positions {
+        let bucket = (position.max(1) as usize - 1) * buckets / length;
+        counts[bucket.min(buckets - 1)] += 1;
+    }
+    let busiest = counts.iter().copied().max().unwrap_or(0).max(1);
+    // Rounded up, so a bin with any change is never drawn empty
+    counts
+        .into_iter()
+        .map(|count| (count * 255).div_ceil(busiest) as u8)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_parser::parse_unified_diff;
+
+    #[test]
+    fn test_summarize_mixed_changes() {
+        let diff = "\
+diff --git a/file.txt b/file.txt
+index abc1234..def5678 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1,5 +1,5 @@
+ line one
+-line two old
++line two new
+ line three
+-line four old
++line four new
+";
+        assert_eq!(
+            summarize(&parse_unified_diff(diff)),
+            ChangeSummary {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 2,
+                renames: 0,
+                binary_files: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_summarize_multiple_files() {
+        let diff = "\
+diff --git a/a.txt b/a.txt
+index abc..def 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,3 @@
+ unchanged
+-old a
++new a
++extra a
+diff --git a/old_name.txt b/new_name.txt
+similarity index 100%
+rename from old_name.txt
+rename to new_name.txt
+diff --git a/image.png b/image.png
+new file mode 100644
+Binary files /dev/null and b/image.png differ
+";
+        assert_eq!(
+            summarize(&parse_unified_diff(diff)),
+            ChangeSummary {
+                files_changed: 3,
+                insertions: 2,
+                deletions: 1,
+                renames: 1,
+                binary_files: 1,
+            }
+        );
+        assert_eq!(summarize(&[]), ChangeSummary::default());
+    }
+
+    #[test]
+    fn test_stats_by_extension() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1,2 @@
+-old
++new
++more
+diff --git a/src/Main.RS b/src/Main.RS
+--- a/src/Main.RS
++++ b/src/Main.RS
+@@ -1 +1 @@
+-a
++b
+diff --git a/Cargo.toml b/Cargo.toml
+--- a/Cargo.toml
++++ b/Cargo.toml
+@@ -1 +1,2 @@
+ [package]
++edition = \"2021\"
+diff --git a/Makefile b/Makefile
+--- a/Makefile
++++ b/Makefile
+@@ -1 +1 @@
+-all:
++all: build
+diff --git a/.gitignore b/.gitignore
+--- a/.gitignore
++++ b/.gitignore
+@@ -1 +0,0 @@
+-target
+";
+        let stat = |extension: &str, files, additions, deletions| ExtStat {
+            extension: extension.to_string(),
+            files,
+            additions,
+            deletions,
+        };
+        assert_eq!(
+            stats_by_extension(&parse_unified_diff(diff)),
+            vec![
+                stat("rs", 2, 3, 2),
+                stat("", 2, 1, 2),
+                stat("toml", 1, 1, 0),
+            ]
+        );
+        assert!(stats_by_extension(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_dominant_language() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1,4 @@
+-a
++b
++c
++d
+diff --git a/web/app.ts b/web/app.ts
+--- a/web/app.ts
++++ b/web/app.ts
+@@ -1 +1,2 @@
+ a
++b
+diff --git a/web/view.tsx b/web/view.tsx
+--- a/web/view.tsx
++++ b/web/view.tsx
+@@ -1 +1 @@
+-a
++b
+diff --git a/notes.txt b/notes.txt
+--- a/notes.txt
++++ b/notes.txt
+@@ -1 +1,9 @@
+ a
++1
++2
++3
++4
++5
++6
++7
++8
+diff --git a/tools/gen.py b/tools/gen.py
+--- a/tools/gen.py
++++ b/tools/gen.py
+@@ -1 +1,2 @@
+-a
++b
++c
+";
+        let files = parse_unified_diff(diff);
+        // Rust 4 changed lines, TypeScript 3 across two files, Python 3; the text file
+        // has the most but no language
+        assert_eq!(dominant_language(&files), Some("Rust".to_string()));
+        // Tied at 3: the first name wins
+        assert_eq!(dominant_language(&files[1..]), Some("Python".to_string()));
+        assert_eq!(dominant_language(&files[3..4]), None);
+        assert_eq!(dominant_language(&[]), None);
+
+        assert_eq!(language_for_path("build/Dockerfile"), Some("Dockerfile"));
+        assert_eq!(language_for_path("SRC/MAIN.RS"), Some("Rust"));
+        assert_eq!(language_for_path("README"), None);
+    }
+
+    #[test]
+    fn test_change_density() {
+        // Full context: 40 lines, all the changes in the first five
+        let mut diff = String::from(
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,40 +1,40 @@\n",
+        );
+        for n in 1..=40 {
+            if n <= 5 {
+                diff.push_str(&format!("-old {n}\n+new {n}\n"));
+            } else {
+                diff.push_str(&format!(" line {n}\n"));
+            }
+        }
+        let file = parse_unified_diff(&diff).remove(0);
+        let density = change_density(&file, 4);
+        assert_eq!(density, vec![255, 0, 0, 0]);
+        let density = change_density(&file, 8);
+        assert_eq!(density[0], 255);
+        assert!(density[1..].iter().all(|&d| d == 0));
+
+        let file = parse_unified_diff(
+            "\
+diff --git a/b.txt b/b.txt
+--- a/b.txt
++++ b/b.txt
+@@ -1,2 +1,3 @@
+ a
++b
++c
+@@ -98,3 +99,3 @@
+ x
+-y
++z
+ end
+",
+        )
+        .remove(0);
+        // Line 101 sets the length: two changes at the top, two near the end
+        assert_eq!(change_density(&file, 4), vec![255, 0, 0, 255]);
+        assert!(change_density(&file, 0).is_empty());
+
+        let deleted = parse_unified_diff(
+            "\
+diff --git a/c.txt b/c.txt
+deleted file mode 100644
+--- a/c.txt
++++ /dev/null
+@@ -1,4 +0,0 @@
+-1
+-2
+-3
+-4
+",
+        )
+        .remove(0);
+        assert_eq!(change_density(&deleted, 2), vec![255, 255]);
+        assert_eq!(change_density(&DiffFile::default(), 3), vec![0, 0, 0]);
+    }
+}
@@ -0,0 +1,99 @@
+//! Throwaway git repositories for tests that need real git state.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub struct TempRepo {
+    pub path: PathBuf,
+}
+
+impl TempRepo {
+    /// Creates an empty (no commits) repository on branch `main`.
+    pub fn new(name: &str) -> Self {
+        let repo = TempRepo::empty_dir(name);
+        repo.git(&["init", "-q", "-b", "main"]);
+        repo
+    }
+
+    /// Creates a bare repository cloned from a non-bare one with a single commit.
+    pub fn bare(name: &str) -> Self {
+        let source = TempRepo::new(&format!("{}-source", name));
+        source.write("README.md", "hello\n");
+        source.commit_all("initial");
+
+        let repo = TempRepo::empty_dir(name);
+        let status = Command::new("git")
+            .args(["clone", "-q", "--bare"])
+            .arg(&source.path)
+            .arg(&repo.path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        repo
+    }
+
+    fn empty_dir(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "great-review-test-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempRepo { path }
+    }
+
+    pub fn path_str(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+
+    /// Runs git in the repository, panicking on failure, and returns stdout.
+    pub fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args([
+                "-c",
+                "init.defaultBranch=main",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .current_dir(&self.path)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    pub fn write(&self, file: &str, contents: &str) {
+        let path = self.path.join(file);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(path, contents).unwrap();
+    }
+
+    pub fn commit_all(&self, message: &str) {
+        self.git(&["add", "-A"]);
+        self.git(&["commit", "-q", "-m", message]);
+    }
+
+    pub fn join(&self, file: &str) -> PathBuf {
+        Path::new(&self.path).join(file)
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -54,11 +54,24 @@ function App() {
         if (cancelled) return;
 
         const remote = args.remote ?? undefined;
-        const repoInfo = await fetchRepoInfo(remote);
-        if (cancelled) return;
-        dispatch({ type: "SET_REPO_INFO", info: repoInfo });
+        const gitDir = args.git_dir ?? undefined;
+        // A diff file is reviewed without a repository
+        if (!args.diff_file) {
+          const repoInfo = await fetchRepoInfo(remote, gitDir);
+          if (cancelled) return;
+          dispatch({ type: "SET_REPO_INFO", info: repoInfo });
+        }
 
-        const files = await fetchDiff(args.range ?? undefined, remote);
+        const files = await fetchDiff(
+          {
+            range: args.range,
+            context_lines: args.context_lines,
+            whitespace: args.whitespace,
+            diff_file: args.diff_file,
+          },
+          remote,
+          gitDir
+        );
         if (cancelled) return;
         dispatch({ type: "SET_DIFF", files });
         setLoading(false);
@@ -223,6 +223,8 @@ function statusBadgeClass(status: FileStatus): string {
       return "file-status-badge deleted";
     case FileStatus.Renamed:
       return "file-status-badge renamed";
+    case FileStatus.Copied:
+      return "file-status-badge copied";
   }
 }
 
@@ -21,11 +21,12 @@ function makeHunk(overrides?: Partial<DiffHunk>): DiffHunk {
     new_start: 1,
     new_count: 4,
     lines: [
-      { content: "  context line", line_type: LineType.Context, old_line_no: 1, new_line_no: 1 },
-      { content: "  old line", line_type: LineType.Deletion, old_line_no: 2, new_line_no: null },
-      { content: "  new line", line_type: LineType.Addition, old_line_no: null, new_line_no: 2 },
-      { content: "  another ctx", line_type: LineType.Context, old_line_no: 3, new_line_no: 3 },
+      { content: "  context line", line_type: LineType.Context, old_line_no: 1, new_line_no: 1, no_newline: false, leading_whitespace: "  ", run_start: true, run_end: true, word_ranges: [], covered: null },
+      { content: "  old line", line_type: LineType.Deletion, old_line_no: 2, new_line_no: null, no_newline: false, leading_whitespace: "  ", run_start: true, run_end: true, word_ranges: [], covered: null },
+      { content: "  new line", line_type: LineType.Addition, old_line_no: null, new_line_no: 2, no_newline: false, leading_whitespace: "  ", run_start: true, run_end: true, word_ranges: [], covered: null },
+      { content: "  another ctx", line_type: LineType.Context, old_line_no: 3, new_line_no: 3, no_newline: false, leading_whitespace: "  ", run_start: true, run_end: true, word_ranges: [], covered: null },
     ],
+    is_whitespace_only: false,
     ...overrides,
   };
 }
@@ -37,7 +38,25 @@ function makeFile(path: string, hunkCount = 1): DiffFile {
       makeHunk({ header: `@@ -${i * 10 + 1},3 +${i * 10 + 1},4 @@` })
     );
   }
-  return { path, old_path: null, hunks, status: FileStatus.Modified };
+  return {
+    path,
+    old_path: null,
+    hunks,
+    status: FileStatus.Modified,
+    old_blob: null,
+    new_blob: null,
+    old_mode: null,
+    new_mode: null,
+    is_symlink: false,
+    old_link_target: null,
+    new_link_target: null,
+    similarity: null,
+    dissimilarity: null,
+    old_submodule_sha: null,
+    new_submodule_sha: null,
+    is_binary: false,
+    abs_path: null,
+  };
 }
 
 let idCounter = 0;
@@ -1,13 +1,348 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen, once } from "@tauri-apps/api/event";
 import { writeText } from "@tauri-apps/plugin-clipboard-manager";
-import { DiffFile, RepoInfo, StartupArgs } from "./types";
+import { CancellableDiff, ChangeSummary, ChangedFile, Comment, CommitMessage, ConflictFile, DiffFile, DiffOptions, DiffResult, DiffStreamComplete, ExtStat, FileOutline, FileStatus, Identity, Match, NormalizedRange, ParseProgress, Refs, RemoteBundle, RemoteEntry, RepoDiff, RepoInfo, RepoSpec, SearchFilter, SideBySideRow, StartupArgs, StashEntry, TreeNode, Vcs, WsIssue } from "./types";
 
-export async function fetchDiff(range?: string, remote?: string): Promise<DiffFile[]> {
-  return invoke<DiffFile[]>("get_diff", { range: range ?? null, remote: remote ?? null });
+export async function fetchDiff(
+  options: Partial<DiffOptions> = {},
+  remote?: string,
+  gitDir?: string,
+  vcs?: Vcs,
+  statusFilter?: FileStatus[]
+): Promise<DiffFile[]> {
+  const result = await invoke<DiffResult>("get_diff", {
+    options,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+    vcs: vcs ?? null,
+    statusFilter: statusFilter ?? null,
+  });
+  return result.files;
 }
 
-export async function fetchRepoInfo(remote?: string): Promise<RepoInfo> {
-  return invoke<RepoInfo>("get_repo_info_cmd", { remote: remote ?? null });
+/** Like fetchDiff, but also returns the verbatim patch git produced. */
+export async function fetchDiffWithRaw(
+  options: Partial<DiffOptions> = {},
+  remote?: string,
+  gitDir?: string,
+  vcs?: Vcs,
+  statusFilter?: FileStatus[]
+): Promise<DiffResult> {
+  return invoke<DiffResult>("get_diff", {
+    options: { ...options, include_raw: true },
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+    vcs: vcs ?? null,
+    statusFilter: statusFilter ?? null,
+  });
+}
+
+export async function fetchDiffCancellable(
+  requestId: number,
+  options: Partial<DiffOptions> = {},
+  remote?: string,
+  gitDir?: string,
+  statusFilter?: FileStatus[]
+): Promise<CancellableDiff> {
+  return invoke<CancellableDiff>("get_diff_cancellable", {
+    requestId,
+    options,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+    statusFilter: statusFilter ?? null,
+  });
+}
+
+export async function cancelDiff(requestId: number): Promise<boolean> {
+  return invoke<boolean>("cancel_diff", { requestId });
+}
+
+export async function clearDiffCache(): Promise<void> {
+  await invoke("clear_diff_cache");
+}
+
+export async function streamDiff(
+  onFile: (file: DiffFile) => void,
+  options: Partial<DiffOptions> = {},
+  remote?: string,
+  gitDir?: string,
+  onProgress?: (progress: ParseProgress) => void,
+  statusFilter?: FileStatus[]
+): Promise<number> {
+  const unlisten = await listen<DiffFile>("diff-file", (event) => onFile(event.payload));
+  const unlistenProgress = await listen<ParseProgress>("parse-progress", (event) =>
+    onProgress?.(event.payload)
+  );
+  let resolveComplete: (totalFiles: number) => void = () => {};
+  const complete = new Promise<number>((resolve) => {
+    resolveComplete = resolve;
+  });
+  const unlistenComplete = await once<DiffStreamComplete>("diff-complete", (event) =>
+    resolveComplete(event.payload.total_files)
+  );
+  try {
+    await invoke("get_diff_stream", {
+      options,
+      remote: remote ?? null,
+      gitDir: gitDir ?? null,
+      statusFilter: statusFilter ?? null,
+    });
+    return await complete;
+  } finally {
+    unlisten();
+    unlistenProgress();
+    unlistenComplete();
+  }
+}
+
+export async function fetchChangedFiles(range?: string, remote?: string, gitDir?: string): Promise<ChangedFile[]> {
+  return invoke<ChangedFile[]>("get_changed_files", {
+    range: range ?? null,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+  });
+}
+
+export async function fetchDiffOutline(range?: string, remote?: string, gitDir?: string): Promise<FileOutline[]> {
+  return invoke<FileOutline[]>("get_diff_outline", {
+    range: range ?? null,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+  });
+}
+
+export async function fetchStashes(gitDir?: string): Promise<StashEntry[]> {
+  return invoke<StashEntry[]>("list_stashes", { gitDir: gitDir ?? null });
+}
+
+export async function fetchConflicts(gitDir?: string): Promise<ConflictFile[]> {
+  return invoke<ConflictFile[]>("get_conflicts", { gitDir: gitDir ?? null });
+}
+
+export async function fetchStashDiff(stashRef?: string, gitDir?: string): Promise<DiffFile[]> {
+  return invoke<DiffFile[]>("get_stash_diff", { stashRef: stashRef ?? null, gitDir: gitDir ?? null });
+}
+
+export async function fetchBranchDiff(
+  base: string,
+  head: string,
+  remote?: string,
+  gitDir?: string
+): Promise<DiffFile[]> {
+  return invoke<DiffFile[]>("get_branch_diff", {
+    base,
+    head,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+  });
+}
+
+export async function fetchFilesDiff(left: string, right: string): Promise<DiffFile[]> {
+  return invoke<DiffFile[]>("diff_files", { left, right });
+}
+
+export async function fetchFileDiff(
+  path: string,
+  oldPath?: string | null,
+  options: Partial<DiffOptions> = {},
+  remote?: string,
+  gitDir?: string
+): Promise<DiffFile> {
+  return invoke<DiffFile>("get_file_diff", {
+    path,
+    oldPath: oldPath ?? null,
+    options,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+  });
+}
+
+export async function fetchRepoInfo(remote?: string, gitDir?: string): Promise<RepoInfo> {
+  return invoke<RepoInfo>("get_repo_info_cmd", { remote: remote ?? null, gitDir: gitDir ?? null });
+}
+
+export async function fetchRemoteBundle(
+  remote: string,
+  range?: string,
+  options?: Partial<DiffOptions>
+): Promise<RemoteBundle> {
+  return invoke<RemoteBundle>("get_remote_bundle", {
+    remote,
+    range: range ?? null,
+    options: options ?? null,
+  });
+}
+
+export async function fetchMultiDiff(repos: RepoSpec[]): Promise<RepoDiff[]> {
+  return invoke<RepoDiff[]>("get_multi_diff", { repos });
+}
+
+export async function fetchMergeBase(a: string, b: string, gitDir?: string): Promise<string> {
+  return invoke<string>("get_merge_base", { a, b, gitDir: gitDir ?? null });
+}
+
+export async function fetchBlobContent(sha: string, gitDir?: string): Promise<string> {
+  return invoke<string>("get_blob_content", { sha, gitDir: gitDir ?? null });
+}
+
+export async function fetchRefs(remote?: string, gitDir?: string): Promise<Refs> {
+  return invoke<Refs>("get_refs", { remote: remote ?? null, gitDir: gitDir ?? null });
+}
+
+export async function resolveRef(refStr: string, remote?: string, gitDir?: string): Promise<string> {
+  return invoke<string>("resolve_ref", { refStr, remote: remote ?? null, gitDir: gitDir ?? null });
+}
+
+export async function fetchDefaultBranch(remote?: string, gitDir?: string): Promise<string> {
+  return invoke<string>("get_default_branch", { remote: remote ?? null, gitDir: gitDir ?? null });
+}
+
+export async function fetchCommitMessages(
+  commit: string,
+  wholeRange?: boolean,
+  remote?: string,
+  gitDir?: string,
+  firstParent?: boolean
+): Promise<CommitMessage[]> {
+  return invoke<CommitMessage[]>("get_commit_message", {
+    commit,
+    wholeRange: wholeRange ?? null,
+    firstParent: firstParent ?? null,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+  });
+}
+
+export async function fetchCommitCount(
+  range: string,
+  remote?: string,
+  gitDir?: string,
+  firstParent?: boolean
+): Promise<number> {
+  return invoke<number>("count_commits", {
+    range,
+    firstParent: firstParent ?? null,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+  });
+}
+
+export async function fetchTipAuthor(
+  range: string,
+  remote?: string,
+  gitDir?: string
+): Promise<Identity> {
+  return invoke<Identity>("get_tip_author", {
+    range,
+    remote: remote ?? null,
+    gitDir: gitDir ?? null,
+  });
+}
+
+export async function normalizeRange(range: string, gitDir?: string): Promise<NormalizedRange> {
+  return invoke<NormalizedRange>("normalize_range", { range, gitDir: gitDir ?? null });
+}
+
+export async function fetchRemotes(gitDir?: string): Promise<RemoteEntry[]> {
+  return invoke<RemoteEntry[]>("get_remotes", { gitDir: gitDir ?? null });
+}
+
+export async function applyPatch(diffText: string, checkOnly: boolean, gitDir?: string): Promise<void> {
+  await invoke("apply_patch", { diffText, checkOnly, gitDir: gitDir ?? null });
+}
+
+export async function searchInDiff(
+  files: DiffFile[],
+  query: string,
+  caseSensitive: boolean,
+  filter?: SearchFilter
+): Promise<Match[]> {
+  return invoke<Match[]>("search_in_diff", { files, query, caseSensitive, filter: filter ?? null });
+}
+
+export async function findTrailingWhitespace(files: DiffFile[]): Promise<WsIssue[]> {
+  return invoke<WsIssue[]>("find_trailing_whitespace", { files });
+}
+
+export async function copyHunk(fileIndex: number, hunkIndex: number, files: DiffFile[]): Promise<string> {
+  return invoke<string>("copy_hunk", { fileIndex, hunkIndex, files });
+}
+
+export async function applySelectedHunks(file: DiffFile, hunkIndices: number[], baseContent: string): Promise<string> {
+  return invoke<string>("apply_selected_hunks", { file, hunkIndices, baseContent });
+}
+
+export async function openInEditor(path: string, lineNo?: number, gitDir?: string): Promise<void> {
+  await invoke("open_in_editor", { path, lineNo: lineNo ?? null, gitDir: gitDir ?? null });
+}
+
+export async function exportReviewMarkdown(files: DiffFile[]): Promise<string> {
+  return invoke<string>("export_review_markdown", { files });
+}
+
+export async function exportReviewHtml(files: DiffFile[], repoInfo: RepoInfo): Promise<string> {
+  return invoke<string>("export_review_html", { files, repoInfo });
+}
+
+export async function fetchSideBySide(file: DiffFile): Promise<SideBySideRow[]> {
+  return invoke<SideBySideRow[]>("get_side_by_side", { file });
+}
+
+export async function fetchDiffSummary(files: DiffFile[]): Promise<ChangeSummary> {
+  return invoke<ChangeSummary>("get_diff_summary", { files });
+}
+
+export async function fetchStatsByExtension(files: DiffFile[]): Promise<ExtStat[]> {
+  return invoke<ExtStat[]>("get_stats_by_extension", { files });
+}
+
+export async function annotateCoverage(files: DiffFile[], lcovPath: string): Promise<DiffFile[]> {
+  return invoke<DiffFile[]>("annotate_coverage", { files, lcovPath });
+}
+
+export async function fetchDominantLanguage(files: DiffFile[]): Promise<string | null> {
+  return invoke<string | null>("get_dominant_language", { files });
+}
+
+export async function fetchChangeDensity(file: DiffFile, buckets: number): Promise<number[]> {
+  return invoke<number[]>("get_change_density", { file, buckets });
+}
+
+export async function fetchFileTree(files: DiffFile[]): Promise<TreeNode> {
+  return invoke<TreeNode>("get_file_tree", { files });
+}
+
+export async function saveReview(comments: Comment[], gitDir?: string): Promise<void> {
+  await invoke("save_review", { comments, gitDir: gitDir ?? null });
+}
+
+export async function loadReview(gitDir?: string): Promise<Comment[]> {
+  return invoke<Comment[]>("load_review", { gitDir: gitDir ?? null });
+}
+
+export async function markFileViewed(
+  path: string,
+  newBlob: string | null,
+  viewed: boolean,
+  gitDir?: string
+): Promise<void> {
+  await invoke("mark_file_viewed", { path, newBlob, viewed, gitDir: gitDir ?? null });
+}
+
+export async function loadViewedFiles(files: DiffFile[], gitDir?: string): Promise<string[]> {
+  return invoke<string[]>("load_viewed_files", { files, gitDir: gitDir ?? null });
+}
+
+export async function reanchorReview(comments: Comment[], files: DiffFile[]): Promise<Comment[]> {
+  return invoke<Comment[]>("reanchor_review", { comments, files });
+}
+
+export async function fetchGithubPrDiff(url: string, token?: string): Promise<DiffFile[]> {
+  return invoke<DiffFile[]>("get_github_pr_diff", { url, token: token ?? null });
+}
+
+export async function fetchGitlabMrDiff(url: string, token?: string): Promise<DiffFile[]> {
+  return invoke<DiffFile[]>("get_gitlab_mr_diff", { url, token: token ?? null });
 }
 
 export async function fetchStartupArgs(): Promise<StartupArgs> {
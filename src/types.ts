@@ -9,6 +9,60 @@ export enum FileStatus {
   Modified = "Modified",
   Deleted = "Deleted",
   Renamed = "Renamed",
+  Copied = "Copied",
+}
+
+export enum WhitespaceMode {
+  Show = "Show",
+  IgnoreAll = "IgnoreAll",
+  IgnoreChange = "IgnoreChange",
+  IgnoreEol = "IgnoreEol",
+}
+
+export enum DiffAlgorithm {
+  Myers = "Myers",
+  Minimal = "Minimal",
+  Patience = "Patience",
+  Histogram = "Histogram",
+}
+
+export enum Vcs {
+  Git = "Git",
+  Hg = "Hg",
+}
+
+/** Mirrors the externally tagged Rust `DiffTarget` enum. */
+export type DiffTarget =
+  | "Range"
+  | { StagedAgainst: string }
+  | "AgainstUpstream"
+  | { MergeBaseWith: string };
+
+export interface DiffOptions {
+  range: string | null;
+  context_lines: number | null;
+  whitespace: WhitespaceMode;
+  algorithm: DiffAlgorithm | null;
+  rename_threshold: number | null;
+  no_renames: boolean;
+  pathspecs: string[];
+  reverse: boolean;
+  fetch_before_diff: boolean;
+  target: DiffTarget;
+  full_context: boolean;
+  merge_duplicate_files: boolean;
+  strip_ansi: boolean;
+  /** `[extension, is_binary]` pairs. Forcing text needs a local git repository. */
+  binary_overrides: [string, boolean][];
+  include_raw: boolean;
+  diff_file: string | null;
+  word_diff: boolean;
+  normalize_backslashes: boolean;
+  merge_parent: number | null;
+  strict: boolean;
+  /** `[key, value]` pairs passed as `git -c key=value`. */
+  config_overrides: [string, string][];
+  indent_heuristic: boolean;
 }
 
 export interface DiffLine {
@@ -16,6 +70,13 @@ export interface DiffLine {
   line_type: LineType;
   old_line_no: number | null;
   new_line_no: number | null;
+  no_newline: boolean;
+  leading_whitespace: string;
+  run_start: boolean;
+  run_end: boolean;
+  /** `[start, end)` byte offsets of changed words; only set for word diffs. */
+  word_ranges: [number, number][];
+  covered: boolean | null;
 }
 
 export interface DiffHunk {
@@ -25,6 +86,7 @@ export interface DiffHunk {
   new_start: number;
   new_count: number;
   lines: DiffLine[];
+  is_whitespace_only: boolean;
 }
 
 export interface DiffFile {
@@ -32,17 +94,206 @@ export interface DiffFile {
   old_path: string | null;
   hunks: DiffHunk[];
   status: FileStatus;
+  old_blob: string | null;
+  new_blob: string | null;
+  old_mode: string | null;
+  new_mode: string | null;
+  is_symlink: boolean;
+  old_link_target: string | null;
+  new_link_target: string | null;
+  similarity: number | null;
+  dissimilarity: number | null;
+  old_submodule_sha: string | null;
+  new_submodule_sha: string | null;
+  is_binary: boolean;
+  abs_path: string | null;
+}
+
+export interface SideBySideRow {
+  old: DiffLine | null;
+  new: DiffLine | null;
+}
+
+export interface ChangeSummary {
+  files_changed: number;
+  insertions: number;
+  deletions: number;
+  renames: number;
+  binary_files: number;
+}
+
+export interface ExtStat {
+  /** Lowercased, without the dot; "" for files without an extension. */
+  extension: string;
+  files: number;
+  additions: number;
+  deletions: number;
+}
+
+export interface ChangedFile {
+  path: string;
+  old_path: string | null;
+  status: FileStatus;
+}
+
+export enum SearchFilter {
+  All = "All",
+  Additions = "Additions",
+  Deletions = "Deletions",
+}
+
+export interface Match {
+  file_index: number;
+  hunk_index: number;
+  line_index: number;
+  ranges: [number, number][];
+}
+
+export interface WsIssue {
+  file_index: number;
+  hunk_index: number;
+  line_index: number;
 }
 
 export interface RepoInfo {
   name: string;
   branch: string;
+  head_sha: string | null;
+  dirty: boolean | null;
+  path: string;
+  bare: boolean;
+  sparse: boolean;
+  sparse_patterns: string[];
+  working_tree_warning: string | null;
+}
+
+export interface Refs {
+  branches: string[];
+  tags: string[];
+  current: string;
+}
+
+export interface RangeEndpoint {
+  ref_name: string;
+  sha: string;
+}
+
+export interface NormalizedRange {
+  range: string;
+  from: RangeEndpoint;
+  to: RangeEndpoint | null;
+  symmetric: boolean;
+}
+
+export interface StashEntry {
+  name: string;
+  sha: string;
+  message: string;
+}
+
+export interface RemoteEntry {
+  name: string;
+  fetch_url: string;
+  push_url: string;
+}
+
+export interface RemoteBundle {
+  repo_info: RepoInfo;
+  files: DiffFile[];
+}
+
+export interface DiffResult {
+  files: DiffFile[];
+  raw: string | null;
+  ignored_count: number;
+}
+
+export interface CancellableDiff extends DiffResult {
+  request_id: number;
+}
+
+export interface DiffStreamComplete {
+  total_files: number;
+  ignored_count: number;
+}
+
+export interface ParseProgress {
+  files_done: number;
+  files_total: number;
+}
+
+export interface CommitMessage {
+  sha: string;
+  subject: string;
+  body: string;
+}
+
+export interface Identity {
+  name: string;
+  email: string;
+}
+
+export interface ConflictRegion {
+  start: number;
+  base: number | null;
+  separator: number;
+  end: number;
+}
+
+export interface ConflictFile {
   path: string;
+  regions: ConflictRegion[];
+}
+
+export interface TreeNode {
+  name: string;
+  children: TreeNode[];
+  file: number | null;
+}
+
+export interface HunkOutline {
+  header: string;
+  section: string | null;
+  old_start: number;
+  new_start: number;
+}
+
+export interface FileOutline {
+  path: string;
+  status: FileStatus;
+  hunks: HunkOutline[];
+}
+
+export interface RepoSpec {
+  path_or_remote: string;
+  range: string | null;
+}
+
+export interface RepoDiff {
+  path_or_remote: string;
+  repo_info: RepoInfo | null;
+  files: DiffFile[];
+  error: string | null;
 }
 
 export interface StartupArgs {
   range: string | null;
   remote: string | null;
+  git_dir: string | null;
+  context_lines: number | null;
+  whitespace: WhitespaceMode;
+  diff_file: string | null;
+}
+
+export interface Comment {
+  file_path: string;
+  line_no: number | null;
+  hunk_index: number;
+  body: string;
+  created_at: string;
+  resolved: boolean;
+  anchor_content: string | null;
+  orphaned: boolean;
 }
 
 export type ReviewDecision = "approved" | "commented" | "rejected";